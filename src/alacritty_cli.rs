@@ -3,12 +3,13 @@
 use std::env;
 use std::process::Command;
 
-fn current_dir() -> String {
-    let dir = env::current_dir()
-        .unwrap_or_else(|e| crate::die!("cannot determine current directory: {e}"));
+use crate::{Error, Result};
+
+fn current_dir() -> Result<String> {
+    let dir = env::current_dir().map_err(|e| Error::io("cannot determine current directory", e))?;
     dir.to_str()
-        .unwrap_or_else(|| crate::die!("current directory path contains invalid UTF-8"))
-        .to_string()
+        .map(str::to_string)
+        .ok_or_else(|| Error::Process("current directory path contains invalid UTF-8".to_string()))
 }
 
 fn shell() -> String {
@@ -27,13 +28,15 @@ impl AlaCli {
 
     /// Spawns a new alacritty window running `cmd` wrapped in the user's shell.
     /// Returns an `AlaWindow` whose `pid` identifies the alacritty process.
-    pub fn spawn_window(&self, cmd: &str) -> AlaWindow {
+    // the window outlives us; we only track its pid
+    #[allow(clippy::zombie_processes)]
+    pub fn spawn_window(&self, cmd: &str) -> Result<AlaWindow> {
         let child = Command::new("alacritty")
-            .args(["--working-directory", &current_dir()])
+            .args(["--working-directory", &current_dir()?])
             .args(["-e", &shell(), "-c", cmd])
             .spawn()
-            .unwrap_or_else(|e| crate::die!("failed to spawn alacritty: {e}"));
-        AlaWindow { pid: child.id() }
+            .map_err(|e| Error::io("failed to spawn alacritty", e))?;
+        Ok(AlaWindow { pid: child.id() })
     }
 }
 
@@ -0,0 +1,107 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+
+use mato::{
+    config::Config,
+    create_chain, die, establish_log_level, mato_dbg, mato_trc, opt_flag, opt_val,
+    options::DeviceOpts,
+    opts,
+    render::device::{self, Device},
+    render::epub,
+    Error,
+};
+
+/// single entry point modeled on groff: reads markdown from a file or standard
+/// input and renders it for the device selected via `-T<device>`, with a default
+/// of `pdf`.
+fn main() -> std::io::Result<()> {
+    let mut p = opts::Parser::new();
+    let opt_device = p.add_opt(opt_val!(
+        "T",
+        "device",
+        "Backend device to be used for rendering, see --list-devices.",
+        "pdf"
+    ));
+    let opt_output = p.add_opt(opt_val!(
+        "o",
+        "output",
+        "Output file, '-' for standard out. Defaults to <input>.pdf for pdf, <input>.epub for epub, standard out otherwise.",
+        ""
+    ));
+    let device_opts = DeviceOpts::add(&mut p);
+    let opt_standard_gropdf = p.add_opt(opt_flag!(
+        "p",
+        "standard-gropdf",
+        "Use standard gropdf instead of gropdf_zig even if found in PATH."
+    ));
+    let opt_gropdf_zig_debug = p.add_opt(opt_flag!(
+        "z",
+        "gropdf-zig-debug",
+        "Pass -d to gropdf_zig for debug output."
+    ));
+
+    let parsed_opts = p.parse(env::args().collect());
+    parsed_opts.handle_standard_flags("mato", env!("CARGO_PKG_VERSION"));
+    mato::log::set_log_level(establish_log_level(&parsed_opts));
+    if let Some(listing) = device_opts.listing(&parsed_opts) {
+        print!("{listing}");
+        return Ok(());
+    }
+
+    let device = device::find(&opt_device.val(&parsed_opts)).unwrap_or_else(|e| die!("{e}"));
+    let mut config = device_opts
+        .configure(&parsed_opts, device)
+        .unwrap_or_else(|e| die!("{e}"));
+    mato_dbg!("source files:\t\t{:?}", config.source_files);
+    config.use_standard_gropdf |= opt_standard_gropdf.is_set(&parsed_opts);
+    config.gropdf_zig_debug |= opt_gropdf_zig_debug.is_set(&parsed_opts);
+    mato_trc!("{:?}", config);
+
+    let output = match opt_output.val(&parsed_opts).as_str() {
+        "" if device.binary && !config.source_file.is_empty() => {
+            mato::replace_file_extension(&config.source_file, device.name)
+                .display()
+                .to_string()
+        }
+        "" => "-".to_string(),
+        output => output.to_string(),
+    };
+    run(&config, device, &output).unwrap_or_else(|e| die!("{e}"));
+    Ok(())
+}
+
+/// renders the input for `device` and writes the result to `output`
+fn run(config: &Config, device: &Device, output: &str) -> mato::Result<()> {
+    let inputs = mato::read_inputs(&config.source_files)?;
+    let result = render(config, device, &inputs)?;
+    mato_dbg!("writing to:\t\t{}", output);
+    if output == "-" {
+        io::stdout()
+            .write_all(&result)
+            .map_err(|e| Error::io("cannot write to standard out", e))
+    } else {
+        fs::write(output, &result).map_err(|e| Error::io(format!("cannot write '{output}'"), e))
+    }
+}
+
+/// transforms `inputs`, pairs of file name and contents, for `device`
+fn render(config: &Config, device: &Device, inputs: &[(String, String)]) -> mato::Result<Vec<u8>> {
+    let mut chain = create_chain(config)?;
+    if device.name == "epub" {
+        let tree = mato::process_all(&mut chain, config, inputs)?;
+        return epub::package(config, tree);
+    }
+    let mut render = device.renderer(config)?;
+    let output = mato::transform_all(&mut render, &mut chain, config, inputs)?;
+    if device.name != "pdf" {
+        return Ok(format!("{output}\n").into_bytes());
+    }
+    let gropdf_zig = if config.use_standard_gropdf {
+        None
+    } else {
+        mato::find_in_path("gropdf_zig")
+    };
+    let (pdf, _) = mato::grotopdf(config, &output, gropdf_zig.as_deref())?;
+    Ok(pdf)
+}
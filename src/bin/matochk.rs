@@ -2,6 +2,7 @@ use std::path::{Path, PathBuf};
 
 fn main() {
     check_groff();
+    check_tbl();
     println!();
 
     let site_font_devpdf = find_site_font_devpdf();
@@ -89,6 +90,16 @@ fn check_groff() {
     }
 }
 
+fn check_tbl() {
+    match which("tbl") {
+        Some(path) => println!("✅ tbl: {}", path.display()),
+        None => {
+            println!("❌ tbl: NOT FOUND (needed to typeset tables)");
+            std::process::exit(1);
+        }
+    }
+}
+
 /// Returns (all_found, has_warnings).
 /// has_warnings is true when any font is found outside the local site-font dir.
 fn check_font_family(
@@ -205,10 +216,9 @@ fn find_devpdf_dirs(site_font_devpdf: Option<&Path>) -> Vec<PathBuf> {
 
 fn which(name: &str) -> Option<PathBuf> {
     std::env::var_os("PATH")
-        .map(|path_var| {
+        .and_then(|path_var| {
             std::env::split_paths(&path_var)
                 .map(|dir| dir.join(name))
                 .find(|p| p.is_file())
         })
-        .flatten()
 }
@@ -1,7 +1,7 @@
 use std::env;
 
 use mato::term_cli::TermCli;
-use mato::{die, establish_log_level, mato_dbg, opt_val, opts};
+use mato::{die, establish_log_level, mato_dbg, opt_val, opts, Error};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const PROG_NAME: &str = "matoedit";
@@ -35,56 +35,60 @@ fn main() -> std::io::Result<()> {
     let source_file = args.pop().unwrap();
     mato_dbg!("source file: {}", source_file);
 
-    // ACQUIRE cli handle, panics if not supported
-    let term_cli = TermCli::get();
+    run(&source_file, &lang).unwrap_or_else(|e| die!("{e}"));
+    Ok(())
+}
+
+/// lays out editor, `matopdf` and viewer for `source_file` and waits for the editor to quit
+fn run(source_file: &str, lang: &str) -> mato::Result<()> {
+    // ACQUIRE cli handle, fails if not supported
+    let term_cli = TermCli::get()?;
 
     // CHECK required tools are available
     if mato::find_in_path("groff").is_none() {
-        die!("groff not found in PATH");
+        return Err(Error::Config("groff not found in PATH".to_string()));
     }
     if let TermCli::WezTerm = term_cli {
         if mato::find_in_path("termpdf.py").is_none() {
-            die!("termpdf.py not found in PATH");
+            return Err(Error::Config("termpdf.py not found in PATH".to_string()));
         }
     }
 
     // we create the source file in any case, so that we can
     // immediately transform it.
-    mato::create_if_not_exists(&source_file);
+    mato::create_if_not_exists(source_file)?;
 
     // OPEN editor
-    let editor_handle = term_cli.get_active_windows_handle();
+    let editor_handle = term_cli.get_active_windows_handle()?;
     mato_dbg!("editor handle: {}", editor_handle);
 
     // we need to figure out the target file name for termpdf to call on
-    let target_file_path = mato::replace_file_extension(&source_file, "pdf");
+    let target_file_path = mato::replace_file_extension(source_file, "pdf");
     mato_dbg!("target file: {}", target_file_path.display());
 
     // CREATE empty pdf if none is there already
-    mato::create_empty_if_not_exists(&format!("{}", target_file_path.display()));
+    mato::create_empty_if_not_exists(&format!("{}", target_file_path.display()))?;
 
     // LAUNCH `termpdf.py` — right-split of editor first so its full height
     // is not constrained by the log pane below
     let termpdf_handle =
-        term_cli.exec_termpdf(&format!("{}", target_file_path.display()), editor_handle);
+        term_cli.exec_termpdf(&format!("{}", target_file_path.display()), editor_handle)?;
     mato_dbg!("termpdf handle: {}", termpdf_handle);
 
     // LAUNCH matopdf — bottom-split of editor so the log pane sits only
     // under the editor column, not spanning the full window width
-    let mato_handle = term_cli.exec_matopdf(&source_file, &lang, true, editor_handle);
+    let mato_handle = term_cli.exec_matopdf(source_file, lang, true, editor_handle)?;
     mato_dbg!("mato handle: {}", mato_handle);
 
     // FOCUS the EDITOR
     // split and spawn move focus to the newly created panes,
     // so we need to refocus on the editor
-    term_cli.focus(editor_handle);
+    term_cli.focus(editor_handle)?;
 
     // OPEN editor and block on call
-    term_cli.open_editor(&source_file);
+    term_cli.open_editor(source_file)?;
 
     // CLOSE everything
-    term_cli.close(mato_handle);
-    term_cli.close(termpdf_handle);
-
-    Ok(())
+    term_cli.close(mato_handle)?;
+    term_cli.close(termpdf_handle)
 }
@@ -1,34 +1,48 @@
 use std::env;
 
-use std::path::Path;
 use std::time::Instant;
 
 use mato::config::Config;
-use mato::process::identity;
-use mato::render::markdown;
-use mato::{die, mato_dbg, mato_trc, Render};
+use mato::options::DeviceOpts;
+use mato::render::device;
+use mato::{create_chain, die, establish_log_level, mato_dbg, mato_trc, opts};
 
+/// formats markdown like `mato -T md`: reads the given files, or standard
+/// input, and writes the formatted markdown to standard out
 fn main() -> std::io::Result<()> {
-    let config = Config::from(env::args().collect())?;
-    mato_trc!("config: {:#?}", config);
-
-    // open source file to be able watch it (we need a file descriptor)
-    mato_dbg!("source file:\t\t{}", &config.source_file);
-    if !config.source_file.is_empty() && !Path::new(&config.source_file).exists() {
-        die!("Could not open source file: {}", config.source_file);
+    let mut p = opts::Parser::new();
+    let device_opts = DeviceOpts::add(&mut p);
+
+    let parsed_opts = p.parse(env::args().collect());
+    parsed_opts.handle_standard_flags("matofmt", env!("CARGO_PKG_VERSION"));
+    mato::log::set_log_level(establish_log_level(&parsed_opts));
+    if let Some(listing) = device_opts.listing(&parsed_opts) {
+        print!("{listing}");
+        return Ok(());
     }
-    let input = mato::read_input(&config.source_file);
-    println!("{}", matofmt(&config, &input));
+
+    let device = device::find("md").unwrap_or_else(|e| die!("{e}"));
+    let config = device_opts
+        .configure(&parsed_opts, device)
+        .unwrap_or_else(|e| die!("{e}"));
+    mato_trc!("config: {:#?}", config);
+    mato_dbg!("source files:\t\t{:?}", config.source_files);
+    let inputs = mato::read_inputs(&config.source_files).unwrap_or_else(|e| die!("{e}"));
+    println!(
+        "{}",
+        matofmt(&config, &inputs).unwrap_or_else(|e| die!("{e}"))
+    );
     Ok(())
 }
 
-fn matofmt(config: &Config, input: &str) -> String {
+/// formats `inputs`, pairs of file name and contents
+fn matofmt(config: &Config, inputs: &[(String, String)]) -> mato::Result<String> {
     let start = Instant::now();
-    let mut processor = identity::Identity {};
-    let mut renderer: Box<dyn Render + '_> = Box::new(markdown::Renderer::new());
-    let output = mato::transform(&mut renderer, &mut processor, config, input);
+    let mut chain = create_chain(config)?;
+    let mut renderer = device::find("md")?.renderer(config)?;
+    let output = mato::transform_all(&mut renderer, &mut chain, config, inputs)?;
     mato_dbg!("transformed in:\t\t{:?}", start.elapsed());
-    output
+    Ok(output)
 }
 
 #[cfg(test)]
@@ -36,7 +50,7 @@ mod tests {
     use mato::config::Config;
 
     fn matofmt(input: &str) -> String {
-        super::matofmt(&Config::default(), input)
+        super::matofmt(&Config::default(), &[(String::new(), input.to_string())]).unwrap()
     }
 
     #[test]
@@ -1,69 +1,65 @@
 use std::env;
 
 use mato::{
-    config::Config, create_default_chain, die, establish_log_level, mato_dbg, mato_trc, opt_flag,
-    opt_val, opts, process::chain::Chain, render::groff, Render,
+    config::Config,
+    create_chain, die, establish_log_level, mato_dbg, mato_trc, opt_val,
+    options::DeviceOpts,
+    opts,
+    render::device::{self, Device},
+    Error,
 };
 
 /// command close to groffs idea, which renders output to
 /// standard out and is configured via `-T<device>`, with
-/// a default of `mom`.
+/// a default of `mom`. devices writing binary files are
+/// left to `mato`.
 fn main() -> std::io::Result<()> {
-    let mut config = Config::default();
     let mut p = opts::Parser::new();
-    let opt_dump_dot_file = p.add_opt(opt_flag!(
-        "X",
-        "dump-dot-file",
-        "Write graphviz dot representation of parse tree to file."
-    ));
-    let opt_lang = p.add_opt(opt_val!("l", "lang", "Set document language.", "den"));
     let opt_device = p.add_opt(opt_val!(
         "T",
         "device",
-        "Backend device to be used for rendering.",
+        "Backend device to be used for rendering, one of the text devices of --list-devices.",
         "mom"
     ));
+    let device_opts = DeviceOpts::add(&mut p);
 
     let parsed_opts = p.parse(env::args().collect());
-    parsed_opts.handle_standard_flags("mato", env!("CARGO_PKG_VERSION"));
+    parsed_opts.handle_standard_flags("matogro", env!("CARGO_PKG_VERSION"));
     mato::log::set_log_level(establish_log_level(&parsed_opts));
-    if parsed_opts.params.is_empty() {
-        die!("no markdown input file provided! please provide one.");
+    if let Some(listing) = device_opts.listing(&parsed_opts) {
+        print!("{listing}");
+        return Ok(());
     }
 
-    config.lang = opt_lang.val(&parsed_opts);
-
-    config.dump_dot_file = opt_dump_dot_file.is_set(&parsed_opts);
-
-    config.source_file = parsed_opts.params.first().unwrap().clone();
-    mato_dbg!("source file:\t\t{}", &config.source_file);
-
+    let device = text_device(&opt_device.val(&parsed_opts)).unwrap_or_else(|e| die!("{e}"));
+    let config = device_opts
+        .configure(&parsed_opts, device)
+        .unwrap_or_else(|e| die!("{e}"));
+    mato_dbg!("source files:\t\t{:?}", config.source_files);
     mato_trc!("{:?}", config);
 
-    let mut render: Box<dyn Render + '_>;
-    let mut chain: Chain;
-    let device = opt_device.val(&parsed_opts);
-    match device.as_str() {
-        "mom" => {
-            chain = create_default_chain(&config, true);
-            render = Box::new(groff::mom::new(&config));
-        }
-        "man" => {
-            chain = create_default_chain(&config, true);
-            render = Box::new(groff::man::new());
-        }
-        "mdoc" => {
-            chain = create_default_chain(&config, false);
-            render = Box::new(groff::mandoc::new());
-        }
-        _ => {
-            die!("Unknown device: {}", device);
-        }
-    };
-    let input = mato::read_input(&config.source_file);
-    println!(
-        "{}",
-        mato::transform(&mut render, &mut chain, &config, &input)
-    );
+    match matogro(&config, device) {
+        Ok(output) => println!("{output}"),
+        Err(e) => die!("{e}"),
+    }
     Ok(())
 }
+
+/// looks up the device named `name`, which needs to render text
+fn text_device(name: &str) -> mato::Result<&'static Device> {
+    let device = device::find(name)?;
+    if device.binary {
+        return Err(Error::Config(format!(
+            "device {name} writes a binary file, use mato -T {name} instead"
+        )));
+    }
+    Ok(device)
+}
+
+/// renders the source files for `device`
+fn matogro(config: &Config, device: &Device) -> mato::Result<String> {
+    let mut chain = create_chain(config)?;
+    let mut render = device.renderer(config)?;
+    let inputs = mato::read_inputs(&config.source_files)?;
+    mato::transform_all(&mut render, &mut chain, config, &inputs)
+}
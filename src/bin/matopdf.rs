@@ -5,15 +5,19 @@ use std::fs;
 
 use std::time::Instant;
 
-use mato::config::Config;
-use mato::create_default_chain;
+use mato::config::{file, Config};
+use mato::create_chain;
 use mato::die;
 use mato::establish_log_level;
 use mato::mato_dbg;
+use mato::mato_err;
 use mato::mato_inf;
 use mato::opt_flag;
 use mato::opt_val;
 use mato::opts;
+use mato::process::external;
+use mato::process::registry::{self, DEFAULT_PROCESSORS};
+use mato::Error;
 use mato::Render;
 use mato::{render::groff, watch};
 
@@ -25,7 +29,35 @@ fn main() -> std::io::Result<()> {
     let mut p = opts::Parser::new();
 
     let opt_lang = p.add_opt(opt_val!("l", "lang", "Set document language.", "den"));
+    let opt_filter = p.add_opt(opt_val!(
+        "F",
+        "filter",
+        "Pipe the tree through external filter commands, separated by commas.",
+        ""
+    ));
 
+    let opt_document_filters = p.add_opt(opt_flag!(
+        "A",
+        "document-filters",
+        "Also run the external filters named in the meta data of the document."
+    ));
+    let opt_process = p.add_opt(opt_val!(
+        "P",
+        "process",
+        "Processors to run, separated by commas. 'default' stands for the usual ones.",
+        ""
+    ));
+    let opt_no_process = p.add_opt(opt_val!(
+        "N",
+        "no-process",
+        "Processors not to run, separated by commas.",
+        ""
+    ));
+    let opt_list_processors = p.add_opt(opt_flag!(
+        "L",
+        "list-processors",
+        "List the available processors and exit."
+    ));
     let opt_watch = p.add_opt(opt_flag!(
         "w",
         "watch",
@@ -65,15 +97,38 @@ fn main() -> std::io::Result<()> {
     let parsed_opts = p.parse(env::args().collect());
     parsed_opts.handle_standard_flags("matopdf", env!("CARGO_PKG_VERSION"));
     mato::log::set_log_level(establish_log_level(&parsed_opts));
+    if opt_list_processors.is_set(&parsed_opts) {
+        print!("{}", registry::describe());
+        return Ok(());
+    }
 
-    // TODO support multiple markdown input files
     if parsed_opts.params.is_empty() {
         die!("no markdown input file provided! please provide one.");
     }
-    config.source_file = parsed_opts.params.first().unwrap().clone();
-    mato_dbg!("source file:\t\t{}", &config.source_file);
+    // further files are joined to the first one, which names the output
+    config.source_files = parsed_opts.params.clone();
+    config.source_file = config.source_files[0].clone();
+    mato_dbg!("source files:\t\t{}", config.source_files.join(" "));
 
+    // settings from mato.toml override the defaults, the command line overrides both
+    let file_config = file::locate_and_load(&config.source_file).unwrap_or_else(|e| die!("{e}"));
     config.lang = opt_lang.val(&parsed_opts);
+    file_config.apply(&mut config);
+    if let Some(lang) = opt_lang.given(&parsed_opts) {
+        config.lang = lang;
+    }
+    if let Some(filters) = opt_filter.given(&parsed_opts) {
+        config.filters = external::split_commands(&filters);
+    }
+    config.document_filters |= opt_document_filters.is_set(&parsed_opts);
+    let process = opt_process.given(&parsed_opts).or(file_config.process);
+    let no_process = opt_no_process.given(&parsed_opts).or(file_config.no_process);
+    config.processors = registry::resolve(
+        DEFAULT_PROCESSORS,
+        &process.unwrap_or_default(),
+        &no_process.unwrap_or_default(),
+    )
+    .unwrap_or_else(|e| die!("{e}"));
     config.watch = opt_watch.is_set(&parsed_opts);
     config.dump_groff = opt_dump_groff.is_set(&parsed_opts);
     config.dump_groff_file = opt_dump_groff_file.is_set(&parsed_opts);
@@ -81,19 +136,22 @@ fn main() -> std::io::Result<()> {
         config.skip_rendering = true;
         config.dump_groff = true;
     }
-    config.use_standard_gropdf = opt_standard_gropdf.is_set(&parsed_opts);
-    config.gropdf_zig_debug = opt_gropdf_zig_debug.is_set(&parsed_opts);
-    config.timing_chart = opt_timing_chart.is_set(&parsed_opts);
+    config.use_standard_gropdf |= opt_standard_gropdf.is_set(&parsed_opts);
+    config.gropdf_zig_debug |= opt_gropdf_zig_debug.is_set(&parsed_opts);
+    config.timing_chart |= opt_timing_chart.is_set(&parsed_opts);
     mato_dbg!("config: {:#?}", config);
 
     if config.watch {
         let kqueue = watch::Kqueue::create();
         loop {
-            matopdf(&config);
+            // keep watching, the next write may fix the problem
+            if let Err(e) = matopdf(&config) {
+                mato_err!("{e}");
+            }
             kqueue.wait_for_write_on_file_name(&config.source_file)?;
         }
     } else {
-        matopdf(&config);
+        matopdf(&config).unwrap_or_else(|e| die!("{e}"));
     };
     Ok(())
 }
@@ -110,7 +168,7 @@ fn print_timing_chart(phases: &[(&str, std::time::Duration)]) {
             if *duration < std::time::Duration::from_millis(1) {
                 "<1ms".to_string()
             } else if *duration < std::time::Duration::from_secs(1) {
-                format!("{}ms", ((*duration).as_micros() + 999) / 1000)
+                format!("{}ms", (*duration).as_micros().div_ceil(1000))
             } else {
                 format!("{}s", (*duration).as_secs() + if (*duration).subsec_nanos() > 0 { 1 } else { 0 })
             }
@@ -156,11 +214,12 @@ fn print_timing_chart(phases: &[(&str, std::time::Duration)]) {
         };
         let bar = format!("{}{}", "█".repeat(full_blocks), partial_char);
         let line = format!(
-            "{:<label_width$} {} {:>time_width$}",
+            "{:<label_width$} {:<bar_width$} {:>time_width$}",
             label,
-            format!("{:<width$}", bar, width = bar_width),
+            bar,
             time_label,
             label_width = label_width,
+            bar_width = bar_width,
             time_width = time_width
         );
         if index + 1 == phases.len() {
@@ -175,14 +234,14 @@ fn print_timing_chart(phases: &[(&str, std::time::Duration)]) {
 /// matopdf is implementing a pipeline, first reading the input, then
 /// transforming the input using a chain, rendering the transformed input into groff
 /// and lastly using groff to render a pdf
-fn matopdf(config: &Config) {
-    let input = mato::read_input(&config.source_file);
+fn matopdf(config: &Config) -> mato::Result<()> {
+    let inputs = mato::read_inputs(&config.source_files)?;
 
-    let mut chain = create_default_chain(config, true);
-    let mut render: Box<dyn Render + '_> = Box::new(groff::mom::new(config));
+    let mut chain = create_chain(config)?;
+    let mut render: Box<dyn Render + '_> = Box::new(groff::mom::new(config)?);
     // MD -> GROFF
     let start = Instant::now();
-    let groff_output = mato::transform(&mut render, &mut chain, config, &input);
+    let groff_output = mato::transform_all(&mut render, &mut chain, config, &inputs)?;
     let transform_time = start.elapsed();
     if !config.timing_chart {
         mato_inf!("transformed in:\t\t{:?}", transform_time);
@@ -195,8 +254,9 @@ fn matopdf(config: &Config) {
         let path_target_file =
             mato::replace_file_extension(&config.source_file, TARGET_FILE_EXTENSION_GRO);
         mato_dbg!("dumping groff output to: {}", path_target_file.display());
-        fs::write(&path_target_file, &groff_output)
-            .unwrap_or_else(|e| die!("cannot write groff file '{}': {e}", path_target_file.display()));
+        fs::write(&path_target_file, &groff_output).map_err(|e| {
+            Error::io(format!("cannot write groff file '{}'", path_target_file.display()), e)
+        })?;
     }
 
     let pdf_target_file =
@@ -212,14 +272,16 @@ fn matopdf(config: &Config) {
         if let Some(ref path) = gropdf_zig {
             mato_dbg!("using gropdf_zig:\t{}", path.display());
         }
-        let (pdf_output, render_times) = mato::grotopdf(config, &groff_output, gropdf_zig.as_deref());
+        let (pdf_output, render_times) =
+            mato::grotopdf(config, &groff_output, gropdf_zig.as_deref())?;
         if !config.timing_chart {
             mato_inf!("rendering total:\t{:?}", render_times.iter().sum::<std::time::Duration>());
         }
 
         let start = Instant::now();
-        fs::write(&pdf_target_file, pdf_output)
-            .unwrap_or_else(|e| die!("cannot write PDF '{}': {e}", pdf_target_file.display()));
+        fs::write(&pdf_target_file, pdf_output).map_err(|e| {
+            Error::io(format!("cannot write PDF '{}'", pdf_target_file.display()), e)
+        })?;
         let writing_time = start.elapsed();
         if config.timing_chart {
             // Print bar graph
@@ -236,6 +298,7 @@ fn matopdf(config: &Config) {
             eprint!("written in:\t\t{:?}", writing_time);
         }
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -245,9 +308,9 @@ mod tests {
     fn matogro(input: &str) -> String {
         let mut config = Config::default();
         config.skip_preamble = true;
-        let mut chain = super::create_default_chain(&config, true);
-        let mut render: Box<dyn Render + '_> = Box::new(super::groff::mom::new(&config));
-        mato::transform(&mut render, &mut chain, &config, input)
+        let mut chain = mato::create_default_chain(&config).unwrap();
+        let mut render: Box<dyn Render + '_> = Box::new(super::groff::mom::new(&config).unwrap());
+        mato::transform(&mut render, &mut chain, &config, input).unwrap()
     }
 
     #[test]
@@ -285,6 +348,20 @@ mod tests {
         );
     }
     #[test]
+    fn reference_link() {
+        assert_eq!(
+            matogro("some [link text][ex] text\n\n[ex]: http://example.com\n"),
+            ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\nsome \\c\n.PDF_WWW_LINK http://example.com \"link text\"\\c\ntext\n.PP\n.br\n"
+        );
+    }
+    #[test]
+    fn bare_url() {
+        assert_eq!(
+            matogro("see https://example.com."),
+            ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\nsee \\c\n.PDF_WWW_LINK https://example.com \"https://example.com\"\\c\n\\&.\n.br\n"
+        );
+    }
+    #[test]
     fn not_link() {
         assert_eq!(
             matogro("some text [link text]"),
@@ -332,6 +409,19 @@ mod tests {
         );
     }
     #[test]
+    fn long_document_does_not_exhaust_the_stack() {
+        let input = "a *line* of text\n\n".repeat(100_000);
+        let output = matogro(&input);
+        assert_eq!(output.matches(".PP\n").count(), 100_000);
+    }
+    #[test]
+    fn backslash_escapes() {
+        assert_eq!(
+            matogro("\\*a\\* \\\\ \\^(b) \\q\n\\.PP"),
+            ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\n*a* \\[rs] \\[ha](b) \\[rs]q\n\\&.PP\n.br\n"
+        );
+    }
+    #[test]
     fn chapter_mark() {
         assert_eq!(
             matogro(">>(c)\n"),
@@ -340,7 +430,8 @@ mod tests {
     }
     #[test]
     fn not_chapter_mark() {
-        assert_eq!(matogro(">>c"), ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\n>>c\n.br\n");
+        // at the start of a line, >> would begin a nested block quote
+        assert_eq!(matogro("x >>c"), ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\nx >>c\n.br\n");
     }
     #[test]
     fn right_side_note() {
@@ -351,7 +442,7 @@ mod tests {
     }
     #[test]
     fn not_right_side_note() {
-        assert_eq!(matogro(">side"), ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\n>side\n.br\n");
+        assert_eq!(matogro("x >side"), ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\nx >side\n.br\n");
     }
     #[test]
     fn foot_note() {
@@ -361,6 +452,13 @@ mod tests {
         );
     }
     #[test]
+    fn reference_foot_note() {
+        assert_eq!(
+            matogro("a[^1]\n[^1]: side\n"),
+            ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\na\\c\n.FOOTNOTE\nside\n.FOOTNOTE END\n\n.br\n"
+        );
+    }
+    #[test]
     fn em_dash() {
         assert_eq!(
             matogro("word---word"),
@@ -399,6 +497,34 @@ mod tests {
             ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\n.LIST\n.SHIFT_LIST 18p\n.PARA_INDENT 0\n.ITEM\n.PP\nlist item\nwhich continues on next line\n.LIST OFF\n.br\n"
         );
     }
+    #[test]
+    fn block_quote() {
+        assert_eq!(
+            matogro("> quoted\n>\n> > nested\n"),
+            ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\n.BLOCKQUOTE\nquoted\n.BLOCKQUOTE OFF\n.QUOTE_INDENT 6\n.BLOCKQUOTE\nnested\n.BLOCKQUOTE OFF\n.QUOTE_INDENT 3\n.br\n"
+        );
+    }
+    #[test]
+    fn nested_block_quote_continues_outer_one() {
+        assert_eq!(
+            matogro("> outer\n>\n> > > inner\n>\n> again\n"),
+            ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\n.BLOCKQUOTE\nouter\n.BLOCKQUOTE OFF\n.QUOTE_INDENT 9\n.BLOCKQUOTE\ninner\n.BLOCKQUOTE OFF\n.QUOTE_INDENT 3\n.BLOCKQUOTE\nagain\n.BLOCKQUOTE OFF\n.br\n"
+        );
+    }
+    #[test]
+    fn ordered_list() {
+        assert_eq!(
+            matogro("1. first\n2. second\n"),
+            ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\n.LIST DIGIT .\n.SHIFT_LIST 18p\n.PARA_INDENT 0\n.ITEM\n.PP\nfirst\n.ITEM\n.PP\nsecond\n.LIST OFF\n.br\n"
+        );
+    }
+    #[test]
+    fn ordered_list_with_start_number() {
+        assert_eq!(
+            matogro("4) fourth\n"),
+            ".DOCTYPE DEFAULT\n.START\n.hym 0\n.hy 1\n.kp 1\n.LIST DIGIT )\n.SHIFT_LIST 18p\n.PARA_INDENT 0\n.RESET_LIST 4\n.ITEM\n.PP\nfourth\n.LIST OFF\n.br\n"
+        );
+    }
 }
 
 #[cfg(test)]
@@ -413,9 +539,9 @@ mod golden_tests {
         config.source_file = md_path.to_string();
         let input = std::fs::read_to_string(md_path)
             .unwrap_or_else(|e| panic!("could not read {md_path}: {e}"));
-        let mut chain = super::create_default_chain(&config, true);
-        let mut render: Box<dyn Render + '_> = Box::new(super::groff::mom::new(&config));
-        mato::transform(&mut render, &mut chain, &config, &input)
+        let mut chain = mato::create_default_chain(&config).unwrap();
+        let mut render: Box<dyn Render + '_> = Box::new(super::groff::mom::new(&config).unwrap());
+        mato::transform(&mut render, &mut chain, &config, &input).unwrap()
     }
 
     fn golden_path(md_path: &str) -> PathBuf {
@@ -454,6 +580,7 @@ mod golden_tests {
     #[test] fn simple_codeblock()          { assert_golden("samples/simple/codeblock.md"); }
     #[test] fn simple_missing_dot()        { assert_golden("samples/simple/missing-dot.md"); }
     #[test] fn simple_paragraph_no_break() { assert_golden("samples/simple/paragraph-no-break.md"); }
+    #[test] fn simple_table()              { assert_golden("samples/simple/table.md"); }
 
     // font-features/
     #[test] fn font_bold_italics_code()    { assert_golden("samples/font-features/bold-italics-code.md"); }
@@ -511,12 +638,12 @@ mod groff_smoke_tests {
         config.source_file = md_path.to_string();
         let input = std::fs::read_to_string(md_path)
             .unwrap_or_else(|e| panic!("could not read {md_path}: {e}"));
-        let mut chain = super::create_default_chain(&config, true);
-        let mut render: Box<dyn Render + '_> = Box::new(super::groff::mom::new(&config));
-        let groff_src = mato::transform(&mut render, &mut chain, &config, &input);
+        let mut chain = mato::create_default_chain(&config).unwrap();
+        let mut render: Box<dyn Render + '_> = Box::new(super::groff::mom::new(&config).unwrap());
+        let groff_src = mato::transform(&mut render, &mut chain, &config, &input).unwrap();
 
         let mut child = Command::new("groff")
-            .args(["-Tpdf", "-mom", &format!("-m{}", config.lang), "-K", "UTF-8"])
+            .args(["-Tpdf", "-t", "-mom", &format!("-m{}", config.lang), "-K", "UTF-8"])
             .stdin(Stdio::piped())
             .stdout(Stdio::null())
             .stderr(Stdio::piped())
@@ -546,6 +673,7 @@ mod groff_smoke_tests {
     #[test] #[ignore] fn simple_codeblock()          { assert_groff_accepts("samples/simple/codeblock.md"); }
     #[test] #[ignore] fn simple_missing_dot()        { assert_groff_accepts("samples/simple/missing-dot.md"); }
     #[test] #[ignore] fn simple_paragraph_no_break() { assert_groff_accepts("samples/simple/paragraph-no-break.md"); }
+    #[test] #[ignore] fn simple_table()              { assert_groff_accepts("samples/simple/table.md"); }
 
     // font-features/
     #[test] #[ignore] fn font_bold_italics_code()    { assert_groff_accepts("samples/font-features/bold-italics-code.md"); }
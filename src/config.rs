@@ -1,10 +1,15 @@
 use std::path::Path;
 
+pub mod file;
+
 /// captures configuration parsed from command line arguments
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Config {
     /// source file that is to be processed
     pub source_file: String,
+    /// all source files, joined into one document in this order. the first one
+    /// is `source_file`
+    pub source_files: Vec<String>,
     /// should watch mode be activated?
     pub watch: bool,
     /// dump intermediate representation
@@ -12,6 +17,15 @@ pub struct Config {
     pub dump_groff_file: bool,
     /// write a graphviz dot representation to a file?
     pub dump_dot_file: bool,
+    /// group runs of inline nodes into clusters in the dot representation?
+    pub dot_clusters: bool,
+    /// commands of external filters to pipe the tree through, in this order
+    pub filters: Vec<String>,
+    /// run the filters named in the `filters` meta data of the document, too? they
+    /// execute whatever the author of the document chose, so they need to be asked for
+    pub document_filters: bool,
+    /// names of the processors to run, in this order
+    pub processors: Vec<String>,
     pub skip_rendering: bool,
     /// language
     pub lang: String,
@@ -28,10 +42,15 @@ impl Config {
     pub const fn default() -> Self {
         Config {
             source_file: String::new(),
+            source_files: Vec::new(),
             watch: false,
             dump_groff: false,
             dump_groff_file: false,
             dump_dot_file: false,
+            dot_clusters: false,
+            filters: Vec::new(),
+            document_filters: false,
+            processors: Vec::new(),
             skip_rendering: false,
             lang: String::new(),
             skip_preamble: false,
@@ -42,7 +61,7 @@ impl Config {
     }
 
     /// create a configuration struct directly from `env::args.collect()`
-    pub fn from(args: Vec<String>) -> Result<Config, std::io::Error> {
+    pub fn from(args: Vec<String>) -> crate::Result<Config> {
         let mut result = Self::default();
         result.lang = "den".to_string();
         if args.len() > 1 {
@@ -61,8 +80,10 @@ impl Config {
             }
         }
         if !result.source_file.is_empty() && !Path::new(&result.source_file).exists() {
-            eprintln!("Could not open source file: {}", result.source_file);
-            std::process::exit(1);
+            return Err(crate::Error::Config(format!(
+                "Could not open source file: {}",
+                result.source_file
+            )));
         }
         Ok(result)
     }
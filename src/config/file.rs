@@ -0,0 +1,321 @@
+//! project-level configuration, read from a `mato.toml` file
+//!
+//! The file is looked for next to the source file first, then in the user-wide
+//! configuration directory, see [`crate::locate_config_file`]. Only the first file
+//! found is read. Its settings override the built-in defaults, options given on the
+//! command line override the file.
+//!
+//! Only the part of TOML needed for flat settings is understood: `key = value`
+//! lines with strings, booleans and arrays of strings, and `#` comments.
+
+use std::fs;
+use std::path::Path;
+
+use super::Config;
+use crate::Error;
+
+pub const FILE_NAME: &str = "mato.toml";
+
+/// settings read from a configuration file, `None` where the file says nothing
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FileConfig {
+    pub lang: Option<String>,
+    pub filters: Option<Vec<String>>,
+    /// run the filters documents name in their meta data, like `--document-filters`
+    pub document_filters: Option<bool>,
+    /// processors to run, separated by commas, like `--process`
+    pub process: Option<String>,
+    /// processors not to run, separated by commas, like `--no-process`
+    pub no_process: Option<String>,
+    pub skip_preamble: Option<bool>,
+    pub standard_gropdf: Option<bool>,
+    pub gropdf_zig_debug: Option<bool>,
+    pub timing_chart: Option<bool>,
+    pub dot_clusters: Option<bool>,
+}
+
+impl FileConfig {
+    /// overrides the settings of `config` with the ones given in the file
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(lang) = &self.lang {
+            config.lang = lang.clone();
+        }
+        if let Some(filters) = &self.filters {
+            config.filters = filters.clone();
+        }
+        let flags = [
+            (self.document_filters, &mut config.document_filters),
+            (self.skip_preamble, &mut config.skip_preamble),
+            (self.standard_gropdf, &mut config.use_standard_gropdf),
+            (self.gropdf_zig_debug, &mut config.gropdf_zig_debug),
+            (self.timing_chart, &mut config.timing_chart),
+            (self.dot_clusters, &mut config.dot_clusters),
+        ];
+        for (value, flag) in flags {
+            if let Some(value) = value {
+                *flag = value;
+            }
+        }
+    }
+}
+
+/// a value in the file
+#[derive(Debug, PartialEq)]
+enum Value {
+    String(String),
+    Bool(bool),
+    Array(Vec<String>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Bool(_) => "a boolean",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+/// locates the configuration file for `source_file` and reads it. returns the
+/// default, empty configuration if there is none.
+pub fn locate_and_load(source_file: &str) -> crate::Result<FileConfig> {
+    match crate::locate_config_file(source_file, FILE_NAME) {
+        Some(path) => load(&path),
+        None => Ok(FileConfig::default()),
+    }
+}
+
+/// reads the configuration file at `path`
+pub fn load(path: &Path) -> crate::Result<FileConfig> {
+    let input = fs::read_to_string(path)
+        .map_err(|e| Error::io(format!("cannot read '{}'", path.display()), e))?;
+    parse(&input).map_err(|e| Error::Config(format!("{}:{e}", path.display())))
+}
+
+/// parses the contents of a configuration file, errors start with the line number
+pub fn parse(input: &str) -> Result<FileConfig, String> {
+    let mut result = FileConfig::default();
+    let mut seen: Vec<String> = Vec::new();
+    let mut lines = input.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line_number = index + 1;
+        let error = |message: String| format!("{line_number}: {message}");
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(error("tables are not supported".to_string()));
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error(format!("expected `key = value`, found `{line}`")));
+        };
+        let key = key.trim().trim_matches('"').to_string();
+        let mut value = value.trim().to_string();
+        // arrays may span several lines
+        if value.starts_with('[') {
+            while !value.ends_with(']') {
+                match lines.next() {
+                    Some((_, next)) => value = format!("{value} {}", strip_comment(next).trim()),
+                    None => return Err(error(format!("unterminated array for `{key}`"))),
+                }
+            }
+        }
+        let value = parse_value(&value).map_err(error)?;
+        if seen.contains(&key) {
+            return Err(error(format!("`{key}` is given more than once")));
+        }
+        seen.push(key.clone());
+        set(&mut result, &key, value).map_err(error)?;
+    }
+    Ok(result)
+}
+
+/// removes a `#` comment, unless the `#` is part of a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    match value {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ if value.starts_with('[') && value.ends_with(']') => {
+            let mut items = Vec::new();
+            let mut rest = value[1..value.len() - 1].trim();
+            while !rest.is_empty() {
+                let (item, after) = parse_string(rest)?;
+                items.push(item);
+                rest = after.trim_start();
+                match rest.strip_prefix(',') {
+                    Some(after_comma) => rest = after_comma.trim_start(),
+                    None if rest.is_empty() => {}
+                    None => return Err(format!("expected `,` in array, found `{rest}`")),
+                }
+            }
+            Ok(Value::Array(items))
+        }
+        _ => match parse_string(value)? {
+            (s, "") => Ok(Value::String(s)),
+            (_, rest) => Err(format!("unexpected `{}` after string", rest.trim())),
+        },
+    }
+}
+
+/// parses a quoted string at the start of `s`, returns it and what follows it
+fn parse_string(s: &str) -> Result<(String, &str), String> {
+    let mut chars = s.char_indices();
+    let quote = match chars.next() {
+        Some((_, q @ ('"' | '\''))) => q,
+        _ => return Err(format!("expected a string, boolean or array, found `{s}`")),
+    };
+    let mut result = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            _ if c == quote => return Ok((result, &s[i + 1..])),
+            '\\' if quote == '"' => match chars.next() {
+                Some((_, '"')) => result.push('"'),
+                Some((_, '\\')) => result.push('\\'),
+                Some((_, 'n')) => result.push('\n'),
+                Some((_, 't')) => result.push('\t'),
+                _ => return Err("unsupported escape sequence in string".to_string()),
+            },
+            _ => result.push(c),
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+/// a value given as a string or as an array of strings, as a comma separated list
+fn list(key: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Array(items) => Ok(items.join(",")),
+        _ => Err(format!("`{key}` must be a string or an array of strings")),
+    }
+}
+
+fn set(config: &mut FileConfig, key: &str, value: Value) -> Result<(), String> {
+    let expected = |kind: &str| format!("`{key}` must be {kind}, not {}", value.kind());
+    match key {
+        "lang" => match value {
+            Value::String(s) => config.lang = Some(s),
+            _ => return Err(expected("a string")),
+        },
+        "filters" => match value {
+            Value::Array(items) => config.filters = Some(items),
+            Value::String(s) => config.filters = Some(crate::process::external::split_commands(&s)),
+            _ => return Err(expected("an array of strings")),
+        },
+        "process" => config.process = Some(list(key, value)?),
+        "no-process" => config.no_process = Some(list(key, value)?),
+        "document-filters" | "skip-preamble" | "standard-gropdf" | "gropdf-zig-debug"
+        | "timing-chart" | "dot-clusters" => {
+            let Value::Bool(b) = value else {
+                return Err(expected("a boolean"));
+            };
+            let flag = match key {
+                "document-filters" => &mut config.document_filters,
+                "skip-preamble" => &mut config.skip_preamble,
+                "standard-gropdf" => &mut config.standard_gropdf,
+                "gropdf-zig-debug" => &mut config.gropdf_zig_debug,
+                "timing-chart" => &mut config.timing_chart,
+                _ => &mut config.dot_clusters,
+            };
+            *flag = Some(b);
+        }
+        _ => return Err(format!("unknown setting `{key}`")),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_read() {
+        let file = parse(
+            "# settings for the book\n\
+             lang = \"en\"   # english\n\
+             timing-chart = true\n\
+             document-filters = true\n\
+             filters = [\n  \"./caps.py\",\n  'upper # not a comment',\n]\n\
+             process = [\"default\", \"identity\"]\n\
+             no-process = \"old-style-figures\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            file,
+            FileConfig {
+                lang: Some("en".to_string()),
+                filters: Some(vec![
+                    "./caps.py".to_string(),
+                    "upper # not a comment".to_string()
+                ]),
+                process: Some("default,identity".to_string()),
+                no_process: Some("old-style-figures".to_string()),
+                timing_chart: Some(true),
+                document_filters: Some(true),
+                ..FileConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn settings_override_defaults_only_where_given() {
+        let mut config = Config::default();
+        config.lang = "den".to_string();
+        config.use_standard_gropdf = true;
+        parse("lang = \"en\"\ntiming-chart = true")
+            .unwrap()
+            .apply(&mut config);
+        assert_eq!(config.lang, "en");
+        assert!(config.timing_chart);
+        assert!(config.use_standard_gropdf);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(
+            parse("\nlanguage = \"en\""),
+            Err("2: unknown setting `language`".to_string())
+        );
+        assert_eq!(
+            parse("timing-chart = \"yes\""),
+            Err("1: `timing-chart` must be a boolean, not a string".to_string())
+        );
+        assert_eq!(
+            parse("lang = en"),
+            Err("1: expected a string, boolean or array, found `en`".to_string())
+        );
+        assert_eq!(
+            parse("lang = \"en\"\nlang = \"de\""),
+            Err("2: `lang` is given more than once".to_string())
+        );
+        assert_eq!(
+            parse("[render]"),
+            Err("1: tables are not supported".to_string())
+        );
+        assert_eq!(
+            parse("filters = [\"a\""),
+            Err("1: unterminated array for `filters`".to_string())
+        );
+    }
+}
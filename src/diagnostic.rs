@@ -0,0 +1,66 @@
+use std::fmt;
+
+use crate::syntax::Span;
+
+/// how serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// a problem found in the source document, located by line and column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// 1-based line of the offending construct
+    pub line: usize,
+    /// 1-based (byte) column of the offending construct
+    pub column: usize,
+    /// byte offset of the offending construct, which tells the source files of a
+    /// joined document apart
+    pub offset: usize,
+    pub severity: Severity,
+}
+
+impl Diagnostic {
+    /// an error located at the start of `span`
+    pub fn error(message: &str, span: Span) -> Self {
+        Self::new(message, span, Severity::Error)
+    }
+
+    /// a warning located at the start of `span`
+    pub fn warning(message: &str, span: Span) -> Self {
+        Self::new(message, span, Severity::Warning)
+    }
+
+    fn new(message: &str, span: Span, severity: Severity) -> Self {
+        Diagnostic {
+            message: message.to_string(),
+            line: span.line,
+            column: span.column,
+            offset: span.start,
+            severity,
+        }
+    }
+}
+
+/// formats like compilers do: `line:column: severity: message`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, self.severity, self.message
+        )
+    }
+}
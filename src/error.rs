@@ -0,0 +1,58 @@
+use std::fmt;
+use std::io;
+
+/// what can go wrong while transforming a document
+#[derive(Debug)]
+pub enum Error {
+    /// reading or writing a file, or talking to another program failed
+    Io { context: String, source: io::Error },
+    /// a setting, an option or a configuration file is invalid
+    Config(String),
+    /// a processor or another program could not do its work
+    Process(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// an I/O error, `context` says what was being done
+    pub fn io(context: impl Into<String>, source: io::Error) -> Self {
+        Error::Io {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { context, source } => write!(f, "{context}: {source}"),
+            Error::Config(message) | Error::Process(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_say_what_was_done() {
+        let error = Error::io(
+            "cannot read 'a.md'",
+            io::Error::new(io::ErrorKind::NotFound, "no such file"),
+        );
+        assert_eq!(error.to_string(), "cannot read 'a.md': no such file");
+        assert!(std::error::Error::source(&error).is_some());
+    }
+}
@@ -9,14 +9,18 @@ use std::env;
 use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Command, ExitStatus, Stdio};
 use std::time::Instant;
-use syntax::Tree;
+use syntax::{dot, DocType, Span, Tree};
 
-use crate::process::{canonicalize, chain, code_block, image_converter};
+use crate::process::{image_converter, include, registry};
+pub use error::{Error, Result};
 
 pub mod config;
+pub mod diagnostic;
+pub mod error;
 pub mod log;
+pub mod options;
 pub mod opts;
 pub mod parser;
 pub mod process;
@@ -40,97 +44,121 @@ pub fn establish_log_level(parsed_opts: &ParserResult) -> u8 {
     }
 }
 
-fn read_all_from_stdin() -> String {
+fn read_all_from_stdin() -> Result<String> {
     let lines = io::stdin().lines();
     let mut result = String::new();
     for line in lines {
-        result.push_str(line.unwrap_or_else(|e| die!("error reading stdin: {e}")).as_str());
+        result.push_str(&line.map_err(|e| Error::io("error reading stdin", e))?);
         result.push('\n');
     }
-    result
+    Ok(result)
 }
 
-pub fn read_input(source_file: &str) -> String {
+/// reads `source_file`, or standard input if it is empty
+pub fn read_input(source_file: &str) -> Result<String> {
     let start = Instant::now();
     let input = if source_file.is_empty() {
-        read_all_from_stdin()
+        read_all_from_stdin()?
     } else {
         std::fs::read_to_string(source_file)
-            .unwrap_or_else(|e| die!("cannot read '{}': {e}", source_file))
+            .map_err(|e| Error::io(format!("cannot read '{source_file}'"), e))?
     };
     m_dbg!("input read in:\t\t{:?}", start.elapsed());
-    input
+    Ok(input)
+}
+
+/// reads all of `source_files`, see [`read_input`]. returns the file names
+/// together with their contents.
+pub fn read_inputs(source_files: &[String]) -> Result<Vec<(String, String)>> {
+    source_files
+        .iter()
+        .map(|source_file| Ok((source_file.clone(), read_input(source_file)?)))
+        .collect()
 }
 
 /// constructs what is considered by us to be a default chain:
 ///
-/// `canonicalize -> image_converter -> code_block_converter`
+/// `include -> footnotes -> links -> external -> canonicalize -> old-style-figures -> image-converter -> code-block`
 ///
 /// and returns it
-pub fn create_default_chain(config: &Config, replace_numerals: bool) -> Chain<'_> {
+pub fn create_default_chain(config: &Config) -> Result<Chain<'_>> {
+    let names: Vec<String> = registry::DEFAULT_PROCESSORS
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    create_chain_of(&names, config)
+}
+
+/// constructs the chain of the processors named in `config.processors`
+pub fn create_chain(config: &Config) -> Result<Chain<'_>> {
+    create_chain_of(&config.processors, config)
+}
+
+fn create_chain_of<'a>(names: &[String], config: &'a Config) -> Result<Chain<'a>> {
     m_trc!("constructing chain...");
-    let chain = chain::new(
-        canonicalize::new(replace_numerals),
-        image_converter::new(config),
-    )
-    .append(code_block::new());
+    let chain = registry::create_chain(names, config).map_err(Error::Config)?;
     m_trc!("done");
     m_dbg!("chain: {:?}", chain);
-    chain
+    Ok(chain)
 }
 
 const MATO_CONFIG_DIR_NAME: &str = "mato";
 
-/// locates and reads a preamble file.
+/// the user-wide configuration directory, `$XDG_CONFIG_HOME/mato` or `$HOME/.config/mato`
+fn user_config_dir() -> Option<PathBuf> {
+    let config_home = match env::var("XDG_CONFIG_HOME") {
+        Ok(xdg_config_home) => {
+            m_dbg!("XDG_CONFIG_HOME = {}", xdg_config_home);
+            PathBuf::from(xdg_config_home)
+        }
+        Err(_) => {
+            m_dbg!("XDG_CONFIG_HOME not set");
+            Path::new(&env::var("HOME").ok()?).join(".config")
+        }
+    };
+    Some(config_home.join(MATO_CONFIG_DIR_NAME))
+}
+
+/// locates a configuration file named `name`.
 ///
 /// algorithm for locating:
 ///
-/// 1. a *sibbling* file, located side-by-side the input file is searched, named `name`
+/// 1. a *sibbling* file, located side-by-side the source file is searched, named `name`
 /// 2. if not found, a user-wide configuration under $XDG_CONFIG_HOME/mato/`name` is searched
-/// 3. if not found, the `default_preamble` string is returned
-pub fn locate_and_load_preamble(config: &Config, name: &str, default_preamble: &str) -> String {
-    if config.skip_preamble {
-        return "".to_string();
+/// 3. if not found, `None` is returned
+pub fn locate_config_file(source_file: &str, name: &str) -> Option<PathBuf> {
+    let sibbling = crate::parent_dir(source_file).join(name);
+    if sibbling.is_file() {
+        m_dbg!("found sibbling {}: {}", name, sibbling.display());
+        return Some(sibbling);
     }
-    let sibbling_preamble = crate::parent_dir(&config.source_file).join(name);
-    if sibbling_preamble.as_path().is_file() {
-        m_dbg!("found sibbling preamble: {}", sibbling_preamble.display());
-        fs::read_to_string(&sibbling_preamble)
-            .unwrap_or_else(|e| die!("cannot read preamble '{}': {e}", sibbling_preamble.display()))
+    let user_file = user_config_dir()?.join(name);
+    if user_file.is_file() {
+        m_dbg!("found user {}: {}", name, user_file.display());
+        Some(user_file)
     } else {
-        // no sibbling preamble
-        // 1. try XDG_CONFIG_HOME
-        let config_home = match env::var("XDG_CONFIG_HOME") {
-            Ok(xdg_config_home) => {
-                m_dbg!("XDG_CONFIG_HOME = {}", xdg_config_home);
-                xdg_config_home
-            }
-            Err(_) => {
-                m_dbg!("XDG_CONFIG_HOME not set");
-                // 2. try $HOME/.config
-                match env::var("HOME") {
-                    Ok(home_path) => format!("{home_path}/.config"),
-                    Err(_) => "".to_string(),
-                }
-            }
-        };
-        if !config_home.is_empty() {
-            // construct mato config path
-            let mato_config_path = Path::new(&config_home).join(MATO_CONFIG_DIR_NAME);
-            if mato_config_path.exists() {
-                m_dbg!("found mato config path {:?}: ", mato_config_path);
-                let user_peamble_path = mato_config_path.join(name);
-                if user_peamble_path.exists() {
-                    m_dbg!("found user preamble: {:?}", user_peamble_path);
-                    return fs::read_to_string(&user_peamble_path)
-                        .unwrap_or_else(|e| die!("cannot read preamble '{}': {e}", user_peamble_path.display()));
-                }
-            } else {
-                m_dbg!("mato config path not found: {:?}", mato_config_path);
-            }
+        m_dbg!("{} not found", name);
+        None
+    }
+}
+
+/// locates and reads a preamble file, see [`locate_config_file`]. if none is
+/// found, the `default_preamble` string is returned.
+pub fn locate_and_load_preamble(
+    config: &Config,
+    name: &str,
+    default_preamble: &str,
+) -> Result<String> {
+    if config.skip_preamble {
+        return Ok("".to_string());
+    }
+    match locate_config_file(&config.source_file, name) {
+        Some(path) => fs::read_to_string(&path)
+            .map_err(|e| Error::io(format!("cannot read preamble '{}'", path.display()), e)),
+        None => {
+            m_dbg!("preamble:\t\tbuilt-in");
+            Ok(default_preamble.to_owned())
         }
-        m_dbg!("preamble:\t\tbuilt-in");
-        default_preamble.to_owned()
     }
 }
 
@@ -146,53 +174,61 @@ pub fn replace_file_extension(file_name: &str, extension: &str) -> PathBuf {
     path_target_file
 }
 
-pub fn create_if_not_exists(file_name: &str) {
+pub fn create_if_not_exists(file_name: &str) -> Result<()> {
     let path_source_file = Path::new(file_name);
     if !path_source_file.is_file() {
         m_dbg!("creating {}", file_name);
         File::create(file_name)
-            .unwrap_or_else(|e| die!("cannot create '{}': {e}", file_name));
+            .map_err(|e| Error::io(format!("cannot create '{file_name}'"), e))?;
     }
+    Ok(())
 }
 
 const EMPTY_PDF: &[u8] = include_bytes!("empty.pdf");
 
-pub fn create_empty_if_not_exists(file_name: &str) {
+pub fn create_empty_if_not_exists(file_name: &str) -> Result<()> {
     let path_source_file = Path::new(file_name);
     if !path_source_file.is_file() {
         m_dbg!("creating empty pdf {}", file_name);
         let mut pdf = File::create(file_name)
-            .unwrap_or_else(|e| die!("cannot create '{}': {e}", file_name));
+            .map_err(|e| Error::io(format!("cannot create '{file_name}'"), e))?;
         pdf.write_all(EMPTY_PDF)
-            .unwrap_or_else(|e| die!("cannot write '{}': {e}", file_name));
+            .map_err(|e| Error::io(format!("cannot write '{file_name}'"), e))?;
     }
+    Ok(())
 }
 
-/// executes the given `cmd` as a sub process, blocks and
-/// returns its output as a string
-fn spawn(cmd: Vec<&str>) {
+/// an error for `command`, unless it exited successfully
+fn check_status(command: &str, status: ExitStatus) -> Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::Process(format!("{command} failed: {status}")))
+    }
+}
+
+/// executes the given `cmd` as a sub process and blocks until it is done
+fn spawn(cmd: Vec<&str>) -> Result<()> {
     m_dbg!("exec: {:?}", cmd);
-    Command::new("/usr/bin/env")
-        .args(cmd)
+    let status = Command::new("/usr/bin/env")
+        .args(&cmd)
         .status()
-        .unwrap_or_else(|e| die!("failed to execute command: {e}"));
+        .map_err(|e| Error::io("failed to execute command", e))?;
+    check_status(&cmd.join(" "), status)
 }
 
 /// executes the given `cmd` as a sub process, blocks and
 /// returns its output as a string
-fn exec(cmd: Vec<&str>) -> String {
+fn exec(cmd: Vec<&str>) -> Result<String> {
     m_dbg!("exec: {:?}", cmd);
-    let out = Command::new("/usr/bin/env")
-        .args(cmd)
+    let output = Command::new("/usr/bin/env")
+        .args(&cmd)
         .output()
-        .unwrap_or_else(|e| die!("failed to execute command: {e}"))
-        .stdout;
-    if out.is_empty() {
-        return String::new();
-    }
-    let s = String::from_utf8(out)
-        .unwrap_or_else(|e| die!("command output is not valid UTF-8: {e}"));
-    s.strip_suffix('\n').unwrap_or(&s).to_string()
+        .map_err(|e| Error::io("failed to execute command", e))?;
+    check_status(&cmd.join(" "), output.status)?;
+    let s = String::from_utf8(output.stdout)
+        .map_err(|e| Error::Process(format!("command output is not valid UTF-8: {e}")))?;
+    Ok(s.strip_suffix('\n').unwrap_or(&s).to_string())
 }
 
 /// top-level helper method to transform a given input string into a target language specified by the passed in renderer
@@ -201,32 +237,211 @@ pub fn transform<P: Process>(
     p: &mut P,
     config: &Config,
     input: &str,
-) -> String {
+) -> Result<String> {
+    let tree = parse(&config.source_file, input);
+    transform_tree(r, p, config, tree)
+}
+
+/// like [`transform`], but for several source files, given together with their
+/// contents. they are joined into one document, see [`join_documents`].
+pub fn transform_all<P: Process>(
+    r: &mut Box<dyn Render + '_>,
+    p: &mut P,
+    config: &Config,
+    inputs: &[(String, String)],
+) -> Result<String> {
+    let tree = process_all(p, config, inputs)?;
+    Ok(render(r, tree, p))
+}
+
+/// parses, joins and processes several source files, given together with their
+/// contents, without rendering them. for renderers that need the tree itself.
+pub fn process_all<P: Process>(
+    p: &mut P,
+    config: &Config,
+    inputs: &[(String, String)],
+) -> Result<Tree> {
+    let (tree, sources) = parse_all(inputs);
+    process_tree(p, config, tree, &sources)
+}
+
+/// parses several source files and joins them into one document. the spans of each
+/// document are moved behind the ones before, so that their offsets tell which file
+/// a node, and a diagnostic about it, comes from. returns the files together with
+/// the offset their spans start at.
+fn parse_all(inputs: &[(String, String)]) -> (Tree, Vec<(usize, &str)>) {
+    let mut sources = Vec::new();
+    let mut offset = 0;
+    let documents = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, (source_file, input))| {
+            let mut tree = parse(source_file, input);
+            sources.push((offset, source_file.as_str()));
+            if index > 0 {
+                tree.map_spans(&mut |span: &mut Span| {
+                    if !span.is_unknown() {
+                        span.start += offset;
+                        span.end += offset;
+                    }
+                });
+            }
+            offset += input.len();
+            if index == 0 {
+                tree
+            } else {
+                // the image converter and includes resolve against the first file only
+                let dir = parent_dir(source_file);
+                include::rebase(image_converter::rebase(tree, dir), dir)
+            }
+        })
+        .collect();
+    (join_documents(documents), sources)
+}
+
+/// the file of `sources` that `diagnostic` was found in
+fn source_of<'a>(sources: &[(usize, &'a str)], diagnostic: &diagnostic::Diagnostic) -> &'a str {
+    sources
+        .iter()
+        .rev()
+        .find(|(offset, _)| *offset <= diagnostic.offset)
+        .map_or("", |(_, source_file)| source_file)
+}
+
+/// the name of `source_file` in messages
+fn source_name(source_file: &str) -> &str {
+    if source_file.is_empty() {
+        "<stdin>"
+    } else {
+        source_file
+    }
+}
+
+/// parses `input`, reporting problems found in `source_file`
+pub(crate) fn parse(source_file: &str, input: &str) -> Tree {
     m_trc!("parsing...");
-    let mut tree = Parser::parse(input);
+    let (tree, diagnostics) = Parser::parse(input);
+    for diagnostic in &diagnostics {
+        mato_err!("{}:{diagnostic}", source_name(source_file));
+    }
     m_trc!("parsed: {:?}", tree);
-    tree = process(p, tree);
+    tree
+}
+
+/// joins parsed documents into one, in the given order. the document type is
+/// the first one declared and all meta data ends up in a single block in front,
+/// where items of earlier documents win. each document starts a new paragraph.
+fn join_documents(mut documents: Vec<Tree>) -> Tree {
+    if documents.len() == 1 {
+        return documents.remove(0);
+    }
+    let mut doc_type = DocType::DEFAULT;
+    let mut span = None;
+    let mut items: Vec<Tree> = Vec::new();
+    let mut children = Vec::new();
+    for document in documents {
+        let Tree::Document(document_type, content, document_span) = document else {
+            children.push(document);
+            continue;
+        };
+        if matches!(doc_type, DocType::DEFAULT) {
+            doc_type = document_type;
+        }
+        span.get_or_insert(document_span);
+        let content = match *content {
+            Tree::Block(content, _) => content,
+            content => vec![content],
+        };
+        let mut body = Vec::new();
+        for child in content {
+            let Tree::MetaDataBlock(block, _) = child else {
+                body.push(child);
+                continue;
+            };
+            let block_items = match *block {
+                Tree::Block(block_items, _) => block_items,
+                item => vec![item],
+            };
+            for item in block_items {
+                let known = match &item {
+                    Tree::MetaDataItem(key, _, _) => items
+                        .iter()
+                        .any(|i| matches!(i, Tree::MetaDataItem(k, _, _) if k == key)),
+                    _ => false,
+                };
+                if !known {
+                    items.push(item);
+                }
+            }
+        }
+        if body.iter().all(|child| matches!(child, Tree::Empty(_))) {
+            continue;
+        }
+        // the paragraph break the parser puts between two paragraphs
+        if !children.is_empty() && !matches!(children.last(), Some(Tree::Paragraph(_))) {
+            if !matches!(children.last(), Some(Tree::LineBreak(_))) {
+                children.push(Tree::LineBreak(Span::default()));
+            }
+            children.push(Tree::Paragraph(Span::default()));
+        }
+        children.extend(body);
+    }
+    if !items.is_empty() {
+        children.insert(0, syntax::meta_data_block(syntax::block(items)));
+    }
+    Tree::Document(doc_type, Box::new(syntax::block(children)), span.unwrap_or_default())
+}
+
+/// processes and renders a parsed document
+fn transform_tree<P: Process>(
+    r: &mut Box<dyn Render + '_>,
+    p: &mut P,
+    config: &Config,
+    tree: Tree,
+) -> Result<String> {
+    let tree = process_tree(p, config, tree, &[(0, &config.source_file)])?;
+    Ok(render(r, tree, p))
+}
+
+/// processes a parsed document. `sources` are the files it was parsed from, each
+/// with the offset its spans start at, to report diagnostics against
+fn process_tree<P: Process>(
+    p: &mut P,
+    config: &Config,
+    mut tree: Tree,
+    sources: &[(usize, &str)],
+) -> Result<Tree> {
+    tree = process(p, tree)?;
+    for diagnostic in p.take_diagnostics() {
+        mato_err!("{}:{diagnostic}", source_name(source_of(sources, &diagnostic)));
+    }
     m_trc!("{:?}", config);
     if config.dump_dot_file {
         let path_target_file = replace_file_extension(&config.source_file, "dot");
         m_trc!("dumping processed tree to: {:?}", path_target_file);
-        fs::write(&path_target_file, format!("{tree}"))
-            .unwrap_or_else(|e| die!("cannot write '{}': {e}", path_target_file.display()));
+        fs::write(&path_target_file, dot::to_dot(&tree, config.dot_clusters)).map_err(|e| {
+            Error::io(format!("cannot write '{}'", path_target_file.display()), e)
+        })?;
     } else {
         m_trc!("processed:\n{:?}", tree);
     }
-    render(r, tree, p)
+    Ok(tree)
 }
 
 /// A processor processes the AST in some way
 pub trait Process: Debug {
-    fn process(&mut self, exp: Tree) -> Tree;
+    fn process(&mut self, exp: Tree) -> Result<Tree>;
+
+    /// hands out the problems found while processing
+    fn take_diagnostics(&mut self) -> Vec<diagnostic::Diagnostic> {
+        vec![]
+    }
 }
 
 /// helper function for static dispatch
 ///
 /// calls the passed in processor on the given exp
-fn process<P: Process>(p: &mut P, exp: Tree) -> Tree {
+fn process<P: Process>(p: &mut P, exp: Tree) -> Result<Tree> {
     p.process(exp)
 }
 
@@ -243,112 +458,6 @@ fn render<P: Process>(r: &mut Box<dyn Render + '_>, exp: Tree, _p: &mut P) -> St
     r.render(exp)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::opts;
-
-    // --- establish_log_level ---
-
-    fn parse_flags(flags: &[&str]) -> opts::ParserResult {
-        let p = opts::Parser::new();
-        let mut args = vec!["cmd".to_string()];
-        args.extend(flags.iter().map(|s| s.to_string()));
-        p.parse(args)
-    }
-
-    #[test]
-    fn log_level_default_is_zero() {
-        let r = parse_flags(&[]);
-        assert_eq!(establish_log_level(&r), 0);
-    }
-
-    #[test]
-    fn log_level_verbose_is_one() {
-        let r = parse_flags(&["--verbose"]);
-        assert_eq!(establish_log_level(&r), 1);
-    }
-
-    #[test]
-    fn log_level_debug_is_two() {
-        let r = parse_flags(&["--debug"]);
-        assert_eq!(establish_log_level(&r), 2);
-    }
-
-    #[test]
-    fn log_level_trace_is_three() {
-        let r = parse_flags(&["--trace"]);
-        assert_eq!(establish_log_level(&r), 3);
-    }
-
-    // --- replace_file_extension ---
-
-    #[test]
-    fn replace_extension_changes_suffix() {
-        let result = replace_file_extension("doc.md", "pdf");
-        assert_eq!(result, std::path::PathBuf::from("doc.pdf"));
-    }
-
-    #[test]
-    fn replace_extension_on_path_with_dirs() {
-        let result = replace_file_extension("/home/user/docs/file.md", "groff");
-        assert_eq!(
-            result,
-            std::path::PathBuf::from("/home/user/docs/file.groff")
-        );
-    }
-
-    // --- parent_dir ---
-
-    #[test]
-    fn parent_dir_returns_containing_directory() {
-        assert_eq!(parent_dir("/home/user/file.md"), std::path::Path::new("/home/user"));
-    }
-
-    // --- locate_and_load_preamble ---
-
-    #[test]
-    fn skip_preamble_returns_empty_string() {
-        let mut config = Config::default();
-        config.source_file = "/some/file.md".to_string();
-        config.skip_preamble = true;
-        let result = locate_and_load_preamble(&config, "preamble.mom", "DEFAULT");
-        assert_eq!(result, "");
-    }
-
-    #[test]
-    fn falls_back_to_default_preamble_when_no_file_found() {
-        let mut config = Config::default();
-        // Point to a directory that definitely has no preamble.mom
-        config.source_file = "/nonexistent/path/file.md".to_string();
-        config.skip_preamble = false;
-        // Point XDG_CONFIG_HOME to a nonexistent path so neither it nor the
-        // $HOME/.config fallback can accidentally pick up a real preamble.
-        std::env::set_var("XDG_CONFIG_HOME", "/nonexistent/xdg_config_home");
-        let result = locate_and_load_preamble(&config, "preamble.mom", "MY_DEFAULT");
-        assert_eq!(result, "MY_DEFAULT");
-    }
-
-    #[test]
-    fn loads_sibling_preamble_when_present() {
-        let dir = std::env::temp_dir().join("mato_test_preamble");
-        std::fs::create_dir_all(&dir).unwrap();
-        let source = dir.join("doc.md");
-        let preamble = dir.join("preamble.mom");
-        std::fs::write(&source, "# Hello").unwrap();
-        std::fs::write(&preamble, "SIBLING_PREAMBLE").unwrap();
-
-        let mut config = Config::default();
-        config.source_file = source.to_str().unwrap().to_string();
-        config.skip_preamble = false;
-
-        let result = locate_and_load_preamble(&config, "preamble.mom", "DEFAULT");
-        // Clean up
-        let _ = std::fs::remove_dir_all(&dir);
-        assert_eq!(result, "SIBLING_PREAMBLE");
-    }
-}
-
 /// Searches the directories in `PATH` for an executable named `name`.
 /// Returns the full path if found, `None` otherwise.
 pub fn find_in_path(name: &str) -> Option<PathBuf> {
@@ -368,7 +477,11 @@ pub fn find_in_path(name: &str) -> Option<PathBuf> {
 /// When `custom_gropdf` is `Some(path)`, a two-stage pipeline is used:
 /// `groff -Z` produces intermediate ditroff output which is then piped into the
 /// provided postprocessor binary instead of the standard `gropdf` perl implementation.
-pub fn grotopdf(config: &Config, input: &str, custom_gropdf: Option<&Path>) -> (Vec<u8>, Vec<std::time::Duration>) {
+pub fn grotopdf(
+    config: &Config,
+    input: &str,
+    custom_gropdf: Option<&Path>,
+) -> Result<(Vec<u8>, Vec<std::time::Duration>)> {
     // calling `groff` directly instead of `mompdf` has a performance
     // adavantage, but will handle forwar references not correctly.
     // see https://www.schaffter.ca/mom/pdf/mom-pdf.pdf and there
@@ -384,6 +497,7 @@ pub fn grotopdf(config: &Config, input: &str, custom_gropdf: Option<&Path>) -> (
             .arg("groff")
             .arg("-Z")
             .arg("-Tpdf")
+            .arg("-t")
             .arg("-mom")
             .arg(format!("-m{}", config.lang))
             .args(["-K", "UTF-8"])
@@ -391,24 +505,25 @@ pub fn grotopdf(config: &Config, input: &str, custom_gropdf: Option<&Path>) -> (
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
-            .unwrap_or_else(|e| die!("failed to spawn groff -Z: {e}"));
+            .map_err(|e| Error::io("failed to spawn groff -Z", e))?;
 
         {
             let mut stdin = troff_child
                 .stdin
                 .take()
-                .unwrap_or_else(|| die!("failed to open stdin for groff -Z"));
+                .ok_or_else(|| Error::Process("failed to open stdin for groff -Z".to_string()))?;
             stdin
                 .write_all(input.as_bytes())
-                .unwrap_or_else(|e| die!("failed to write to groff -Z stdin: {e}"));
+                .map_err(|e| Error::io("failed to write to groff -Z stdin", e))?;
         }
 
         let troff_output = troff_child
             .wait_with_output()
-            .unwrap_or_else(|e| die!("failed to read groff -Z output: {e}"));
+            .map_err(|e| Error::io("failed to read groff -Z output", e))?;
         if !troff_output.stderr.is_empty() {
             let _ = io::stderr().write(&troff_output.stderr);
         }
+        check_status("groff -Z", troff_output.status)?;
         let groff_z_time = start.elapsed();
         if crate::log::get_log_level() >= 1 { eprintln!("groff -Z:\t\t{:?}", groff_z_time); }
 
@@ -423,32 +538,34 @@ pub fn grotopdf(config: &Config, input: &str, custom_gropdf: Option<&Path>) -> (
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
-            .unwrap_or_else(|e| die!("failed to spawn gropdf_zig: {e}"));
+            .map_err(|e| Error::io("failed to spawn gropdf_zig", e))?;
 
         {
             let mut stdin = gropdf_child
                 .stdin
                 .take()
-                .unwrap_or_else(|| die!("failed to open stdin for gropdf_zig"));
+                .ok_or_else(|| Error::Process("failed to open stdin for gropdf_zig".to_string()))?;
             stdin
                 .write_all(&troff_output.stdout)
-                .unwrap_or_else(|e| die!("failed to write to gropdf_zig stdin: {e}"));
+                .map_err(|e| Error::io("failed to write to gropdf_zig stdin", e))?;
         }
 
         let output = gropdf_child
             .wait_with_output()
-            .unwrap_or_else(|e| die!("failed to read gropdf_zig output: {e}"));
+            .map_err(|e| Error::io("failed to read gropdf_zig output", e))?;
         if !output.stderr.is_empty() {
             let _ = io::stderr().write(&output.stderr);
         }
+        check_status("gropdf_zig", output.status)?;
         let gropdf_time = start.elapsed();
         if crate::log::get_log_level() >= 1 { eprintln!("gropdf_zig:\t\t{:?}", gropdf_time); }
-        (output.stdout, vec![groff_z_time, gropdf_time])
+        Ok((output.stdout, vec![groff_z_time, gropdf_time]))
     } else {
         let start = Instant::now();
         let mut child = Command::new("/usr/bin/env")
             .arg("groff")
             .arg("-Tpdf")
+            .arg("-t") // preprocess with tbl for tables
             .arg("-mom")
             .arg(format!("-m{}", config.lang))
             .args(["-K", "UTF-8"]) // process with preconv to support utf-8
@@ -456,24 +573,218 @@ pub fn grotopdf(config: &Config, input: &str, custom_gropdf: Option<&Path>) -> (
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
-            .unwrap_or_else(|e| die!("failed to spawn groff: {e}"));
+            .map_err(|e| Error::io("failed to spawn groff", e))?;
 
         {
             // this lexical block is only here to let stdin run out of scope to be closed...
             let mut stdin = child.stdin.take()
-                .unwrap_or_else(|| die!("failed to open stdin for groff"));
+                .ok_or_else(|| Error::Process("failed to open stdin for groff".to_string()))?;
             stdin
                 .write_all(input.as_bytes())
-                .unwrap_or_else(|e| die!("failed to write to groff stdin: {e}"));
+                .map_err(|e| Error::io("failed to write to groff stdin", e))?;
         }
         // ... otherwise this call would not terminate
         let output = child.wait_with_output()
-            .unwrap_or_else(|e| die!("failed to read groff output: {e}"));
+            .map_err(|e| Error::io("failed to read groff output", e))?;
         if !output.stderr.is_empty() {
             let _ = io::stderr().write(&output.stderr);
         }
+        check_status("groff", output.status)?;
         let groff_time = start.elapsed();
         if crate::log::get_log_level() >= 1 { eprintln!("groff:\t\t\t{:?}", groff_time); }
-        (output.stdout, vec![groff_time])
+        Ok((output.stdout, vec![groff_time]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opts;
+
+    // --- establish_log_level ---
+
+    fn parse_flags(flags: &[&str]) -> opts::ParserResult {
+        let p = opts::Parser::new();
+        let mut args = vec!["cmd".to_string()];
+        args.extend(flags.iter().map(|s| s.to_string()));
+        p.parse(args)
+    }
+
+    #[test]
+    fn log_level_default_is_zero() {
+        let r = parse_flags(&[]);
+        assert_eq!(establish_log_level(&r), 0);
+    }
+
+    #[test]
+    fn log_level_verbose_is_one() {
+        let r = parse_flags(&["--verbose"]);
+        assert_eq!(establish_log_level(&r), 1);
+    }
+
+    #[test]
+    fn log_level_debug_is_two() {
+        let r = parse_flags(&["--debug"]);
+        assert_eq!(establish_log_level(&r), 2);
+    }
+
+    #[test]
+    fn log_level_trace_is_three() {
+        let r = parse_flags(&["--trace"]);
+        assert_eq!(establish_log_level(&r), 3);
+    }
+
+    // --- replace_file_extension ---
+
+    #[test]
+    fn replace_extension_changes_suffix() {
+        let result = replace_file_extension("doc.md", "pdf");
+        assert_eq!(result, std::path::PathBuf::from("doc.pdf"));
+    }
+
+    #[test]
+    fn replace_extension_on_path_with_dirs() {
+        let result = replace_file_extension("/home/user/docs/file.md", "groff");
+        assert_eq!(
+            result,
+            std::path::PathBuf::from("/home/user/docs/file.groff")
+        );
+    }
+
+    // --- parent_dir ---
+
+    #[test]
+    fn parent_dir_returns_containing_directory() {
+        assert_eq!(parent_dir("/home/user/file.md"), std::path::Path::new("/home/user"));
+    }
+
+    // --- locate_and_load_preamble ---
+
+    #[test]
+    fn skip_preamble_returns_empty_string() {
+        let mut config = Config::default();
+        config.source_file = "/some/file.md".to_string();
+        config.skip_preamble = true;
+        let result = locate_and_load_preamble(&config, "preamble.mom", "DEFAULT").unwrap();
+        assert_eq!(result, "");
+    }
+
+    #[test]
+    fn falls_back_to_default_preamble_when_no_file_found() {
+        let mut config = Config::default();
+        // Point to a directory that definitely has no preamble.mom
+        config.source_file = "/nonexistent/path/file.md".to_string();
+        config.skip_preamble = false;
+        // Point XDG_CONFIG_HOME to a nonexistent path so neither it nor the
+        // $HOME/.config fallback can accidentally pick up a real preamble.
+        std::env::set_var("XDG_CONFIG_HOME", "/nonexistent/xdg_config_home");
+        let result = locate_and_load_preamble(&config, "preamble.mom", "MY_DEFAULT").unwrap();
+        assert_eq!(result, "MY_DEFAULT");
+    }
+
+    #[test]
+    fn loads_sibling_preamble_when_present() {
+        let dir = std::env::temp_dir().join("mato_test_preamble");
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("doc.md");
+        let preamble = dir.join("preamble.mom");
+        std::fs::write(&source, "# Hello").unwrap();
+        std::fs::write(&preamble, "SIBLING_PREAMBLE").unwrap();
+
+        let mut config = Config::default();
+        config.source_file = source.to_str().unwrap().to_string();
+        config.skip_preamble = false;
+
+        let result = locate_and_load_preamble(&config, "preamble.mom", "DEFAULT").unwrap();
+        // Clean up
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(result, "SIBLING_PREAMBLE");
+    }
+
+    // --- errors ---
+
+    #[derive(Debug)]
+    struct Failing;
+
+    impl Process for Failing {
+        fn process(&mut self, _exp: Tree) -> Result<Tree> {
+            Err(Error::Process("cannot do this".to_string()))
+        }
+    }
+
+    #[test]
+    fn processor_errors_are_returned() {
+        let config = Config::default();
+        let mut chain = process::chain::new(process::identity::new(), Box::new(Failing));
+        let mut render: Box<dyn Render + '_> = Box::new(render::markdown::Renderer::new());
+        let result = transform(&mut render, &mut chain, &config, "text");
+        assert_eq!(result.unwrap_err().to_string(), "cannot do this");
+    }
+
+    #[test]
+    fn failing_commands_are_errors() {
+        assert!(spawn(vec!["true"]).is_ok());
+        assert_eq!(
+            spawn(vec!["false"]).unwrap_err().to_string(),
+            "false failed: exit status: 1"
+        );
+        assert_eq!(exec(vec!["echo", "hi"]).unwrap(), "hi");
+        assert!(exec(vec!["sh", "-c", "echo hi; exit 2"]).is_err());
+    }
+
+    // --- joining ---
+
+    #[test]
+    fn documents_are_joined_with_one_meta_data_block() {
+        let joined = join_documents(vec![
+            parse("a.md", "---\ntitle: A\n---\n# One\n"),
+            parse("b.md", "---\ntitle: B\nauthor: X\ndoctype: CHAPTER\n---\n# Two\n"),
+        ]);
+        let joined = format!("{joined:?}");
+        assert!(joined.starts_with("Document(CHAPTER, Block([MetaDataBlock("));
+        assert_eq!(joined.matches("MetaDataBlock").count(), 1);
+        assert!(joined.contains("MetaDataItem(\"title\", \"A\")"));
+        assert!(!joined.contains("MetaDataItem(\"title\", \"B\")"));
+        assert!(joined.contains("MetaDataItem(\"author\", \"X\")"));
+        assert!(joined.find("One").unwrap() < joined.find("Two").unwrap());
+    }
+
+    #[test]
+    fn diagnostics_name_the_joined_file_they_are_found_in() {
+        let inputs = [
+            ("c.md".to_string(), "text[^n]\n\n[^n]: note\n".to_string()),
+            ("d.md".to_string(), "a\n\nb\n\nc[^m]\n".to_string()),
+        ];
+        let (tree, sources) = parse_all(&inputs);
+        let mut footnotes = process::footnotes::new();
+        footnotes.process(tree).unwrap();
+        let diagnostics: Vec<String> = footnotes
+            .take_diagnostics()
+            .iter()
+            .map(|d| format!("{}:{d}", source_of(&sources, d)))
+            .collect();
+        assert_eq!(diagnostics, ["d.md:5:2: warning: undefined footnote [^m]"]);
+    }
+
+    #[test]
+    fn joined_documents_start_new_paragraphs() {
+        let join = |inputs: &[&str]| {
+            let documents = inputs.iter().map(|input| parse("a.md", input)).collect();
+            let mut render: Box<dyn Render + '_> = Box::new(render::markdown::Renderer::new());
+            render.render(join_documents(documents))
+        };
+        assert_eq!(join(&["Text a.", "Text b."]), join(&["Text a.\n\nText b."]));
+        assert_eq!(join(&["Text a.\n", "Text b.\n"]), join(&["Text a.\n\nText b.\n"]));
+        assert_eq!(join(&["Text a.\n\n", "Text b."]), join(&["Text a.\n\nText b."]));
+        assert_eq!(join(&["Text a.", "", "Text b."]), join(&["Text a.\n\nText b."]));
+    }
+
+    #[test]
+    fn missing_input_is_an_error() {
+        let result = read_input("/nonexistent/path/file.md");
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("cannot read '/nonexistent/path/file.md': "));
     }
 }
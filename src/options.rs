@@ -0,0 +1,127 @@
+//! the command line options shared by the commands rendering for a device, `mato`,
+//! `matogro` and `matofmt`, and how they configure a transformation
+use crate::config::{file, Config};
+use crate::opts::{self, Opt, ParserResult};
+use crate::process::{external, registry};
+use crate::render::device::{self, Device};
+use crate::{opt_flag, opt_val, Error, Result};
+
+/// the shared options, registered with an [`opts::Parser`]
+pub struct DeviceOpts {
+    lang: Opt,
+    filter: Opt,
+    document_filters: Opt,
+    process: Opt,
+    no_process: Opt,
+    list_processors: Opt,
+    list_devices: Opt,
+    dump_dot_file: Opt,
+    dot_clusters: Opt,
+}
+
+impl DeviceOpts {
+    /// registers the shared options with `p`
+    pub fn add(p: &mut opts::Parser) -> Self {
+        DeviceOpts {
+            lang: p.add_opt(opt_val!("l", "lang", "Set document language.", "den")),
+            filter: p.add_opt(opt_val!(
+                "F",
+                "filter",
+                "Pipe the tree through external filter commands, separated by commas.",
+                ""
+            )),
+            document_filters: p.add_opt(opt_flag!(
+                "A",
+                "document-filters",
+                "Also run the external filters named in the meta data of the document."
+            )),
+            process: p.add_opt(opt_val!(
+                "P",
+                "process",
+                "Processors to run, separated by commas. 'default' stands for the usual ones.",
+                ""
+            )),
+            no_process: p.add_opt(opt_val!(
+                "N",
+                "no-process",
+                "Processors not to run, separated by commas.",
+                ""
+            )),
+            list_processors: p.add_opt(opt_flag!(
+                "L",
+                "list-processors",
+                "List the available processors and exit."
+            )),
+            list_devices: p.add_opt(opt_flag!(
+                "D",
+                "list-devices",
+                "List the available devices and exit."
+            )),
+            dump_dot_file: p.add_opt(opt_flag!(
+                "X",
+                "dump-dot-file",
+                "Write graphviz dot representation of parse tree to file."
+            )),
+            dot_clusters: p.add_opt(opt_flag!(
+                "C",
+                "dot-clusters",
+                "Group inline nodes into clusters in the dot representation."
+            )),
+        }
+    }
+
+    /// the listing asked for with `--list-processors` or `--list-devices`, if any
+    pub fn listing(&self, r: &ParserResult) -> Option<String> {
+        if self.list_processors.is_set(r) {
+            Some(registry::describe())
+        } else if self.list_devices.is_set(r) {
+            Some(device::describe())
+        } else {
+            None
+        }
+    }
+
+    /// configures the transformation of the source files given as parameters for
+    /// `device`. no file, or `-`, stands for standard input. settings from `mato.toml`
+    /// override the defaults, the command line overrides both.
+    pub fn configure(&self, r: &ParserResult, device: &Device) -> Result<Config> {
+        let mut config = Config::default();
+        config.source_files = r
+            .params
+            .iter()
+            .map(|param| if param == "-" { String::new() } else { param.clone() })
+            .collect();
+        if config.source_files.is_empty() {
+            config.source_files.push(String::new());
+        }
+        config.source_file = config.source_files[0].clone();
+
+        let file_config = file::locate_and_load(&config.source_file)?;
+        config.lang = self.lang.val(r);
+        file_config.apply(&mut config);
+        if let Some(lang) = self.lang.given(r) {
+            config.lang = lang;
+        }
+        let filters = self.filter.given(r);
+        if let Some(filters) = &filters {
+            config.filters = external::split_commands(filters);
+        }
+        config.document_filters |= self.document_filters.is_set(r);
+        config.dump_dot_file = self.dump_dot_file.is_set(r);
+        config.dot_clusters |= self.dot_clusters.is_set(r);
+
+        config.processors = registry::resolve(
+            device.processors,
+            &self.process.given(r).or(file_config.process).unwrap_or_default(),
+            &self.no_process.given(r).or(file_config.no_process).unwrap_or_default(),
+        )
+        .map_err(Error::Config)?;
+        if filters.is_some() && !config.processors.iter().any(|name| name == "external") {
+            return Err(Error::Config(format!(
+                "--filter needs the external processor, which is not run for device {}",
+                device.name
+            )));
+        }
+        Ok(config)
+    }
+}
@@ -40,6 +40,20 @@ impl Opt {
             _ => "".to_owned(),
         }
     }
+
+    /// the value, if the option was given on the command line, `None` if
+    /// the default would be used
+    pub fn given(&self, r: &ParserResult) -> Option<String> {
+        match self {
+            Opt::Value {
+                short_name: _,
+                long_name,
+                description: _,
+                default: _,
+            } => r.opts.get(long_name).cloned(),
+            _ => None,
+        }
+    }
 }
 /// Parser captures vectors of Opts and ValOpts
 pub struct Parser {
@@ -1,7 +1,9 @@
+use crate::diagnostic::Diagnostic;
 use crate::syntax::{
-    bold, color, empty, escape_lit, footnote, heading, hyperref, image, image_size, list,
-    list_item, lit, meta_data_item, prelit, DocType, Tree,
+    block, bold, color, empty, escape_lit, footnote, heading, hyperref, image, image_size, list,
+    list_item, lit, meta_data_item, prelit, Alignment, DocType, ListKind, Span, Tree,
 };
+use std::collections::HashMap;
 use std::str;
 
 /// holds parsing state
@@ -14,14 +16,185 @@ pub struct Parser<'a> {
     /// the current position of parsing
     current_position: usize,
     current_line: usize,
+    /// the position at which the current line starts
+    line_start: usize,
     /// the character at the current parsing position
     current_char: u8,
     doc_type: String,
+    /// problems found so far
+    diagnostics: Vec<Diagnostic>,
+    /// constructs known to be malformed, keyed by their start offset and kind, so that we do
+    /// not parse them over and over again when an enclosing construct is re-parsed
+    failed: HashMap<(usize, &'static str), Diagnostic>,
+    /// the line our input starts on
+    first_line: usize,
+    /// per line from `first_line` on, the number of bytes stripped from its start before it
+    /// was handed to us, like the `> ` of a block quote. columns are reported including them.
+    line_prefixes: Vec<usize>,
 }
 
+/// a point in the input, from which spans of nodes are constructed
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+/// the parser state at the start of a construct, to rewind to, if the construct is malformed
+struct Mark {
+    position: Position,
+    line_start: usize,
+    diagnostics: usize,
+    doc_type: String,
+}
+
+/// a character we expected, but did not find
+#[derive(Debug)]
+struct Unexpected {
+    expected: u8,
+    /// `None` at the end of the input
+    found: Option<u8>,
+    at: Position,
+}
+
+type ParseResult<T> = Result<T, Unexpected>;
+
 /// indentation unit of lists in spaces
 const LIST_INDENT: u8 = 2;
 
+/// indentation of the lines continuing a footnote definition
+const FOOTNOTE_INDENT: usize = 4;
+
+/// lines of the input with some prefix stripped, like the `>` of block quotes, to be parsed
+/// on their own
+#[derive(Default)]
+struct NestedSource {
+    content: String,
+    /// per line: its offset in `content`, its offset in the input and the number of columns
+    /// before its content in the input
+    lines: Vec<(usize, usize, usize)>,
+    /// the input line of the first line
+    first_line: usize,
+}
+
+/// appends an expression to the children of a sequence. an empty expression at the
+/// beginning is replaced by the next one, the children of blocks are spliced in.
+fn push_expression(children: &mut Vec<Tree>, expr: Tree) {
+    if let [Tree::Empty(_)] = children.as_slice() {
+        children.clear();
+    }
+    match expr {
+        Tree::Block(blocks, _) => children.extend(blocks),
+        _ => children.push(expr),
+    }
+}
+
+/// makes a sequence of `children` using `kind`, unless there is only one or none
+fn sequence(mut children: Vec<Tree>, kind: fn(Vec<Tree>, Span) -> Tree) -> Tree {
+    match children.len() {
+        0 => empty(),
+        1 => children.pop().unwrap(),
+        _ => kind(children, Span::default()),
+    }
+}
+
+/// true, if `address` starts with a scheme like `https:` or `irc:`
+fn is_uri(address: &[u8]) -> bool {
+    let Some(colon) = address.iter().position(|&c| c == b':') else {
+        return false;
+    };
+    (2..=32).contains(&colon)
+        && address[0].is_ascii_alphabetic()
+        && address[1..colon]
+            .iter()
+            .all(|&c| c.is_ascii_alphanumeric() || b"+.-".contains(&c))
+}
+
+/// true, if `address` looks like an e-mail address `name@host.domain`
+fn is_email(address: &[u8]) -> bool {
+    let Some(at) = address.iter().position(|&c| c == b'@') else {
+        return false;
+    };
+    let (name, host) = (&address[..at], &address[at + 1..]);
+    !name.is_empty()
+        && name
+            .iter()
+            .all(|&c| c.is_ascii_alphanumeric() || b".!#$%&'*+/=?^_`{|}~-".contains(&c))
+        && host.split(|&c| c == b'.').all(|label| {
+            !label.is_empty() && label.iter().all(|&c| c.is_ascii_alphanumeric() || c == b'-')
+        })
+}
+
+/// describes a character for use in diagnostics
+fn describe(char: Option<u8>) -> String {
+    match char {
+        None => "end of input".to_string(),
+        Some(b'\n') => "line break".to_string(),
+        Some(c) if c.is_ascii_graphic() || c == b' ' => format!("'{}'", c as char),
+        Some(_) => "non-ASCII character".to_string(),
+    }
+}
+
+/// splits a table row into cells, returning the byte ranges of the cell contents without
+/// surrounding white space. leading and trailing pipes are optional, escaped pipes (`\|`)
+/// do not separate cells.
+fn table_cells(row: &[u8]) -> Vec<(usize, usize)> {
+    let mut bounds = vec![];
+    let mut cell_start = 0;
+    for (i, &c) in row.iter().enumerate() {
+        if c == b'|' && (i == 0 || row[i - 1] != b'\\') {
+            bounds.push((cell_start, i));
+            cell_start = i + 1;
+        }
+    }
+    bounds.push((cell_start, row.len()));
+    let trim = |(mut start, mut end): (usize, usize)| {
+        while start < end && row[start].is_ascii_whitespace() {
+            start += 1;
+        }
+        while end > start && row[end - 1].is_ascii_whitespace() {
+            end -= 1;
+        }
+        (start, end)
+    };
+    let mut cells: Vec<(usize, usize)> = bounds.into_iter().map(trim).collect();
+    // an empty first or last cell comes from a leading or trailing pipe
+    if cells.len() > 1 && cells[0].0 == cells[0].1 {
+        cells.remove(0);
+    }
+    if cells.len() > 1 && cells[cells.len() - 1].0 == cells[cells.len() - 1].1 {
+        cells.pop();
+    }
+    cells
+}
+
+/// reads the column alignments from a delimiter row like `|:---|:---:|---:|`.
+/// returns `None`, if `row` is not a delimiter row.
+fn table_alignments(row: &[u8]) -> Option<Vec<Alignment>> {
+    if !row.contains(&b'|') {
+        return None;
+    }
+    table_cells(row)
+        .into_iter()
+        .map(|(start, end)| {
+            let cell = &row[start..end];
+            let left = cell.first() == Some(&b':');
+            let right = cell.len() > 1 && cell.last() == Some(&b':');
+            let dashes = &cell[left as usize..cell.len() - right as usize];
+            if dashes.is_empty() || dashes.iter().any(|&c| c != b'-') {
+                return None;
+            }
+            Some(match (left, right) {
+                (true, true) => Alignment::Center,
+                (true, false) => Alignment::Left,
+                (false, true) => Alignment::Right,
+                (false, false) => Alignment::Default,
+            })
+        })
+        .collect()
+}
+
 impl Parser<'_> {
     fn new(input: &str) -> Parser<'_> {
         let input_byte_slice = input.as_bytes();
@@ -31,24 +204,37 @@ impl Parser<'_> {
             current_position: 0,
             current_char: input_byte_slice[0],
             current_line: 1,
+            line_start: 0,
             doc_type: "".to_owned(),
+            diagnostics: vec![],
+            failed: HashMap::new(),
+            first_line: 1,
+            line_prefixes: vec![],
         }
     }
 
+    /// parses `input` into a document. malformed constructs are kept as literal
+    /// text and reported in the returned diagnostics, so parsing never fails.
     #[must_use]
-    pub fn parse(input: &str) -> Tree {
+    pub fn parse(input: &str) -> (Tree, Vec<Diagnostic>) {
         if input.is_empty() {
-            Tree::Document(DocType::DEFAULT, Box::new(empty()))
+            (
+                Tree::Document(DocType::DEFAULT, Box::new(empty()), Span::default()),
+                vec![],
+            )
         } else {
             let mut parser = Parser::new(input);
+            let start = parser.position();
             // passing "" as bytes parses until the end of file
             let ast = Box::new(parser.parse_complete_until(b""));
-            match parser.doc_type.to_uppercase().as_ref() {
-                "SLIDES" => Tree::Document(DocType::SLIDES, ast),
-                "CHAPTER" => Tree::Document(DocType::CHAPTER, ast),
-                "LETTER" => Tree::Document(DocType::LETTER, ast),
-                _ => Tree::Document(DocType::DEFAULT, ast),
-            }
+            let span = parser.span_from(start);
+            let doc_type = match parser.doc_type.to_uppercase().as_ref() {
+                "SLIDES" => DocType::SLIDES,
+                "CHAPTER" => DocType::CHAPTER,
+                "LETTER" => DocType::LETTER,
+                _ => DocType::DEFAULT,
+            };
+            (Tree::Document(doc_type, ast, span), parser.diagnostics)
         }
     }
 
@@ -57,6 +243,7 @@ impl Parser<'_> {
     fn advance(&mut self) {
         if self.current_char == b'\n' {
             self.current_line += 1;
+            self.line_start = self.current_position + 1;
         }
         self.current_position += 1;
         if !self.at_end() {
@@ -66,6 +253,104 @@ impl Parser<'_> {
         }
     }
 
+    /// the current parsing position
+    fn position(&self) -> Position {
+        let prefix = self
+            .current_line
+            .checked_sub(self.first_line)
+            .and_then(|line| self.line_prefixes.get(line))
+            .unwrap_or(&0);
+        Position {
+            offset: self.current_position,
+            line: self.current_line,
+            column: self.current_position - self.line_start + 1 + prefix,
+        }
+    }
+
+    /// constructs a span reaching from `start` up to the current position
+    fn span_from(&self, start: Position) -> Span {
+        let end = self.position();
+        Span {
+            start: start.offset,
+            end: end.offset,
+            line: start.line,
+            column: start.column,
+            end_line: end.line,
+            end_column: end.column,
+        }
+    }
+
+    /// attaches a span reaching from `start` up to the current position to `tree`
+    fn spanned(&self, tree: Tree, start: Position) -> Tree {
+        tree.with_span(self.span_from(start))
+    }
+
+    /// saves the current state, so that we can rewind to it
+    fn mark(&self) -> Mark {
+        Mark {
+            position: self.position(),
+            line_start: self.line_start,
+            diagnostics: self.diagnostics.len(),
+            doc_type: self.doc_type.clone(),
+        }
+    }
+
+    /// rewinds to a previously saved state, dropping diagnostics recorded since then
+    fn reset(&mut self, mark: Mark) {
+        self.current_position = mark.position.offset;
+        self.current_line = mark.position.line;
+        self.line_start = mark.line_start;
+        self.current_char = if self.at_end() {
+            4 // EOF
+        } else {
+            self.input[self.current_position]
+        };
+        self.diagnostics.truncate(mark.diagnostics);
+        self.doc_type = mark.doc_type;
+    }
+
+    /// parses a construct beginning with `opening` using `parse_construct`. if the construct
+    /// turns out to be malformed, we rewind to its start, record a diagnostic and return
+    /// `opening` as a literal, so the rest of it is parsed as ordinary text.
+    fn attempt(
+        &mut self,
+        what: &'static str,
+        opening: &str,
+        parse_construct: fn(&mut Self) -> ParseResult<Tree>,
+    ) -> Tree {
+        let start = self.position();
+        let key = (start.offset, what);
+        let diagnostic = match self.failed.get(&key) {
+            Some(diagnostic) => diagnostic.clone(),
+            None => {
+                let mark = self.mark();
+                match parse_construct(self) {
+                    Ok(tree) => return tree,
+                    Err(unexpected) => {
+                        self.reset(mark);
+                        let diagnostic = Diagnostic::error(
+                            &format!(
+                                "could not parse {what}: expected {} at line {}, column {}, but found {}",
+                                describe(Some(unexpected.expected)),
+                                unexpected.at.line,
+                                unexpected.at.column,
+                                describe(unexpected.found)
+                            ),
+                            self.span_from(start),
+                        );
+                        self.failed.insert(key, diagnostic.clone());
+                        diagnostic
+                    }
+                }
+            }
+        };
+        self.diagnostics.push(diagnostic);
+        for char in opening.bytes() {
+            self.skip(char);
+        }
+        self.spanned(lit(opening), start)
+    }
+
     /// true, if current index is equal to or greater than the
     /// input string length
     const fn at_end(&self) -> bool {
@@ -93,45 +378,50 @@ impl Parser<'_> {
         }
     }
 
-    /// eat up a given character, or panic if that is not found at
+    /// eat up a given character, or fail if that is not found at
     /// the current position or if we are already at the end of the
     /// input string
-    fn consume(&mut self, char: u8) {
-        assert!(
-            !self.at_end(),
-            "at line {}: consume({}): index {} out of bounds {}; was expecting '{}' but hit EOF before.",
-            self.current_line,
-            char as char,
-            self.current_position,
-            self.input_len,
-            char as char
-        );
-        assert!(
-            self.current_char == char,
-            "at line {}: expected char '{}' at index {}, but found '{}'",
-            self.current_line,
-            char as char,
-            self.current_position,
-            self.current_char as char
-        );
+    fn consume(&mut self, char: u8) -> ParseResult<()> {
+        if self.at_end() || self.current_char != char {
+            return Err(Unexpected {
+                expected: char,
+                found: (!self.at_end()).then_some(self.current_char),
+                at: self.position(),
+            });
+        }
+        self.advance();
+        Ok(())
+    }
+
+    /// eat up a character the caller already knows to be at the current position
+    fn skip(&mut self, char: u8) {
+        debug_assert_eq!(self.current_char, char, "at line {}", self.current_line);
         self.advance();
     }
 
+    /// skips the given character and returns it as a literal
+    fn skip_as_lit(&mut self, char: u8, literal: &str) -> Tree {
+        let start = self.position();
+        self.skip(char);
+        self.spanned(lit(literal), start)
+    }
+
     /// parse a symmetrically quoted sub string, like
     /// something enclosed in a " pair
     fn parse_symmetric_quoted(&mut self) -> Tree {
         let break_char = self.current_char;
-        self.consume(break_char); // opening quote
+        self.skip(break_char); // opening quote
         let exp = self.parse_complete_until(&[break_char]); // body
         if !self.at_end() && self.current_char == break_char {
-            self.consume(break_char); // ending quote
+            self.skip(break_char); // ending quote
         }
         exp
     }
 
     fn try_bold_or_lit_until(&mut self, break_chars: &[u8]) -> Tree {
+        let start = self.position();
         let break_char = self.current_char;
-        self.consume(break_char); // opening quote
+        let opening = self.skip_as_lit(break_char, "*"); // opening quote
         let exp = self.parse_format_until(
             format!(
                 "{}{}",
@@ -141,12 +431,12 @@ impl Parser<'_> {
             .as_bytes(),
         ); // body
         if self.current_char == break_char {
-            self.consume(break_char); // ending quote
-            Tree::Bold(Box::new(exp))
+            self.skip(break_char); // ending quote
+            self.spanned(Tree::Bold(Box::new(exp), Span::default()), start)
         } else {
             eprintln!("current char: {}, {:?}", self.current_char as char, exp);
             // we consumed the '*', so we prepend it again
-            lit("*").cat(exp)
+            opening.cat(exp)
         }
     }
 
@@ -155,24 +445,24 @@ impl Parser<'_> {
     fn parse_quoted_base(
         &mut self,
         break_char: u8,
-        func: for<'a, 'b> fn(&'a mut Parser, &'b [u8]) -> Tree,
-    ) -> Tree {
-        self.consume(self.current_char); // opening quote
+        func: fn(&mut Self, &[u8]) -> Tree,
+    ) -> ParseResult<Tree> {
+        self.skip(self.current_char); // opening quote
         let exp = func(self, &[break_char]); // body
-        self.consume(break_char); // ending quote
-        exp
+        self.consume(break_char)?; // ending quote
+        Ok(exp)
     }
 
     /// parse an asymmetrically quoted substring, like
     /// something enclosed in a pair of parentheses, ( and ).
-    fn parse_quoted(&mut self, break_char: u8) -> Tree {
-        self.parse_quoted_base(break_char, |a, b| Parser::parse_complete_until(a, b))
+    fn parse_quoted(&mut self, break_char: u8) -> ParseResult<Tree> {
+        self.parse_quoted_base(break_char, Self::parse_complete_until)
     }
 
     /// parse an asymmetrically quoted substring, like
     /// something enclosed in a pair of parentheses, ( and ).
-    fn parse_quoted_literal(&mut self, break_char: u8) -> Tree {
-        self.parse_quoted_base(break_char, |a, b| Parser::parse_literal(a, b))
+    fn parse_quoted_literal(&mut self, break_char: u8) -> ParseResult<Tree> {
+        self.parse_quoted_base(break_char, Self::parse_literal)
     }
 
     /// advances over input string until a non-hash character is encountered and returns number of
@@ -196,28 +486,64 @@ impl Parser<'_> {
     /// `# heading` is a level 1 heading,
     /// `## heading` is a level 2 heading.
     fn parse_heading(&mut self) -> Tree {
-        self.consume(b'#');
+        let start = self.position();
+        self.skip(b'#');
         let level = self.parse_heading_level(0);
-        let literal = self.parse_literal(b"/\n");
+        let mut literal = self.parse_heading_text();
         let mut heading_name = "".to_string();
         if self.current_char == b'/' {
-            self.consume(b'/');
-            heading_name = self.parse_string_until(b"/");
-            self.consume(b'/');
+            let slash = self.position();
+            self.skip(b'/');
+            let name = self.parse_string_until(b"/\n");
+            if self.current_char == b'/' {
+                self.skip(b'/');
+                heading_name = name;
+            } else {
+                // no closing slash, so this was not an anchor, but part of the heading
+                literal = literal.cat(self.spanned(lit(&format!("/{name}")), slash));
+            }
         }
-        let result = heading(literal, level, &heading_name);
+        let result = self.spanned(heading(literal, level, &heading_name), start);
         if self.at_end() {
             return result;
         }
-        if self.peek(2, b'#') && level != 2 {
+        if self.current_char == b'\n' && self.peek(2, b'#') && level != 2 {
             // this heading is followed by another heading, so we slurp away the newline
             // so that there is not too much vertical white space in between them
-            self.consume(b'\n');
+            self.skip(b'\n');
             result
         } else {
             // heading is not followed by another heading, so we insert a VSpace node.
-            result.cat(Tree::VSpace())
+            let v_space = self.spanned(Tree::VSpace(Span::default()), self.position());
+            block(vec![result, v_space])
+        }
+    }
+
+    /// the text of a heading is literal, except for footnote references. the slashes of
+    /// a url, `scheme://`, do not start an anchor name
+    fn parse_heading_text(&mut self) -> Tree {
+        let mut text = self.parse_literal(b"/\n[");
+        while self.current_char == b'[' || self.at_scheme_slashes() {
+            let start = self.position();
+            let next = if self.current_char == b'/' {
+                self.skip(b'/');
+                self.skip(b'/');
+                lit("//")
+            } else {
+                match self.footnote_label() {
+                    Some(label) => self.parse_footnote_reference(label),
+                    None => {
+                        self.skip(b'[');
+                        lit("[")
+                    }
+                }
+            };
+            text = text.cat(self.spanned(next, start));
+            if !self.at_end() && !b"/\n[".contains(&self.current_char) {
+                text = text.cat(self.parse_literal(b"/\n["));
+            }
         }
+        text
     }
 
     /// prase a markdown footnote, of the form:
@@ -226,18 +552,28 @@ impl Parser<'_> {
     ///
     /// if the tilde `^` is not followed by `(`, a literal tilde is returned
     fn parse_footnote(&mut self) -> Tree {
-        self.consume(b'^');
-        match self.current_char {
-            b'(' => footnote(self.parse_quoted(b')')),
-            _ => lit("^"),
+        if self.peek(1, b'(') {
+            self.attempt("footnote", "^(", |p| {
+                p.skip(b'^');
+                Ok(footnote(p.parse_quoted(b')')?))
+            })
+        } else {
+            self.skip(b'^');
+            lit("^")
         }
     }
 
+    fn parse_small_caps(&mut self) -> Tree {
+        self.attempt("small caps", "{", |p| {
+            Ok(Tree::SmallCaps(Box::new(p.parse_quoted(b'}')?), Span::default()))
+        })
+    }
+
     fn count_drop_cap_level(&mut self) -> u8 {
         let mut level = 0;
         while self.current_char == b'%' {
             level += 1;
-            self.consume(b'%');
+            self.skip(b'%');
         }
         level
     }
@@ -253,76 +589,418 @@ impl Parser<'_> {
             // cap of 1 does not make any sense. so if the user
             // specifies a single '%' we have a character that
             // drops _one_ line and spans _two_.
-            Tree::DropCap(drop_cap_char, drop_cap_level + 1)
+            Tree::DropCap(drop_cap_char, drop_cap_level + 1, Span::default())
         } else {
-            self.consume(b'%');
+            self.skip(b'%');
             lit("%")
         }
     }
 
-    fn parse_color_spec(&mut self) -> Tree {
-        self.consume(b'\\');
-        match self.current_char {
-            b'{' => color(self.parse_quoted_literal(b'}')),
-            _ => lit("\\"),
+    /// a backslash starts a color spec `\{color}` or escapes the punctuation character
+    /// following it, which is then taken literally
+    fn parse_color_spec_or_escape(&mut self) -> Tree {
+        if self.peek(1, b'{') {
+            self.attempt("color spec", "\\{", |p| {
+                p.skip(b'\\');
+                Ok(color(p.parse_quoted_literal(b'}')?))
+            })
+        } else if self.current_position + 1 < self.input_len
+            && self.input[self.current_position + 1].is_ascii_punctuation()
+        {
+            self.skip(b'\\');
+            let escaped = self.current_char as char;
+            self.advance();
+            Tree::Escaped(escaped, Span::default())
+        } else {
+            self.skip(b'\\');
+            escape_lit("\\")
         }
     }
 
     fn parse_right_sidenote(&mut self) -> Tree {
-        self.consume(b'>');
+        if self.peek(1, b'(') {
+            self.attempt("sidenote", ">(", Self::parse_sidenote_or_chapter_mark)
+        } else if self.peek(1, b'>') && self.peek(2, b'(') {
+            self.attempt("chapter mark", ">>(", Self::parse_sidenote_or_chapter_mark)
+        } else if self.peek(1, b'>') {
+            self.skip(b'>');
+            self.skip(b'>');
+            lit(">>")
+        } else {
+            self.skip(b'>');
+            lit(">")
+        }
+    }
+
+    /// true, if a block quote starts at the current position: a `>` at the start of a line,
+    /// which does not open a sidenote `>(` or a chapter mark `>>(`
+    fn at_block_quote(&self) -> bool {
+        let at_line_start = self.current_position == 0 || self.peek_back(1, b'\n');
+        at_line_start
+            && self.current_char == b'>'
+            && !self.peek(1, b'(')
+            && !(self.peek(1, b'>') && self.peek(2, b'('))
+    }
+
+    /// parses consecutive lines starting with `>` as a block quote. the lines are stripped of
+    /// the `>` and one following space, and what remains is parsed like a document of its own,
+    /// so quotes can contain paragraphs, lists, code blocks and further quotes.
+    fn parse_block_quote(&mut self) -> Tree {
+        let start = self.position();
+        let mut nested = NestedSource::default();
+        while self.at_block_quote() {
+            let prefix = if self.peek(1, b' ') { 2 } else { 1 };
+            self.take_line(&mut nested, prefix);
+        }
+        let quote = self.parse_nested(nested);
+        self.spanned(Tree::BlockQuote(Box::new(quote), Span::default()), start)
+    }
+
+    /// moves the rest of the current line, without its first `prefix` bytes, into `nested`
+    fn take_line(&mut self, nested: &mut NestedSource, prefix: usize) {
+        if nested.lines.is_empty() {
+            nested.first_line = self.current_line;
+        }
+        self.advance_to(self.current_position + prefix);
+        let end = (self.line_end(self.current_position) + 1).min(self.input_len);
+        let prefix = self.position().column - 1;
+        nested.lines.push((nested.content.len(), self.current_position, prefix));
+        nested.content.push_str(str::from_utf8(&self.input[self.current_position..end]).unwrap());
+        self.advance_to(end);
+    }
+
+    /// parses the lines collected in `nested` like a document of its own and moves the spans
+    /// of the result to where the lines are found in our input. the nested parser knows the
+    /// prefixes stripped from its lines, so the lines and columns it reports, in spans and
+    /// diagnostics alike, are ours already.
+    fn parse_nested(&mut self, nested: NestedSource) -> Tree {
+        if nested.content.is_empty() {
+            return empty();
+        }
+        let mut parser = Parser::new(&nested.content);
+        parser.current_line = nested.first_line;
+        parser.first_line = nested.first_line;
+        parser.line_prefixes = nested.lines.iter().map(|&(_, _, prefix)| prefix).collect();
+        let mut tree = parser.parse_complete_until(b"");
+        self.diagnostics.append(&mut parser.diagnostics);
+        let shift_of = |offset: usize| {
+            let line = nested
+                .lines
+                .partition_point(|&(line_start, _, _)| line_start <= offset)
+                .saturating_sub(1);
+            let (line_start, input_offset, _) = nested.lines[line];
+            input_offset - line_start
+        };
+        tree.map_spans(&mut |span: &mut Span| {
+            if span.is_unknown() {
+                return;
+            }
+            span.start += shift_of(span.start);
+            span.end += shift_of(span.end);
+        });
+        tree
+    }
+
+    /// the label of the footnote reference `[^label]` at the current position, if there is one
+    fn footnote_label(&self) -> Option<String> {
+        if !self.peek(1, b'^') {
+            return None;
+        }
+        let label_start = self.current_position + 2;
+        let len = self.input[label_start.min(self.input_len)..self.input_len]
+            .iter()
+            .take_while(|&&c| c != b']' && !c.is_ascii_whitespace())
+            .count();
+        if len == 0 || !self.peek(len + 2, b']') {
+            return None;
+        }
+        Some(str::from_utf8(&self.input[label_start..label_start + len]).unwrap().to_string())
+    }
+
+    /// true, if the footnote reference with `label` at the current position starts a
+    /// definition `[^label]:` at the beginning of a line
+    fn at_footnote_definition(&self, label: &str) -> bool {
+        let at_line_start = self.current_position == 0 || self.peek_back(1, b'\n');
+        at_line_start && self.peek(label.len() + 3, b':')
+    }
+
+    fn parse_footnote_reference(&mut self, label: String) -> Tree {
+        self.advance_to(self.current_position + label.len() + 3);
+        Tree::FootnoteRef(label, Span::default())
+    }
+
+    /// parses a footnote definition `[^label]: text`. lines indented by four spaces continue
+    /// it, also after blank lines, so a definition can span several paragraphs.
+    fn parse_footnote_definition(&mut self, label: String) -> Tree {
+        let start = self.position();
+        let mut nested = NestedSource::default();
+        let mut prefix = label.len() + 4;
+        while self.peek(prefix, b' ') {
+            prefix += 1;
+        }
+        if self.current_position + prefix >= self.input_len || self.peek(prefix, b'\n') {
+            // the text starts on the next line
+            let end = (self.line_end(self.current_position) + 1).min(self.input_len);
+            self.advance_to(end);
+        } else {
+            self.take_line(&mut nested, prefix);
+        }
+        loop {
+            // blank lines belong to the definition, if an indented line follows them
+            let mut next = self.current_position;
+            while next < self.input_len {
+                let end = self.line_end(next);
+                if self.input[next..end].iter().any(|&c| c != b' ') {
+                    break;
+                }
+                next = end + 1;
+            }
+            if next >= self.input_len
+                || !self.input[next..self.input_len].starts_with(&[b' '; FOOTNOTE_INDENT])
+            {
+                break;
+            }
+            while self.current_position < next {
+                let blank = self.line_end(self.current_position) - self.current_position;
+                self.take_line(&mut nested, blank);
+            }
+            self.take_line(&mut nested, FOOTNOTE_INDENT);
+        }
+        let content_len = nested.content.trim_end_matches('\n').len();
+        nested.content.truncate(content_len);
+        let content = self.parse_nested(nested);
+        self.spanned(Tree::FootnoteDef(label, Box::new(content), Span::default()), start)
+    }
+
+    fn parse_sidenote_or_chapter_mark(&mut self) -> ParseResult<Tree> {
+        self.skip(b'>');
         let is_chapter_mark = self.current_char == b'>';
         if is_chapter_mark {
-            self.consume(b'>');
+            self.skip(b'>');
         }
-        match self.current_char {
-            b'(' => {
-                let box_exp = Box::new(self.parse_quoted(b')'));
-                let exp = if is_chapter_mark {
-                    self.consume(b'\n');
-                    Tree::ChapterMark(box_exp)
-                } else {
-                    Tree::RightSidenote(box_exp)
-                };
-                if self.current_char == b' ' {
-                    self.consume(b' ');
+        let box_exp = Box::new(self.parse_quoted(b')')?);
+        let exp = if is_chapter_mark {
+            if !self.at_end() {
+                self.consume(b'\n')?;
+            }
+            Tree::ChapterMark(box_exp, Span::default())
+        } else {
+            Tree::RightSidenote(box_exp, Span::default())
+        };
+        if self.current_char == b' ' {
+            self.skip(b' ');
+        }
+        Ok(exp)
+    }
+
+    /// parses a link definition `[label]: url "title"` standing alone on a line. the url
+    /// may be enclosed in `<>`, the title in `""`, `''` or `()`. returns `None` and leaves
+    /// the position untouched, if there is no definition at the current position.
+    fn parse_link_definition(&mut self) -> Option<Tree> {
+        if !(self.current_position == 0 || self.peek_back(1, b'\n')) {
+            return None;
+        }
+        let mark = self.mark();
+        let start = self.position();
+        self.skip(b'[');
+        let label = self.parse_string_until(b"[]\n");
+        if label.trim().is_empty() || self.current_char != b']' || !self.peek(1, b':') {
+            self.reset(mark);
+            return None;
+        }
+        self.skip(b']');
+        self.skip(b':');
+        self.skip_spaces();
+        let url = if self.current_char == b'<' {
+            self.skip(b'<');
+            let url = self.parse_literal(b">\n");
+            self.consume(b'>').ok().map(|_| url)
+        } else {
+            Some(self.parse_literal(b" \n"))
+        };
+        self.skip_spaces();
+        let title = match self.current_char {
+            b'"' | b'\'' | b'(' => {
+                let closing = if self.current_char == b'(' { b')' } else { self.current_char };
+                self.advance();
+                let title = self.parse_string_until(&[closing, b'\n']);
+                self.consume(closing).ok().map(|_| title)
+            }
+            _ => Some(String::new()),
+        };
+        self.skip_spaces();
+        match (url, title) {
+            (Some(url), Some(title))
+                if (self.at_end() || self.current_char == b'\n') && url.span().start < url.span().end =>
+            {
+                let definition = self.spanned(
+                    Tree::LinkDef(label, Box::new(url), title, Span::default()),
+                    start,
+                );
+                if !self.at_end() {
+                    self.skip(b'\n');
                 }
-                exp
+                Some(definition)
             }
             _ => {
-                if is_chapter_mark {
-                    lit(">>")
-                } else {
-                    lit(">")
-                }
+                self.reset(mark);
+                None
+            }
+        }
+    }
+
+    /// the address range of the autolink at `offset`, if there is one: `<scheme:...>` and
+    /// `<mail@host>` in angle brackets or a bare `http://` or `https://` url. a bare url ends
+    /// at white space, at `<` or at one of `break_chars`, trailing punctuation is not part of
+    /// it.
+    fn autolink_at(&self, offset: usize, break_chars: &[u8]) -> Option<(usize, usize)> {
+        // link texts and image captions end at `]`, links must not be nested
+        if break_chars.contains(&b']') {
+            return None;
+        }
+        let rest = &self.input[offset..self.input_len];
+        if rest.first() == Some(&b'<') {
+            let len = rest[1..]
+                .iter()
+                .take_while(|&&c| c != b'>' && c != b'<' && !c.is_ascii_whitespace())
+                .count();
+            let address = &rest[1..1 + len];
+            return (rest.get(1 + len) == Some(&b'>') && (is_uri(address) || is_email(address)))
+                .then_some((offset + 1, offset + 1 + len));
+        }
+        let scheme_len = if rest.starts_with(b"http://") {
+            7
+        } else if rest.starts_with(b"https://") {
+            8
+        } else {
+            return None;
+        };
+        if offset > 0 && self.input[offset - 1].is_ascii_alphanumeric() {
+            return None;
+        }
+        let mut len = rest
+            .iter()
+            .take_while(|&&c| !c.is_ascii_whitespace() && c != b'<' && !break_chars.contains(&c))
+            .count();
+        loop {
+            let trailing = rest[len - 1];
+            let unbalanced_paren = trailing == b')'
+                && rest[..len].iter().filter(|&&c| c == b')').count()
+                    > rest[..len].iter().filter(|&&c| c == b'(').count();
+            if b"?!.,:;*_~'\"".contains(&trailing) || unbalanced_paren {
+                len -= 1;
+            } else {
+                break;
             }
         }
+        (len > scheme_len).then_some((offset, offset + len))
+    }
+
+    /// parses the autolink found by `autolink_at`. e-mail addresses link to `mailto:`
+    fn parse_autolink(&mut self, (address_start, address_end): (usize, usize)) -> Tree {
+        let start = self.position();
+        let angled = self.current_char == b'<';
+        if angled {
+            self.skip(b'<');
+        }
+        let address = self.position();
+        let text = str::from_utf8(&self.input[address_start..address_end]).unwrap().to_string();
+        self.advance_to(address_end);
+        let url = if is_uri(text.as_bytes()) {
+            text.clone()
+        } else {
+            format!("mailto:{text}")
+        };
+        let text = self.spanned(lit(&text), address);
+        let url = self.spanned(lit(&url), address);
+        if angled {
+            self.skip(b'>');
+        }
+        // like after hyper links, we swollow a space following the link
+        if self.current_char == b' ' {
+            self.skip(b' ')
+        }
+        self.spanned(hyperref(text, url), start)
+    }
+
+    /// parses a literal until one of `break_chars` or an autolink is encountered
+    fn parse_text(&mut self, break_chars: &[u8], literal_break_chars: &[u8]) -> Tree {
+        let start = self.position();
+        while !self.at_end()
+            && !literal_break_chars.contains(&self.current_char)
+            && (self.current_position == start.offset
+                || self.autolink_at(self.current_position, break_chars).is_none())
+        {
+            self.advance();
+        }
+        let text = str::from_utf8(&self.input[start.offset..self.current_position]).unwrap();
+        self.spanned(lit(text), start)
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.current_char == b' ' {
+            self.skip(b' ');
+        }
     }
 
-    fn parse_link(&mut self) -> Tree {
-        self.consume(b'[');
+    fn parse_link(&mut self) -> ParseResult<Tree> {
+        let start = self.position();
+        let opening = self.skip_as_lit(b'[', "[");
         let link_text = self.parse_format_until(b"]");
-        self.consume(b']');
+        let text_end = self.current_position;
+        let closing = self.position();
+        self.consume(b']')?;
+        let closing = self.spanned(lit("]"), closing);
+        let source = |parser: &Self, from: usize, to: usize| {
+            str::from_utf8(&parser.input[from..to]).unwrap().to_string()
+        };
         if self.current_char == b'(' {
-            self.consume(b'(');
+            self.skip(b'(');
             if self.current_char == b'#' {
                 // internal link
-                self.consume(b'#');
+                self.skip(b'#');
                 let target = self.parse_string_until(b")");
-                self.consume(b')');
-                Tree::DocRef(target, Box::new(link_text))
+                self.consume(b')')?;
+                Ok(Tree::DocRef(target, Box::new(link_text), Span::default()))
             } else {
                 // hyper link
                 let exp_url = self.parse_literal(b")");
-                self.consume(b')');
+                self.consume(b')')?;
                 // if there is a space after the hyperlink, we swollow it
                 // to avoid a line break in the PDf after the link
                 if self.current_char == b' ' {
-                    self.consume(b' ')
+                    self.skip(b' ')
                 }
-                hyperref(link_text, exp_url)
+                Ok(hyperref(link_text, exp_url))
+            }
+        } else if self.current_char == b'[' {
+            // full `[text][label]` or collapsed `[label][]` reference link
+            let suffix_start = self.current_position;
+            self.skip(b'[');
+            let label = self.parse_string_until(b"[]");
+            self.consume(b']')?;
+            let label = if label.is_empty() {
+                source(self, start.offset + 1, text_end)
+            } else {
+                label
+            };
+            // like above, a space after the link is swollowed
+            if self.current_char == b' ' {
+                self.skip(b' ')
             }
+            let suffix = source(self, suffix_start, self.current_position);
+            Ok(Tree::LinkRef(label, Box::new(link_text), suffix, Span::default()))
+        } else if text_end > start.offset + 1 {
+            // shortcut `[label]` reference link, which stays text, if `label` is not defined
+            let suffix_start = self.current_position;
+            if self.current_char == b' ' {
+                self.skip(b' ')
+            }
+            let suffix = source(self, suffix_start, self.current_position);
+            let label = source(self, start.offset + 1, text_end);
+            Ok(Tree::LinkRef(label, Box::new(link_text), suffix, Span::default()))
         } else {
-            lit("[").cat(link_text).cat(lit("]"))
+            Ok(opening.cat(link_text).cat(closing))
         }
     }
 
@@ -341,25 +1019,46 @@ impl Parser<'_> {
     }
 
     fn parse_literal(&mut self, break_chars: &[u8]) -> Tree {
-        lit(&self.parse_string_until(break_chars))
+        let start = self.position();
+        let literal = lit(&self.parse_string_until(break_chars));
+        self.spanned(literal, start)
     }
 
     fn parse_preformatted_literal(&mut self, break_chars: &[u8]) -> Tree {
-        prelit(&self.parse_string_until(break_chars))
+        let start = self.position();
+        let literal = prelit(&self.parse_string_until(break_chars));
+        self.spanned(literal, start)
     }
 
     /// a 'pass through' is a command written in the source markdown language, but to be passed on
-    /// or through to the target language, like groff
+    /// or through to the target language, like groff. `//include file.md` is the exception,
+    /// see [`Tree::Include`]
     fn parse_pass_through(&mut self) -> Tree {
-        self.consume(b'/');
+        let start = self.position();
+        if self.at_scheme_slashes() {
+            self.skip(b'/');
+            self.skip(b'/');
+            return lit("//");
+        }
+        self.skip(b'/');
         if self.peek(0, b'/') {
-            self.consume(b'/');
+            self.skip(b'/');
+            if self.input[self.current_position..].starts_with(b"include ") {
+                self.advance_to(self.current_position + "include ".len());
+                let file = self.parse_string_until(b"\n").trim().to_string();
+                return self.spanned(Tree::Include(file, Span::default()), start);
+            }
             self.parse_literal(b"\n")
         } else {
             lit("/")
         }
     }
 
+    /// are we at the `//` following the scheme of a url, like in `https://`?
+    fn at_scheme_slashes(&self) -> bool {
+        self.current_char == b'/' && self.peek(1, b'/') && self.peek_back(1, b':')
+    }
+
     fn is_all_space_until(&self, index: u8) -> bool {
         for i in 0..index as usize {
             if !self.peek(i, b' ') {
@@ -369,23 +1068,24 @@ impl Parser<'_> {
         true
     }
 
-    fn consume_all_space_until(&mut self, index: u8) {
+    fn skip_all_space_until(&mut self, index: u8) {
         for _ in 0..index {
-            self.consume(b' ');
+            self.skip(b' ');
         }
     }
 
-    fn consume_all_space(&mut self) {
+    fn skip_all_space(&mut self) {
         while !self.at_end() && self.current_char == b' ' {
-            self.consume(b' ')
+            self.skip(b' ')
         }
     }
 
     /// parse a key value pair in a meta data header. key and value are delimited by `:`.
-    fn parse_meta_data_item(&mut self) -> Tree {
+    fn parse_meta_data_item(&mut self) -> ParseResult<Tree> {
         //println!("parsing metadata header");
-        let key = self.parse_string_until(b":");
-        self.consume(b':');
+        let start = self.position();
+        let key = self.parse_string_until(b":\n");
+        self.consume(b':')?;
         while self.current_char == b' ' {
             self.advance();
         }
@@ -393,140 +1093,207 @@ impl Parser<'_> {
         if "doctype" == key {
             println!("setting docype {value}");
             self.doc_type = value;
-            empty()
+            Ok(self.spanned(empty(), start))
         } else {
-            meta_data_item(key.to_string(), value.to_string())
+            Ok(self.spanned(meta_data_item(key.to_string(), value.to_string()), start))
         }
     }
 
     /// parse a list of key value items in a meta data block
-    fn parse_meta_data_items(&mut self) -> Tree {
-        let mut items = empty();
+    fn parse_meta_data_items(&mut self) -> ParseResult<Tree> {
+        let start = self.position();
+        let mut items = vec![];
         while self.current_char != b'-' && self.current_char != b'\n' {
-            items = items.cat(self.parse_meta_data_item());
-            self.consume(b'\n')
+            items.push(self.parse_meta_data_item()?);
+            self.consume(b'\n')?
         }
-        items
+        Ok(self.spanned(Tree::Block(items, Span::default()), start))
     }
 
-    /// try to parse a meta data block. such blocks beginn with three `---` on a line, followed by
+    /// parse a meta data block. such blocks beginn with three `---` on a line, followed by
     /// a key value list of undefined length and end with a `---` line.
+    fn parse_meta_data_block(&mut self) -> ParseResult<Tree> {
+        self.skip(b'-');
+        self.skip(b'-');
+        self.skip(b'-');
+        while self.current_char == b' ' || self.current_char == b'\t' {
+            self.advance()
+        }
+        self.consume(b'\n')?;
+        let items = self.parse_meta_data_items()?;
+        if self.current_char == b'-' {
+            self.consume(b'-')?;
+            self.consume(b'-')?;
+            self.consume(b'-')?;
+        }
+        if !self.at_end() {
+            self.consume(b'\n')?;
+        }
+        if self.current_char == b'\n' {
+            self.skip(b'\n');
+        }
+        Ok(Tree::MetaDataBlock(Box::new(items), Span::default()))
+    }
+
+    /// try to parse a meta data block, a list, a dash or a literal `-`.
     fn parse_mdb_or_list_or_lit(&mut self) -> Tree {
         let at_line_start = self.current_position == 0 || self.peek_back(1, b'\n');
         if at_line_start && self.peek(1, b'-') && self.peek(2, b'-') {
-            self.consume(b'-');
-            self.consume(b'-');
-            self.consume(b'-');
-            while self.current_char == b' ' || self.current_char == b'\t' {
-                self.advance()
-            }
-            self.consume(b'\n');
-            let items = self.parse_meta_data_items();
-            if self.current_char == b'-' {
-                self.consume(b'-');
-                self.consume(b'-');
-                self.consume(b'-');
-            }
-            self.consume(b'\n');
-            if self.current_char == b'\n' {
-                self.consume(b'\n');
-            }
-            Tree::MetaDataBlock(Box::new(items))
+            self.attempt("metadata block", "---", Self::parse_meta_data_block)
         } else if at_line_start && self.peek(1, b' ') {
-            self.parse_list_or_bold_or_lit(0, b'-')
+            self.parse_list_or_bold_or_lit(b'-')
         } else if self.peek(1, b'-') && self.peek(2, b'-') {
-            self.consume(b'-');
-            self.consume(b'-');
-            self.consume(b'-');
-            Tree::EmDash
+            self.skip(b'-');
+            self.skip(b'-');
+            self.skip(b'-');
+            Tree::EmDash(Span::default())
         } else if self.peek(1, b'-') {
-            self.consume(b'-');
-            self.consume(b'-');
-            Tree::EnDash
+            self.skip(b'-');
+            self.skip(b'-');
+            Tree::EnDash(Span::default())
         } else {
             self.advance();
             lit("-")
         }
     }
 
-    fn parse_list_item(&mut self, level: u8, list_char: u8) -> Tree {
-        let mut item = empty();
-        self.consume_all_space_until(level * LIST_INDENT);
-        self.consume(list_char);
-        self.consume(b' ');
+    fn parse_list_item(&mut self, level: u8, marker_len: usize) -> Tree {
+        let start = self.position();
+        let mut lines = vec![];
+        self.skip_all_space_until(level * LIST_INDENT);
+        self.advance_to(self.current_position + marker_len);
+        self.skip(b' ');
         loop {
-            item = item.cat(self.parse_complete_until(b"\n"));
+            match self.parse_complete_until(b"\n") {
+                Tree::Block(children, _) => lines.extend(children),
+                line => lines.push(line),
+            }
+            let newline = self.position();
             if !self.at_end() {
-                self.consume(b'\n');
+                self.skip(b'\n');
             }
             if self.is_all_space_until((level * LIST_INDENT) + LIST_INDENT)
-                && !self.peek(
-                    (level * LIST_INDENT) as usize + LIST_INDENT as usize,
-                    list_char,
-                )
+                && self.list_marker(level + 1).is_none()
             {
-                self.consume_all_space_until((level * LIST_INDENT) + LIST_INDENT);
+                let newline = self.spanned(lit("\n"), newline);
+                self.skip_all_space_until((level * LIST_INDENT) + LIST_INDENT);
                 // reappend the newline we swallowed above
-                item = item.cat(lit("\n"));
+                lines.push(newline);
                 continue;
             }
             break;
         }
-        list_item(item, level)
+        let item = self.spanned_if_unknown(sequence(lines, Tree::Block), start);
+        self.spanned(list_item(item, level), start)
     }
 
-    fn parse_list_or_bold_or_lit(&mut self, level: u8, list_char: u8) -> Tree {
+    fn parse_list_or_bold_or_lit(&mut self, list_char: u8) -> Tree {
         // we try to decide if the author wanted to start a bold segment or write a list:
-        // ATM we simply check, if c + (l * li) + 1 is a white space
+        // ATM we simply check, if c + 1 is a white space
         let at_line_start = self.current_position == 0 || self.peek_back(1, b'\n');
-        if at_line_start && self.peek((level * LIST_INDENT) as usize + 1, b' ') {
+        match self.list_marker(0) {
             // if * is followed by white space and we are at the start of a line
-            self.parse_list(level, list_char)
-        } else if list_char == b'*' {
-            self.parse_bold()
-        } else {
-            lit(&format!("{}", list_char as char))
+            Some((kind, _)) if at_line_start => self.parse_list(0, kind),
+            _ if list_char == b'*' => self.parse_bold(),
+            _ => lit(&format!("{}", list_char as char)),
+        }
+    }
+
+    /// the kind and length of the list item marker of the given level at the
+    /// current position, if there is one: `*`, `-`, `1.` or `1)`, followed by a space
+    fn list_marker(&self, level: u8) -> Option<(ListKind, usize)> {
+        if !self.is_all_space_until(level * LIST_INDENT) {
+            return None;
+        }
+        let indent = (level * LIST_INDENT) as usize;
+        let offset = self.current_position + indent;
+        match self.input[..self.input_len].get(offset) {
+            Some(&c @ (b'*' | b'-')) if self.peek(indent + 1, b' ') => {
+                Some((ListKind::Bullet(c as char), 1))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let digits = self.input[offset..self.input_len]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                // more digits would overflow the start number
+                if digits > 9 || !self.peek(indent + digits + 1, b' ') {
+                    return None;
+                }
+                let delimiter = self.input[offset + digits];
+                if delimiter != b'.' && delimiter != b')' {
+                    return None;
+                }
+                let start = str::from_utf8(&self.input[offset..offset + digits])
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                Some((ListKind::Ordered(start, delimiter as char), digits + 1))
+            }
+            _ => None,
+        }
+    }
+
+    /// the kind of the ordered list starting at the current position, if any.
+    /// like in commonmark, only lists starting with 1 may interrupt a paragraph,
+    /// so that a wrapped line starting with a date like `2. Oktober` stays text.
+    fn ordered_list_start(&self) -> Option<ListKind> {
+        let at_line_start = self.current_position == 0 || self.peek_back(1, b'\n');
+        if !at_line_start || !self.current_char.is_ascii_digit() {
+            return None;
+        }
+        let after_blank_line = self.current_position < 2 || self.peek_back(2, b'\n');
+        match self.list_marker(0) {
+            Some((kind @ ListKind::Ordered(start, _), _)) if after_blank_line || start == 1 => {
+                Some(kind)
+            }
+            _ => None,
         }
     }
 
-    fn parse_list(&mut self, level: u8, list_char: u8) -> Tree {
-        let mut iterator = empty();
+    fn parse_list(&mut self, level: u8, kind: ListKind) -> Tree {
+        let start = self.position();
+        let mut items = vec![];
         loop {
-            if self.peek((level * LIST_INDENT) as usize, list_char)
-                && self.peek((level * LIST_INDENT) as usize + 1, b' ')
-            {
-                iterator = iterator.cat(self.parse_list_item(level, list_char));
-                continue;
-            } else if self.peek(((level + 1) * LIST_INDENT) as usize, list_char)
-                && self.peek(((level + 1) * LIST_INDENT) as usize + 1, b' ')
-            {
-                // nested list
-                iterator = iterator.cat(self.parse_list_or_bold_or_lit(level + 1, list_char));
+            match self.list_marker(level) {
+                Some((item_kind, marker_len)) if kind.continued_by(item_kind) => {
+                    items.push(self.parse_list_item(level, marker_len));
+                    continue;
+                }
+                // an item of another kind ends this list and starts a new one
+                Some(_) => break,
+                None => {}
+            }
+            if let Some((nested_kind, _)) = self.list_marker(level + 1) {
+                // nested list, which may be of another kind
+                items.push(self.parse_list(level + 1, nested_kind));
             } else {
                 break;
             }
         }
-        list(iterator, level)
+        let items = self.spanned(Tree::Block(items, Span::default()), start);
+        self.spanned(list(items, level, kind), start)
     }
     fn parse_bold(&mut self) -> Tree {
         if self.peek(1, b'*') {
-            self.consume(b'*')
+            self.skip(b'*')
         }
         let res = bold(self.parse_symmetric_quoted());
         if self.current_char == b'*' {
-            self.consume(b'*')
+            self.skip(b'*')
         }
         res
     }
-    fn parse_code_block(&mut self) -> Tree {
-        let mut block_type = empty();
-        self.consume(b'`');
-        self.consume(b'`');
-        self.consume_all_space(); // slurp away aditional white space
+    fn parse_code_block(&mut self) -> ParseResult<Tree> {
+        let mut block_type = self.spanned(empty(), self.position());
+        self.skip(b'`');
+        self.skip(b'`');
+        self.skip(b'`');
+        self.skip_all_space(); // slurp away aditional white space
         if self.current_char != b'\n' {
             block_type = self.parse_literal(b"\n");
         }
-        self.consume(b'\n');
+        self.consume(b'\n')?;
         // groff requires '.'s at the beginning of the line to be escaped, even in code blocks;
         // maybe, we can change the character in code blocks, temporarily
         // TODO escape '.' at line beginnings
@@ -534,184 +1301,379 @@ impl Parser<'_> {
         // when parse_preformatted_literal returns, it encountered a ` char we are in a code block
         // and such a block is only ended by three backticks on a line
         while !(self.at_end() || self.peek(1, b'`') && self.peek(2, b'`')) {
-            result = result.cat(lit("\\[ga]"));
-            self.consume(b'`');
+            result = result.cat(self.skip_as_lit(b'`', "\\[ga]"));
             result = result.cat(self.parse_preformatted_literal(b"`"))
         }
         if !self.at_end() {
-            self.consume(b'`');
+            self.skip(b'`');
             if !self.at_end() && self.current_char != 4 {
-                self.consume(b'`');
+                self.skip(b'`');
                 if !self.at_end() && self.current_char != 4 {
-                    self.consume(b'`');
+                    self.skip(b'`');
                 }
             }
         }
-        Tree::CodeBlock(Box::new(block_type), Box::new(result))
+        Ok(Tree::CodeBlock(Box::new(block_type), Box::new(result), Span::default()))
     }
 
-    fn parse_code(&mut self) -> Tree {
-        self.consume(b'`'); // opening quote
-                            // here, we need to peek 1 and 2 characters ahead to see if
-                            // they are also back ticks, and if so parse a code block
-                            // instead of an inline code snippet.
-        if self.peek(0, b'`') && self.peek(1, b'`') {
-            return self.parse_code_block();
-        }
+    fn parse_inline_code(&mut self) -> ParseResult<Tree> {
+        self.skip(b'`'); // opening quote
 
         // this is an ugly groff necessity: if our code snippet
         // begins with a dot, we need to escape it
+        let start = self.position();
         let exp = if self.current_char == b'.' {
-            self.consume(b'.');
-            escape_lit(".")
+            self.skip(b'.');
+            self.spanned(escape_lit("."), start)
         } else {
-            Tree::Empty()
+            empty()
         };
         let code_exp = self.parse_literal(b"`");
         let exp = match exp {
-            Tree::Empty() => code_exp,
+            Tree::Empty(_) => code_exp,
             _ => exp.cat(code_exp),
         };
-        self.consume(b'`'); // closing quote
-        Tree::InlineCode(Box::new(exp))
+        self.consume(b'`')?; // closing quote
+        Ok(Tree::InlineCode(Box::new(exp), Span::default()))
+    }
+
+    fn parse_code(&mut self) -> Tree {
+        // here, we need to peek 1 and 2 characters ahead to see if
+        // they are also back ticks, and if so parse a code block
+        // instead of an inline code snippet.
+        if self.peek(1, b'`') && self.peek(2, b'`') {
+            self.attempt("code block", "```", Self::parse_code_block)
+        } else {
+            self.attempt("inline code", "`", Self::parse_inline_code)
+        }
     }
 
-    fn parse_image_size(&mut self) -> Tree {
+    fn parse_image_size(&mut self) -> ParseResult<Tree> {
+        let start = self.position();
         let x = self.parse_complete_until(b"x");
-        self.consume(b'x');
+        self.consume(b'x')?;
         let y = self.parse_complete_until(b"]");
-        image_size(x, y)
+        Ok(self.spanned(image_size(x, y), start))
     }
 
     fn parse_image(&mut self) -> Tree {
         if self.peek(1, b'[') {
-            self.consume(b'!');
-            self.consume(b'[');
-            let caption = self.parse_complete_until(b"|]");
-            let mut size_spec = image_size(lit("100"), lit("100"));
-            if self.current_char == b'|' {
-                self.consume(b'|');
-                size_spec = self.parse_image_size();
-            }
-            self.consume(b']');
-            self.consume(b'(');
-            let path = self.parse_literal(b")");
-            self.consume(b')');
-            image(caption, path, size_spec)
+            self.attempt("image", "![", Self::parse_image_spec)
         } else {
-            self.consume(b'!');
+            self.skip(b'!');
             lit("!")
         }
     }
 
-    /// Parses only formatting subset of markup as opposed to global_parse_until
-    fn parse_format_until(&mut self, break_chars: &[u8]) -> Tree {
-        let mut expression = Tree::Empty(); // we start with
-                                            // "nothing", as rust has
-                                            // no null values
-        while !self.at_end() && !break_chars.contains(&self.current_char) {
-            let expr = match self.current_char {
-                b'*' => self.try_bold_or_lit_until(b"]"),
-                b'_' => Tree::Italic(Box::new(self.parse_symmetric_quoted())),
-                b'{' => Tree::SmallCaps(Box::new(self.parse_quoted(b'}'))),
-                b'`' => self.parse_code(),
-                b'"' => Tree::Quote(Box::new(self.parse_symmetric_quoted())),
-                b'^' => self.parse_footnote(),
-                b'%' => self.parse_drop_cap(),
-                b'&' => {
-                    self.consume(self.current_char);
-                    escape_lit("&")
-                }
-                b'.' => {
-                    self.consume(self.current_char);
-                    escape_lit(".")
-                }
-                b'/' => self.parse_pass_through(),
-                b'\\' => self.parse_color_spec(),
-                _ => self.parse_literal(
-                    format!("-_*#\"^`&[{{{}>\n", str::from_utf8(break_chars).unwrap()).as_bytes(),
-                ),
-            };
-            expression = match expression {
-                Tree::Empty() => expr,
-                _ => expression.cat(expr),
-            };
+    fn parse_image_spec(&mut self) -> ParseResult<Tree> {
+        self.skip(b'!');
+        self.skip(b'[');
+        let caption = self.parse_complete_until(b"|]");
+        // the default size spec has no source, so it is located
+        // where it would have been written
+        let here = self.position();
+        let mut size_spec = self.spanned(
+            image_size(self.spanned(lit("100"), here), self.spanned(lit("100"), here)),
+            here,
+        );
+        if self.current_char == b'|' {
+            self.skip(b'|');
+            size_spec = self.parse_image_size()?;
         }
-        expression
+        self.consume(b']')?;
+        self.consume(b'(')?;
+        let path = self.parse_literal(b")");
+        self.consume(b')')?;
+        Ok(image(caption, path, size_spec))
     }
 
-    /// parses complete mark-up set, as opposed to only formatting, like above fmt_parse_until.
-    /// stops parsing when one of the characters in `break_chars` is encountered.
-    fn parse_complete_until(&mut self, break_chars: &[u8]) -> Tree {
-        // we start with "nothing", as rust has no null values
-        let mut expression = Tree::Empty();
-        // main parsing loop. note that this function might be called recursivly.
-        while !self.at_end() && !break_chars.contains(&self.current_char) {
-            let expr = match self.current_char {
-                b'-' => self.parse_mdb_or_list_or_lit(),
-                b'#' => self.parse_heading(),
-                b'*' => self.parse_list_or_bold_or_lit(0, b'*'),
-                b'_' => Tree::Italic(Box::new(self.parse_symmetric_quoted())),
-                b'{' => Tree::SmallCaps(Box::new(self.parse_quoted(b'}'))),
-                b'`' => self.parse_code(),
-                b'"' => Tree::Quote(Box::new(self.parse_symmetric_quoted())),
-                b'^' => self.parse_footnote(),
-                b'%' => self.parse_drop_cap(),
-                b'&' => {
-                    self.consume(self.current_char);
-                    escape_lit("&")
-                }
-                b'.' => {
-                    self.consume(self.current_char);
-                    escape_lit(".")
-                }
-                b'/' => self.parse_pass_through(),
-                b'\\' => self.parse_color_spec(),
-                b'[' => self.parse_link(),
-                b'\n' => {
-                    // if the blank line is followed by a heading do not insert a paragraph
-                    if self.peek(1, b'\n') {
-                        if self.peek(2, b'#') {
-                            // a heading follows
-                            self.consume(b'\n');
-                            self.consume(b'\n');
-                            Tree::LineBreak()
-                        } else {
-                            // no heading follows
-                            self.consume(b'\n');
-                            self.consume(b'\n');
-                            Tree::LineBreak().cat(Tree::Paragraph())
-                        }
-                    } else {
-                        self.consume(b'\n');
-                        Tree::LineBreak()
+    /// the offset of the end of the line containing `offset`
+    fn line_end(&self, offset: usize) -> usize {
+        self.input[offset..self.input_len]
+            .iter()
+            .position(|&c| c == b'\n')
+            .map_or(self.input_len, |i| offset + i)
+    }
+
+    /// if a pipe table starts at the current position, returns its column alignments. a
+    /// table starts with a header line containing a `|`, followed by a delimiter row with
+    /// the same number of cells.
+    fn table_start(&self) -> Option<Vec<Alignment>> {
+        let at_line_start = self.current_position == 0 || self.peek_back(1, b'\n');
+        if !at_line_start {
+            return None;
+        }
+        let header_end = self.line_end(self.current_position);
+        let header = &self.input[self.current_position..header_end];
+        if !header.contains(&b'|') || header_end >= self.input_len {
+            return None;
+        }
+        let delimiter_row = &self.input[header_end + 1..self.line_end(header_end + 1)];
+        table_alignments(delimiter_row).filter(|a| a.len() == table_cells(header).len())
+    }
+
+    /// parses the inline formatting between `start` and `end` with a parser, that
+    /// treats `end` as the end of the input
+    fn parse_inline_range(&mut self, start: usize, end: usize) -> Tree {
+        let mut inline_parser = Parser {
+            input: self.input,
+            input_len: end,
+            current_position: start,
+            current_line: self.current_line,
+            line_start: self.line_start,
+            current_char: if start < end { self.input[start] } else { 4 },
+            doc_type: String::new(),
+            diagnostics: vec![],
+            failed: HashMap::new(),
+            first_line: self.first_line,
+            line_prefixes: self.line_prefixes.clone(),
+        };
+        let tree = inline_parser.parse_format_until(b"");
+        self.diagnostics.append(&mut inline_parser.diagnostics);
+        tree
+    }
+
+    /// advances to `offset`, which has to be on the current line
+    fn advance_to(&mut self, offset: usize) {
+        while self.current_position < offset {
+            self.advance();
+        }
+    }
+
+    /// parses a table row with `columns` cells, surplus cells are dropped,
+    /// missing ones are added empty
+    fn parse_table_row(&mut self, columns: usize) -> Tree {
+        let start = self.position();
+        let row_end = self.line_end(start.offset);
+        let mut cells = vec![];
+        for (cell_start, cell_end) in table_cells(&self.input[start.offset..row_end]) {
+            if cells.len() == columns {
+                break;
+            }
+            cells.push(self.parse_inline_range(start.offset + cell_start, start.offset + cell_end));
+        }
+        self.advance_to(row_end);
+        while cells.len() < columns {
+            cells.push(self.spanned(empty(), self.position()));
+        }
+        let row = self.spanned(Tree::TableRow(cells, Span::default()), start);
+        if !self.at_end() {
+            self.skip(b'\n');
+        }
+        row
+    }
+
+    /// true, if a table caption, `Table: caption` or `: caption`, starts at `offset`
+    fn at_table_caption(&self, offset: usize) -> bool {
+        let line = &self.input[offset..self.input_len];
+        line.starts_with(b"Table:") || line.starts_with(b": ")
+    }
+
+    /// parses a table caption directly below a table, or separated by an empty line
+    fn parse_table_caption(&mut self) -> Tree {
+        if self.current_char == b'\n' && self.at_table_caption(self.current_position + 1) {
+            self.skip(b'\n');
+        }
+        if self.at_end() || !self.at_table_caption(self.current_position) {
+            return empty();
+        }
+        let prefix = if self.current_char == b':' { 1 } else { "Table:".len() };
+        self.advance_to(self.current_position + prefix);
+        self.skip_all_space();
+        let caption = self.parse_inline_range(self.current_position, self.line_end(self.current_position));
+        self.advance_to(self.line_end(self.current_position));
+        if !self.at_end() {
+            self.skip(b'\n');
+        }
+        caption
+    }
+
+    /// parses a pipe table, like
+    ///
+    /// ```text
+    /// | Name | Value |
+    /// |:-----|------:|
+    /// | a    |     1 |
+    ///
+    /// Table: a caption
+    /// ```
+    fn parse_table(&mut self, alignments: Vec<Alignment>) -> Tree {
+        let start = self.position();
+        let header = self.parse_table_row(alignments.len());
+        self.advance_to(self.line_end(self.current_position));
+        if !self.at_end() {
+            self.skip(b'\n');
+        }
+        let mut rows = vec![];
+        while !self.at_end()
+            && self.input[self.current_position..self.line_end(self.current_position)].contains(&b'|')
+        {
+            rows.push(self.parse_table_row(alignments.len()));
+        }
+        let caption = self.parse_table_caption();
+        self.spanned(
+            Tree::Table(alignments, Box::new(header), rows, Box::new(caption), Span::default()),
+            start,
+        )
+    }
+
+    /// Parses only formatting subset of markup as opposed to global_parse_until
+    fn parse_format_until(&mut self, break_chars: &[u8]) -> Tree {
+        let mut children = vec![];
+        let start = self.position();
+        while !self.at_end() && !break_chars.contains(&self.current_char) {
+            let expr_start = self.position();
+            let expr = match self.current_char {
+                b'*' => self.try_bold_or_lit_until(b"]"),
+                b'_' => Tree::Italic(Box::new(self.parse_symmetric_quoted()), Span::default()),
+                b'{' => self.parse_small_caps(),
+                b'`' => self.parse_code(),
+                b'"' => Tree::Quote(Box::new(self.parse_symmetric_quoted()), Span::default()),
+                b'^' => self.parse_footnote(),
+                b'%' => self.parse_drop_cap(),
+                b'&' => {
+                    self.skip(b'&');
+                    escape_lit("&")
+                }
+                b'.' => {
+                    self.skip(b'.');
+                    escape_lit(".")
+                }
+                b'/' => self.parse_pass_through(),
+                b'\\' => self.parse_color_spec_or_escape(),
+                b'[' => match self.footnote_label() {
+                    Some(label) => self.parse_footnote_reference(label),
+                    None => {
+                        self.skip(b'[');
+                        lit("[")
                     }
+                },
+                // these only have a meaning in the complete mark-up set, so here they are
+                // literals. without this arm, we would not make any progress on them.
+                b'-' | b'#' | b'>' | b'\n' => {
+                    let char = self.current_char;
+                    self.skip(char);
+                    lit(&(char as char).to_string())
                 }
-                b'>' => self.parse_right_sidenote(),
-                b'!' => self.parse_image(),
-                _ => self.parse_literal(
-                    format!("-_*#\"^`&[{{{}>\n", str::from_utf8(break_chars).unwrap()).as_bytes(),
-                ),
+                _ => match self.autolink_at(self.current_position, break_chars) {
+                    Some(address) => self.parse_autolink(address),
+                    None => self.parse_text(
+                        break_chars,
+                        format!("-_*#\"^`&[\\{{{}>\n", str::from_utf8(break_chars).unwrap()).as_bytes(),
+                    ),
+                },
             };
-            expression = match expression {
-                Tree::Empty() => expr,
-                _ => expression.cat(expr),
+            push_expression(&mut children, self.spanned_if_unknown(expr, expr_start));
+        }
+        self.spanned_if_unknown(sequence(children, Tree::Inline), start)
+    }
+
+    /// attaches a span from `start` up to the current position to `tree`,
+    /// if the parsing function that produced it did not do so already
+    fn spanned_if_unknown(&self, tree: Tree, start: Position) -> Tree {
+        if tree.span().is_unknown() {
+            self.spanned(tree, start)
+        } else {
+            tree
+        }
+    }
+
+    /// parses complete mark-up set, as opposed to only formatting, like above fmt_parse_until.
+    /// stops parsing when one of the characters in `break_chars` is encountered.
+    fn parse_complete_until(&mut self, break_chars: &[u8]) -> Tree {
+        let mut children = vec![];
+        let start = self.position();
+        // main parsing loop. note that this function might be called recursivly.
+        while !self.at_end() && !break_chars.contains(&self.current_char) {
+            let expr_start = self.position();
+            let expr = if let Some(alignments) = self.table_start() {
+                self.parse_table(alignments)
+            } else if let Some(kind) = self.ordered_list_start() {
+                self.parse_list(0, kind)
+            } else {
+                match self.current_char {
+                    b'-' => self.parse_mdb_or_list_or_lit(),
+                    b'#' => self.parse_heading(),
+                    b'*' => self.parse_list_or_bold_or_lit(b'*'),
+                    b'_' => Tree::Italic(Box::new(self.parse_symmetric_quoted()), Span::default()),
+                    b'{' => self.parse_small_caps(),
+                    b'`' => self.parse_code(),
+                    b'"' => Tree::Quote(Box::new(self.parse_symmetric_quoted()), Span::default()),
+                    b'^' => self.parse_footnote(),
+                    b'%' => self.parse_drop_cap(),
+                    b'&' => {
+                        self.skip(b'&');
+                        escape_lit("&")
+                    }
+                    b'.' => {
+                        self.skip(b'.');
+                        escape_lit(".")
+                    }
+                    b'/' => self.parse_pass_through(),
+                    b'\\' => self.parse_color_spec_or_escape(),
+                    b'[' => match self.footnote_label() {
+                        Some(label) if self.at_footnote_definition(&label) => {
+                            self.parse_footnote_definition(label)
+                        }
+                        Some(label) => self.parse_footnote_reference(label),
+                        None => match self.parse_link_definition() {
+                            Some(definition) => definition,
+                            None => self.attempt("link", "[", Self::parse_link),
+                        },
+                    },
+                    b'\n' => {
+                        // if the blank line is followed by a heading do not insert a paragraph
+                        if self.peek(1, b'\n') {
+                            if self.peek(2, b'#') {
+                                // a heading follows
+                                self.skip(b'\n');
+                                self.skip(b'\n');
+                                Tree::LineBreak(Span::default())
+                            } else {
+                                // no heading follows
+                                let line_break = self.position();
+                                self.skip(b'\n');
+                                let line_break = self.spanned(Tree::LineBreak(Span::default()), line_break);
+                                let paragraph = self.position();
+                                self.skip(b'\n');
+                                let paragraph = self.spanned(Tree::Paragraph(Span::default()), paragraph);
+                                block(vec![line_break, paragraph])
+                            }
+                        } else {
+                            self.skip(b'\n');
+                            Tree::LineBreak(Span::default())
+                        }
+                    }
+                    b'>' if self.at_block_quote() => self.parse_block_quote(),
+                    b'>' => self.parse_right_sidenote(),
+                    b'!' => self.parse_image(),
+                    _ => match self.autolink_at(self.current_position, break_chars) {
+                        Some(address) => self.parse_autolink(address),
+                        None => self.parse_text(
+                            break_chars,
+                            format!("-_*#\"^`&[\\{{{}>\n", str::from_utf8(break_chars).unwrap())
+                                .as_bytes(),
+                        ),
+                    },
+
+                }
             };
+            push_expression(&mut children, self.spanned_if_unknown(expr, expr_start));
         }
-        expression
+        self.spanned_if_unknown(sequence(children, Tree::Block), start)
     }
 }
 #[cfg(test)]
 mod tests {
     use super::Parser;
     fn parse_to_ast(s: &str) -> String {
-        let p = Parser::parse(s);
+        let (p, _) = Parser::parse(s);
         format!("{p:?}")
     }
     #[test]
     fn construction() {
         let parser = Parser::new("\"quoted\"");
-        assert_eq!(format!("{parser:?}"), "Parser { input: [34, 113, 117, 111, 116, 101, 100, 34], input_len: 8, current_position: 0, current_line: 1, current_char: 34, doc_type: \"\" }");
+        assert_eq!(format!("{parser:?}"), "Parser { input: [34, 113, 117, 111, 116, 101, 100, 34], input_len: 8, current_position: 0, current_line: 1, line_start: 0, current_char: 34, doc_type: \"\", diagnostics: [], failed: {}, first_line: 1, line_prefixes: [] }");
     }
     #[test]
     fn expression() {
@@ -732,14 +1694,14 @@ mod tests {
     fn em_dash_inline() {
         assert_eq!(
             parse_to_ast("word---word"),
-            "Document(DEFAULT, Cat(Cat(Literal(\"word\"), EmDash), Literal(\"word\")))"
+            "Document(DEFAULT, Block([Literal(\"word\"), EmDash, Literal(\"word\")]))"
         );
     }
     #[test]
     fn en_dash_inline() {
         assert_eq!(
             parse_to_ast("word--word"),
-            "Document(DEFAULT, Cat(Cat(Literal(\"word\"), EnDash), Literal(\"word\")))"
+            "Document(DEFAULT, Block([Literal(\"word\"), EnDash, Literal(\"word\")]))"
         );
     }
     #[test]
@@ -747,7 +1709,7 @@ mod tests {
         // --- at line start is a metadata block delimiter, not an em dash
         assert_eq!(
             parse_to_ast("some text---more text"),
-            "Document(DEFAULT, Cat(Cat(Literal(\"some text\"), EmDash), Literal(\"more text\")))"
+            "Document(DEFAULT, Block([Literal(\"some text\"), EmDash, Literal(\"more text\")]))"
         );
     }
     #[test]
@@ -756,7 +1718,7 @@ mod tests {
         // as best-effort based on the order markers are encountered
         assert_eq!(
             parse_to_ast("*this is _wrong* nesting_"),
-            "Document(DEFAULT, Bold(Cat(Literal(\"this is \"), Italic(Cat(Literal(\"wrong\"), Bold(Cat(Literal(\" nesting\"), Italic(Empty))))))))"
+            "Document(DEFAULT, Bold(Block([Literal(\"this is \"), Italic(Block([Literal(\"wrong\"), Bold(Block([Literal(\" nesting\"), Italic(Empty)]))]))])))"
         )
     }
 
@@ -766,7 +1728,7 @@ mod tests {
     fn heading_level_1() {
         assert_eq!(
             parse_to_ast("# My Title\n"),
-            "Document(DEFAULT, Cat(Cat(Heading(Literal(\"My Title\"), 0, \"\"), VSpace), LineBreak))"
+            "Document(DEFAULT, Block([Heading(Literal(\"My Title\"), 0, \"\"), VSpace, LineBreak]))"
         );
     }
 
@@ -774,7 +1736,7 @@ mod tests {
     fn heading_level_2() {
         assert_eq!(
             parse_to_ast("## Sub\n"),
-            "Document(DEFAULT, Cat(Cat(Heading(Literal(\"Sub\"), 1, \"\"), VSpace), LineBreak))"
+            "Document(DEFAULT, Block([Heading(Literal(\"Sub\"), 1, \"\"), VSpace, LineBreak]))"
         );
     }
 
@@ -782,7 +1744,7 @@ mod tests {
     fn heading_level_3() {
         assert_eq!(
             parse_to_ast("### Deep\n"),
-            "Document(DEFAULT, Cat(Cat(Heading(Literal(\"Deep\"), 2, \"\"), VSpace), LineBreak))"
+            "Document(DEFAULT, Block([Heading(Literal(\"Deep\"), 2, \"\"), VSpace, LineBreak]))"
         );
     }
 
@@ -790,7 +1752,7 @@ mod tests {
     fn heading_with_named_anchor() {
         assert_eq!(
             parse_to_ast("# Title/anchor/\n"),
-            "Document(DEFAULT, Cat(Cat(Heading(Literal(\"Title\"), 0, \"anchor\"), VSpace), LineBreak))"
+            "Document(DEFAULT, Block([Heading(Literal(\"Title\"), 0, \"anchor\"), VSpace, LineBreak]))"
         );
     }
 
@@ -800,7 +1762,7 @@ mod tests {
     fn list_single_item_star() {
         assert_eq!(
             parse_to_ast("* item\n"),
-            "Document(DEFAULT, List(Cat(Empty, ListItem(Cat(Empty, Literal(\"item\")), 0)), 0))"
+            "Document(DEFAULT, List(Block([ListItem(Literal(\"item\"), 0)]), 0, Bullet('*')))"
         );
     }
 
@@ -808,7 +1770,7 @@ mod tests {
     fn list_two_items_star() {
         assert_eq!(
             parse_to_ast("* a\n* b\n"),
-            "Document(DEFAULT, List(Cat(Cat(Empty, ListItem(Cat(Empty, Literal(\"a\")), 0)), ListItem(Cat(Empty, Literal(\"b\")), 0)), 0))"
+            "Document(DEFAULT, List(Block([ListItem(Literal(\"a\"), 0), ListItem(Literal(\"b\"), 0)]), 0, Bullet('*')))"
         );
     }
 
@@ -816,7 +1778,7 @@ mod tests {
     fn list_single_item_dash() {
         assert_eq!(
             parse_to_ast("- item\n"),
-            "Document(DEFAULT, List(Cat(Empty, ListItem(Cat(Empty, Literal(\"item\")), 0)), 0))"
+            "Document(DEFAULT, List(Block([ListItem(Literal(\"item\"), 0)]), 0, Bullet('-')))"
         );
     }
 
@@ -824,7 +1786,7 @@ mod tests {
     fn list_nested() {
         assert_eq!(
             parse_to_ast("* outer\n  * inner\n"),
-            "Document(DEFAULT, List(Cat(Cat(Empty, ListItem(Cat(Empty, Literal(\"outer\")), 0)), List(Cat(Empty, ListItem(Cat(Empty, Literal(\"inner\")), 1)), 1)), 0))"
+            "Document(DEFAULT, List(Block([ListItem(Literal(\"outer\"), 0), List(Block([ListItem(Literal(\"inner\"), 1)]), 1, Bullet('*'))]), 0, Bullet('*')))"
         );
     }
 
@@ -832,10 +1794,58 @@ mod tests {
     fn list_multiline_item() {
         assert_eq!(
             parse_to_ast("* line one\n  continues\n"),
-            "Document(DEFAULT, List(Cat(Empty, ListItem(Cat(Cat(Cat(Empty, Literal(\"line one\")), Literal(\"\\n\")), Literal(\"continues\")), 0)), 0))"
+            "Document(DEFAULT, List(Block([ListItem(Block([Literal(\"line one\"), Literal(\"\\n\"), Literal(\"continues\")]), 0)]), 0, Bullet('*')))"
+        );
+    }
+
+    // --- Ordered lists ---
+
+    #[test]
+    fn ordered_list() {
+        assert_eq!(
+            parse_to_ast("1. a\n2. b\n"),
+            "Document(DEFAULT, List(Block([ListItem(Literal(\"a\"), 0), ListItem(Literal(\"b\"), 0)]), 0, Ordered(1, '.')))"
         );
     }
 
+    #[test]
+    fn ordered_list_with_start_number_and_parenthesis() {
+        assert_eq!(
+            parse_to_ast("7) a\n"),
+            "Document(DEFAULT, List(Block([ListItem(Literal(\"a\"), 0)]), 0, Ordered(7, ')')))"
+        );
+    }
+
+    #[test]
+    fn ordered_list_nested_in_bullet_list() {
+        assert_eq!(
+            parse_to_ast("* a\n  1. b\n* c\n"),
+            "Document(DEFAULT, List(Block([ListItem(Literal(\"a\"), 0), List(Block([ListItem(Literal(\"b\"), 1)]), 1, Ordered(1, '.')), ListItem(Literal(\"c\"), 0)]), 0, Bullet('*')))"
+        );
+    }
+
+    #[test]
+    fn other_delimiter_starts_new_list() {
+        assert_eq!(
+            parse_to_ast("1. a\n1) b\n"),
+            "Document(DEFAULT, Block([List(Block([ListItem(Literal(\"a\"), 0)]), 0, Ordered(1, '.')), List(Block([ListItem(Literal(\"b\"), 0)]), 0, Ordered(1, ')'))]))"
+        );
+    }
+
+    #[test]
+    fn number_at_wrapped_line_start_is_text() {
+        // only lists starting with 1 may interrupt a paragraph
+        assert_eq!(
+            parse_to_ast("am\n2. Oktober"),
+            "Document(DEFAULT, Block([Literal(\"am\"), LineBreak, Literal(\"2. Oktober\")]))"
+        );
+    }
+
+    #[test]
+    fn number_without_space_is_text() {
+        assert_eq!(parse_to_ast("3.14"), "Document(DEFAULT, Literal(\"3.14\"))");
+    }
+
     // --- Inline code ---
 
     #[test]
@@ -851,7 +1861,7 @@ mod tests {
         // a backtick-code starting with '.' must escape the dot
         assert_eq!(
             parse_to_ast("`.PP`"),
-            "Document(DEFAULT, InlineCode(Cat(EscapeLit(\".\"), Literal(\"PP\"))))"
+            "Document(DEFAULT, InlineCode(Inline([EscapeLit(\".\"), Literal(\"PP\")])))"
         );
     }
 
@@ -861,7 +1871,7 @@ mod tests {
     fn code_block_plain() {
         assert_eq!(
             parse_to_ast("```\nhello\n```\n"),
-            "Document(DEFAULT, Cat(CodeBlock(Empty, PreformattedLiteral(\"hello\\n\")), LineBreak))"
+            "Document(DEFAULT, Block([CodeBlock(Empty, PreformattedLiteral(\"hello\\n\")), LineBreak]))"
         );
     }
 
@@ -869,7 +1879,7 @@ mod tests {
     fn code_block_with_type() {
         assert_eq!(
             parse_to_ast("```rust\nfn f() {}\n```\n"),
-            "Document(DEFAULT, Cat(CodeBlock(Literal(\"rust\"), PreformattedLiteral(\"fn f() {}\\n\")), LineBreak))"
+            "Document(DEFAULT, Block([CodeBlock(Literal(\"rust\"), PreformattedLiteral(\"fn f() {}\\n\")), LineBreak]))"
         );
     }
 
@@ -892,13 +1902,113 @@ mod tests {
     }
 
     #[test]
-    fn bare_brackets_not_link() {
+    fn bare_brackets_are_shortcut_reference() {
         assert_eq!(
             parse_to_ast("[not a link]"),
-            "Document(DEFAULT, Cat(Cat(Literal(\"[\"), Literal(\"not a link\")), Literal(\"]\")))"
+            "Document(DEFAULT, LinkRef(\"not a link\", Literal(\"not a link\"), \"\"))"
+        );
+    }
+
+    #[test]
+    fn empty_brackets_not_link() {
+        assert_eq!(
+            parse_to_ast("[]"),
+            "Document(DEFAULT, Inline([Literal(\"[\"), Empty, Literal(\"]\")]))"
+        );
+    }
+
+    #[test]
+    fn full_reference_link() {
+        assert_eq!(
+            parse_to_ast("[*text*][Ref] x"),
+            "Document(DEFAULT, Block([LinkRef(\"Ref\", Bold(Literal(\"text\")), \"[Ref] \"), Literal(\"x\")]))"
+        );
+    }
+
+    #[test]
+    fn collapsed_reference_link() {
+        assert_eq!(
+            parse_to_ast("[ref][]"),
+            "Document(DEFAULT, LinkRef(\"ref\", Literal(\"ref\"), \"[]\"))"
+        );
+    }
+
+    #[test]
+    fn link_definition() {
+        assert_eq!(
+            parse_to_ast("[ref]: http://example.com \"A title\"\nx"),
+            "Document(DEFAULT, Block([LinkDef(\"ref\", Literal(\"http://example.com\"), \"A title\"), Literal(\"x\")]))"
+        );
+        assert_eq!(
+            parse_to_ast("[ref]:   <http://example.com>  (title)"),
+            "Document(DEFAULT, LinkDef(\"ref\", Literal(\"http://example.com\"), \"title\"))"
         );
     }
 
+    #[test]
+    fn link_definition_needs_url_and_line_start() {
+        assert_eq!(
+            parse_to_ast("[ref]:\n"),
+            "Document(DEFAULT, Block([LinkRef(\"ref\", Literal(\"ref\"), \"\"), Literal(\":\"), LineBreak]))"
+        );
+        assert!(!parse_to_ast("a [ref]: url").contains("LinkDef"));
+        assert!(!parse_to_ast("[ref]: url \"title").contains("LinkDef"));
+    }
+
+    #[test]
+    fn angle_autolink() {
+        assert_eq!(
+            parse_to_ast("<https://example.com>"),
+            "Document(DEFAULT, HyperRef(Literal(\"https://example.com\"), Literal(\"https://example.com\")))"
+        );
+    }
+
+    #[test]
+    fn email_autolink() {
+        assert_eq!(
+            parse_to_ast("write <me@example.com>"),
+            "Document(DEFAULT, Block([Literal(\"write \"), HyperRef(Literal(\"me@example.com\"), Literal(\"mailto:me@example.com\"))]))"
+        );
+    }
+
+    #[test]
+    fn bare_url_autolink() {
+        assert_eq!(
+            parse_to_ast("see https://example.com/a_(b). next"),
+            "Document(DEFAULT, Block([Literal(\"see \"), HyperRef(Literal(\"https://example.com/a_(b)\"), Literal(\"https://example.com/a_(b)\")), EscapeLit(\".\"), Literal(\" next\")]))"
+        );
+    }
+
+    #[test]
+    fn url_slashes_are_no_pass_through_or_anchor() {
+        assert_eq!(
+            parse_to_ast("# see https://x.y /a/"),
+            "Document(DEFAULT, Heading(Inline([Literal(\"see https:\"), Literal(\"//\"), Literal(\"x.y \")]), 0, \"a\"))"
+        );
+        assert_eq!(
+            parse_to_ast("see http\\://x.y"),
+            "Document(DEFAULT, Block([Literal(\"see http\"), Escaped(':'), Literal(\"//\"), Literal(\"x.y\")]))"
+        );
+    }
+
+    #[test]
+    fn bare_url_ends_at_closing_char() {
+        assert_eq!(
+            parse_to_ast("^(http://x.org)"),
+            "Document(DEFAULT, Footnote(HyperRef(Literal(\"http://x.org\"), Literal(\"http://x.org\"))))"
+        );
+    }
+
+    #[test]
+    fn not_autolinks() {
+        assert_eq!(
+            parse_to_ast("a <b> c"),
+            "Document(DEFAULT, Block([Literal(\"a <b\"), Literal(\">\"), Literal(\" c\")]))"
+        );
+        assert_eq!(parse_to_ast("xhttp://x.org"), "Document(DEFAULT, Literal(\"xhttp://x.org\"))");
+        assert!(!parse_to_ast("[http://x.org](y)").contains("HyperRef(HyperRef"));
+    }
+
     // --- Images ---
 
     #[test]
@@ -921,7 +2031,7 @@ mod tests {
     fn bang_without_bracket_is_literal() {
         assert_eq!(
             parse_to_ast("!hello"),
-            "Document(DEFAULT, Cat(Literal(\"!\"), Literal(\"hello\")))"
+            "Document(DEFAULT, Block([Literal(\"!\"), Literal(\"hello\")]))"
         );
     }
 
@@ -939,7 +2049,7 @@ mod tests {
     fn caret_without_paren_is_literal() {
         assert_eq!(
             parse_to_ast("^x"),
-            "Document(DEFAULT, Cat(Literal(\"^\"), Literal(\"x\")))"
+            "Document(DEFAULT, Block([Literal(\"^\"), Literal(\"x\")]))"
         );
     }
 
@@ -947,7 +2057,7 @@ mod tests {
     fn right_sidenote() {
         assert_eq!(
             parse_to_ast(">(side)\n"),
-            "Document(DEFAULT, Cat(RightSidenote(Literal(\"side\")), LineBreak))"
+            "Document(DEFAULT, Block([RightSidenote(Literal(\"side\")), LineBreak]))"
         );
     }
 
@@ -961,9 +2071,10 @@ mod tests {
 
     #[test]
     fn gt_without_paren_is_literal() {
+        // at the start of a line, it would begin a block quote
         assert_eq!(
-            parse_to_ast(">x"),
-            "Document(DEFAULT, Cat(Literal(\">\"), Literal(\"x\")))"
+            parse_to_ast("a>x"),
+            "Document(DEFAULT, Block([Literal(\"a\"), Literal(\">\"), Literal(\"x\")]))"
         );
     }
 
@@ -1003,11 +2114,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn include() {
+        assert_eq!(
+            parse_to_ast("//include chapter.md#Intro\nx"),
+            "Document(DEFAULT, Block([Include(\"chapter.md#Intro\"), LineBreak, Literal(\"x\")]))"
+        );
+    }
+
     #[test]
     fn single_slash_is_literal() {
         assert_eq!(
             parse_to_ast("/x"),
-            "Document(DEFAULT, Cat(Literal(\"/\"), Literal(\"x\")))"
+            "Document(DEFAULT, Block([Literal(\"/\"), Literal(\"x\")]))"
         );
     }
 
@@ -1017,7 +2136,7 @@ mod tests {
     fn metadata_block() {
         assert_eq!(
             parse_to_ast("---\ntitle: My Doc\n---\n"),
-            "Document(DEFAULT, MetaDataBlock(Cat(Empty, MetaDataItem(\"title\", \"My Doc\"))))"
+            "Document(DEFAULT, MetaDataBlock(Block([MetaDataItem(\"title\", \"My Doc\")])))"
         );
     }
 
@@ -1027,7 +2146,7 @@ mod tests {
     fn double_newline_produces_paragraph() {
         assert_eq!(
             parse_to_ast("a\n\nb"),
-            "Document(DEFAULT, Cat(Cat(Literal(\"a\"), Cat(LineBreak, Paragraph)), Literal(\"b\")))"
+            "Document(DEFAULT, Block([Literal(\"a\"), LineBreak, Paragraph, Literal(\"b\")]))"
         );
     }
 
@@ -1035,7 +2154,7 @@ mod tests {
     fn single_newline_is_linebreak() {
         assert_eq!(
             parse_to_ast("a\nb"),
-            "Document(DEFAULT, Cat(Cat(Literal(\"a\"), LineBreak), Literal(\"b\")))"
+            "Document(DEFAULT, Block([Literal(\"a\"), LineBreak, Literal(\"b\")]))"
         );
     }
 
@@ -1053,7 +2172,372 @@ mod tests {
     fn backslash_without_brace_is_literal() {
         assert_eq!(
             parse_to_ast("\\x"),
-            "Document(DEFAULT, Cat(Literal(\"\\\\\"), Literal(\"x\")))"
+            "Document(DEFAULT, Block([EscapeLit(\"\\\\\"), Literal(\"x\")]))"
+        );
+    }
+
+    // --- Backslash escapes ---
+
+    #[test]
+    fn escaped_metacharacters_are_literal() {
+        assert_eq!(
+            parse_to_ast("\\*a\\* \\_b\\_"),
+            "Document(DEFAULT, Block([Escaped('*'), Literal(\"a\"), Escaped('*'), Literal(\" \"), Escaped('_'), Literal(\"b\"), Escaped('_')]))"
+        );
+    }
+
+    #[test]
+    fn escaped_backslash() {
+        assert_eq!(
+            parse_to_ast("a\\\\{red}"),
+            "Document(DEFAULT, Block([Literal(\"a\"), Escaped('\\\\'), SmallCaps(Literal(\"red\"))]))"
+        );
+    }
+
+    #[test]
+    fn escapes_prevent_block_constructs() {
+        assert_eq!(
+            parse_to_ast("\\# no heading"),
+            "Document(DEFAULT, Block([Escaped('#'), Literal(\" no heading\")]))"
+        );
+        assert_eq!(
+            parse_to_ast("\\* no item"),
+            "Document(DEFAULT, Block([Escaped('*'), Literal(\" no item\")]))"
+        );
+    }
+
+    #[test]
+    fn escape_inside_bold() {
+        assert_eq!(
+            parse_to_ast("*a\\*b*"),
+            "Document(DEFAULT, Bold(Block([Literal(\"a\"), Escaped('*'), Literal(\"b\")])))"
+        );
+    }
+
+    // --- Spans ---
+
+    fn spans(input: &str) -> (crate::syntax::Tree, Vec<crate::syntax::Span>) {
+        use crate::syntax::Tree;
+        fn collect(t: &Tree, out: &mut Vec<crate::syntax::Span>) {
+            out.push(t.span());
+            match t {
+                Tree::Document(_, b, _) | Tree::Heading(b, _, _, _) | Tree::Bold(b, _) => {
+                    collect(b, out)
+                }
+                Tree::Block(children, _) | Tree::Inline(children, _) => {
+                    children.iter().for_each(|child| collect(child, out))
+                }
+                _ => {}
+            }
+        }
+        let (tree, _) = Parser::parse(input);
+        let mut out = vec![];
+        collect(&tree, &mut out);
+        (tree, out)
+    }
+
+    #[test]
+    fn document_span_covers_input() {
+        let (tree, _) = spans("a
+bc");
+        let span = tree.span();
+        assert_eq!((span.start, span.end), (0, 4));
+        assert_eq!((span.line, span.column, span.end_line, span.end_column), (1, 1, 2, 3));
+    }
+
+    #[test]
+    fn heading_span_has_line_and_column() {
+        let (_, all) = spans("intro\n\n# Title\n");
+        // the heading starts at the hash on line 3, its text at column 3
+        assert!(all.iter().any(|s| (s.start, s.end, s.line, s.column) == (7, 14, 3, 1)));
+        assert!(all.iter().any(|s| (s.start, s.end, s.line, s.column) == (9, 14, 3, 3)));
+    }
+
+    #[test]
+    fn bold_span_includes_delimiters() {
+        let (_, all) = spans("some *bold* text");
+        assert!(all.iter().any(|s| (s.start, s.end, s.column, s.end_column) == (5, 11, 6, 12)));
+        assert!(all.iter().all(|s| !s.is_unknown()));
+    }
+
+    #[test]
+    fn empty_input_has_unknown_span() {
+        assert!(Parser::parse("").0.span().is_unknown());
+    }
+
+    // --- Recovery from malformed input ---
+
+    fn diagnostics(s: &str) -> Vec<String> {
+        let (_, diagnostics) = Parser::parse(s);
+        diagnostics.iter().map(|d| format!("{d}")).collect()
+    }
+
+    #[test]
+    fn well_formed_input_has_no_diagnostics() {
+        assert!(diagnostics("# Title\n\nsome *bold* [link](url)").is_empty());
+    }
+
+    #[test]
+    fn unterminated_link_is_literal() {
+        assert_eq!(
+            parse_to_ast("[link]("),
+            "Document(DEFAULT, Block([Literal(\"[\"), Literal(\"link](\")]))"
         );
+        assert_eq!(
+            diagnostics("[link]("),
+            ["1:1: error: could not parse link: expected ')' at line 1, column 8, but found end of input"]
+        );
+    }
+
+    #[test]
+    fn unclosed_meta_data_block_is_literal() {
+        assert_eq!(
+            parse_to_ast("---\ntitle: x"),
+            "Document(DEFAULT, Block([Literal(\"---\"), LineBreak, Literal(\"title: x\")]))"
+        );
+        assert_eq!(
+            diagnostics("---\ntitle: x\nBody\n"),
+            ["1:1: error: could not parse metadata block: expected ':' at line 3, column 5, but found line break"]
+        );
+    }
+
+    #[test]
+    fn image_without_path_is_literal() {
+        assert_eq!(
+            parse_to_ast("![cap] x"),
+            "Document(DEFAULT, Block([Literal(\"![\"), Literal(\"cap] x\")]))"
+        );
+        assert_eq!(
+            diagnostics("text\n![cap] x"),
+            ["2:1: error: could not parse image: expected '(' at line 2, column 7, but found ' '"]
+        );
+    }
+
+    #[test]
+    fn unterminated_inline_constructs_are_literal() {
+        assert_eq!(
+            parse_to_ast("^(note"),
+            "Document(DEFAULT, Block([Literal(\"^(\"), Literal(\"note\")]))"
+        );
+        assert_eq!(
+            parse_to_ast("`code"),
+            "Document(DEFAULT, Block([Literal(\"`\"), Literal(\"code\")]))"
+        );
+        assert_eq!(
+            parse_to_ast("{caps"),
+            "Document(DEFAULT, Block([Literal(\"{\"), Literal(\"caps\")]))"
+        );
+        assert_eq!(
+            parse_to_ast(">(side"),
+            "Document(DEFAULT, Block([Literal(\">(\"), Literal(\"side\")]))"
+        );
+    }
+
+    #[test]
+    fn nested_failures_are_reported_once() {
+        assert_eq!(
+            diagnostics("[a ^(b"),
+            [
+                "1:1: error: could not parse link: expected ']' at line 1, column 7, but found end of input",
+                "1:4: error: could not parse footnote: expected ')' at line 1, column 7, but found end of input"
+            ]
+        );
+    }
+
+    #[test]
+    fn heading_with_slash_but_without_anchor() {
+        assert_eq!(
+            parse_to_ast("# and/or"),
+            "Document(DEFAULT, Heading(Inline([Literal(\"and\"), Literal(\"/or\")]), 0, \"\"))"
+        );
+    }
+
+    #[test]
+    fn block_chars_in_link_text_are_literal() {
+        assert_eq!(
+            parse_to_ast("[a-b](x)"),
+            "Document(DEFAULT, HyperRef(Inline([Literal(\"a\"), Literal(\"-\"), Literal(\"b\")]), Literal(\"x\")))"
+        );
+    }
+
+    // --- Tables ---
+
+    #[test]
+    fn table_with_header_and_rows() {
+        assert_eq!(
+            parse_to_ast("| a | b |\n|---|---|\n| 1 | 2 |\n"),
+            "Document(DEFAULT, Table([Default, Default], TableRow([Literal(\"a\"), Literal(\"b\")]), \
+             [TableRow([Literal(\"1\"), Literal(\"2\")])], Empty))"
+        );
+    }
+
+    #[test]
+    fn table_alignments() {
+        assert!(parse_to_ast("a | b | c | d\n:-- | :-: | --: | -\n")
+            .starts_with("Document(DEFAULT, Table([Left, Center, Right, Default], "));
+    }
+
+    #[test]
+    fn table_cells_are_formatted() {
+        assert_eq!(
+            parse_to_ast("| *a* | `b` |\n|-|-|\n"),
+            "Document(DEFAULT, Table([Default, Default], \
+             TableRow([Bold(Literal(\"a\")), InlineCode(Literal(\"b\"))]), [], Empty))"
+        );
+    }
+
+    #[test]
+    fn table_rows_are_padded_and_truncated() {
+        assert_eq!(
+            parse_to_ast("| a | b |\n|-|-|\n| 1 |\n| 1 | 2 | 3 |"),
+            "Document(DEFAULT, Table([Default, Default], TableRow([Literal(\"a\"), Literal(\"b\")]), \
+             [TableRow([Literal(\"1\"), Empty]), TableRow([Literal(\"1\"), Literal(\"2\")])], Empty))"
+        );
+    }
+
+    #[test]
+    fn table_caption() {
+        assert!(parse_to_ast("| a |\n|-|\n\nTable: *the* caption\n")
+            .ends_with("[], Inline([Bold(Literal(\"the\")), Literal(\" caption\")])))"));
+        assert!(parse_to_ast("| a |\n|-|\n: caption")
+            .ends_with("[], Literal(\"caption\")))"));
+    }
+
+    #[test]
+    fn table_ends_at_line_without_pipe() {
+        assert_eq!(
+            parse_to_ast("| a |\n|-|\n\nb"),
+            "Document(DEFAULT, Block([Table([Default], TableRow([Literal(\"a\")]), [], Empty), \
+             LineBreak, Literal(\"b\")]))"
+        );
+    }
+
+    #[test]
+    fn escaped_pipe_in_table_cell() {
+        assert_eq!(
+            parse_to_ast("| a \\| b |\n|-|\n"),
+            "Document(DEFAULT, Table([Default], TableRow([Inline([Literal(\"a \"), Escaped('|'), Literal(\" b\")])]), [], Empty))"
+        );
+    }
+
+    #[test]
+    fn pipe_without_delimiter_row_is_no_table() {
+        assert_eq!(
+            parse_to_ast("a | b\nc"),
+            "Document(DEFAULT, Block([Literal(\"a | b\"), LineBreak, Literal(\"c\")]))"
+        );
+    }
+
+    #[test]
+    fn table_cell_spans() {
+        let (tree, _) = Parser::parse("x\n| a | bc |\n|-|-|\n");
+        let crate::syntax::Tree::Document(_, body, _) = tree else { panic!() };
+        let crate::syntax::Tree::Block(children, _) = *body else { panic!() };
+        let Some(crate::syntax::Tree::Table(_, header, _, _, span)) = children.into_iter().last()
+        else {
+            panic!()
+        };
+        assert_eq!((span.line, span.column, span.end_line), (2, 1, 4));
+        let crate::syntax::Tree::TableRow(cells, _) = *header else { panic!() };
+        let cell = cells[1].span();
+        assert_eq!((cell.start, cell.end, cell.line, cell.column), (8, 10, 2, 7));
+    }
+
+    // --- Block quotes ---
+
+    #[test]
+    fn block_quote() {
+        assert_eq!(
+            parse_to_ast("> quoted\n"),
+            "Document(DEFAULT, BlockQuote(Block([Literal(\"quoted\"), LineBreak])))"
+        );
+    }
+
+    #[test]
+    fn block_quote_with_paragraphs() {
+        assert_eq!(
+            parse_to_ast("> a\n>\n> b\n\nc"),
+            "Document(DEFAULT, Block([BlockQuote(Block([Literal(\"a\"), LineBreak, Paragraph, \
+             Literal(\"b\"), LineBreak])), LineBreak, Literal(\"c\")]))"
+        );
+    }
+
+    #[test]
+    fn nested_block_quote_with_list() {
+        assert_eq!(
+            parse_to_ast(">> * a\n"),
+            "Document(DEFAULT, BlockQuote(BlockQuote(List(Block([ListItem(Literal(\"a\"), 0)]), 0, Bullet('*')))))"
+        );
+    }
+
+    #[test]
+    fn sidenote_at_line_start_is_no_block_quote() {
+        assert!(parse_to_ast(">(note)").starts_with("Document(DEFAULT, RightSidenote("));
+        assert!(parse_to_ast(">>(1)").starts_with("Document(DEFAULT, ChapterMark("));
+        assert_eq!(
+            parse_to_ast("a > b"),
+            "Document(DEFAULT, Block([Literal(\"a \"), Literal(\">\"), Literal(\" b\")]))"
+        );
+    }
+
+    #[test]
+    fn block_quote_spans_point_into_input() {
+        let (tree, _) = Parser::parse("x\n> a\n> *b*\n");
+        let crate::syntax::Tree::Document(_, body, _) = tree else { panic!() };
+        let crate::syntax::Tree::Block(children, _) = *body else { panic!() };
+        let quote = children.into_iter().last().unwrap();
+        let span = quote.span();
+        assert_eq!((span.start, span.end, span.line, span.end_line), (2, 12, 2, 4));
+        let crate::syntax::Tree::BlockQuote(content, _) = quote else { panic!() };
+        let crate::syntax::Tree::Block(children, _) = *content else { panic!() };
+        let bold = children.iter().find(|child| matches!(child, crate::syntax::Tree::Bold(..)));
+        let span = bold.unwrap().span();
+        assert_eq!((span.start, span.end, span.line, span.column), (8, 11, 3, 3));
+    }
+
+    #[test]
+    fn block_quote_diagnostics_point_into_input() {
+        assert_eq!(
+            diagnostics("> [a"),
+            ["1:3: error: could not parse link: expected ']' at line 1, column 5, but found end of input"]
+        );
+        assert_eq!(
+            diagnostics("> a\n> > [b"),
+            ["2:5: error: could not parse link: expected ']' at line 2, column 7, but found end of input"]
+        );
+    }
+
+    // --- Reference footnotes ---
+
+    #[test]
+    fn footnote_reference() {
+        assert_eq!(
+            parse_to_ast("a[^note] b"),
+            "Document(DEFAULT, Block([Literal(\"a\"), FootnoteRef(\"note\"), Literal(\" b\")]))"
+        );
+    }
+
+    #[test]
+    fn footnote_definition_with_paragraphs() {
+        assert_eq!(
+            parse_to_ast("[^1]: one\n    two\n\n    three\n\nafter"),
+            "Document(DEFAULT, Block([FootnoteDef(\"1\", Block([Literal(\"one\"), LineBreak, \
+             Literal(\"two\"), LineBreak, Paragraph, Literal(\"three\")])), LineBreak, Literal(\"after\")]))"
+        );
+    }
+
+    #[test]
+    fn footnote_definition_starting_on_next_line() {
+        let (tree, _) = Parser::parse("[^1]:\n    text\n");
+        assert_eq!(format!("{tree:?}"), "Document(DEFAULT, FootnoteDef(\"1\", Literal(\"text\")))");
+        let crate::syntax::Tree::Document(_, def, _) = tree else { panic!() };
+        let crate::syntax::Tree::FootnoteDef(_, text, _) = *def else { panic!() };
+        let span = text.span();
+        assert_eq!((span.start, span.end, span.line, span.column), (10, 14, 2, 5));
+    }
+
+    #[test]
+    fn caret_with_space_is_no_footnote() {
+        assert!(!parse_to_ast("[^ a]").contains("Footnote"));
     }
 }
@@ -1,5 +1,11 @@
 pub mod canonicalize;
 pub mod chain;
 pub mod code_block;
+pub mod external;
+pub mod footnotes;
 pub mod identity;
+pub mod include;
 pub mod image_converter;
+pub mod links;
+pub mod old_style_figures;
+pub mod registry;
@@ -1,7 +1,8 @@
 use std::time::Instant;
 
+use crate::diagnostic::Diagnostic;
 use crate::log::get_log_level;
-use crate::{m_trc, Process};
+use crate::{m_trc, Process, Result};
 
 use crate::Tree;
 use core::fmt::Debug;
@@ -14,17 +15,23 @@ pub struct Chain<'a> {
 }
 
 impl Process for Chain<'_> {
-    fn process(&mut self, exp: Tree) -> Tree {
+    fn process(&mut self, exp: Tree) -> Result<Tree> {
         let start = Instant::now();
-        let result = self.a.process(exp);
+        let result = self.a.process(exp)?;
         if get_log_level() >= 2 {
             m_trc!("{:?}: {:?}", self.a, start.elapsed());
         }
-        let result = self.b.process(result);
+        let result = self.b.process(result)?;
         if get_log_level() >= 2 {
             m_trc!("{:?}: {:?}", self.b, start.elapsed());
         }
-        result
+        Ok(result)
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.a.take_diagnostics();
+        diagnostics.append(&mut self.b.take_diagnostics());
+        diagnostics
     }
 }
 
@@ -0,0 +1,219 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::diagnostic::Diagnostic;
+use crate::syntax::{fold_children, json, Fold, Span, Tree};
+use crate::{m_dbg, m_trc, Process};
+
+/// External processor pipes the tree through filter programs, like pandoc filters.
+/// each filter reads the tree as JSON on its standard input and writes the transformed
+/// tree to its standard output. filters are given on the command line and in the
+/// `filters` meta data item of the document, both as comma separated commands.
+/// the ones the document names only run when asked for, as they would let any
+/// document execute programs, otherwise they are reported.
+#[derive(Debug, Default)]
+pub struct ExternalFilters {
+    commands: Vec<String>,
+    /// run the filters named by the document?
+    document_filters: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// splits a comma separated list of filter commands
+pub fn split_commands(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// takes the `filters` meta data items out of the tree, collecting the commands they name
+#[derive(Default)]
+struct DocumentFilters(Vec<(String, Span)>);
+
+impl Fold for DocumentFilters {
+    fn fold(&mut self, tree: Tree) -> Tree {
+        match tree {
+            Tree::MetaDataItem(key, value, span) if key == "filters" => {
+                self.0
+                    .extend(split_commands(&value).into_iter().map(|c| (c, span)));
+                Tree::Empty(span)
+            }
+            _ => fold_children(self, tree),
+        }
+    }
+}
+
+impl ExternalFilters {
+    /// runs `command` on `tree`, returning the tree unchanged if the filter fails
+    fn run(&mut self, command: &str, tree: Tree, span: Span) -> Tree {
+        m_dbg!("running filter: {}", command);
+        match filter(command, &tree) {
+            Ok(filtered) => filtered,
+            Err(message) => {
+                self.diagnostics.push(Diagnostic::error(
+                    &format!("filter `{command}` {message}"),
+                    span,
+                ));
+                tree
+            }
+        }
+    }
+}
+
+/// pipes `tree` through `command`, describing what went wrong if it fails
+fn filter(command: &str, tree: &Tree) -> Result<Tree, String> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or("is empty")?;
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not be started: {e}"))?;
+    let input = json::to_json(tree);
+    let mut stdin = child.stdin.take().ok_or("has no standard input")?;
+    // writing from a thread of its own, so that a filter writing before it has read
+    // all of its input cannot block us. filters not reading at all are fine, too.
+    let writer = thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("could not be read from: {e}"))?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(format!("failed with {}", output.status));
+    }
+    let output = String::from_utf8(output.stdout)
+        .map_err(|_| "returned output that is not valid UTF-8".to_string())?;
+    json::from_json(&output).map_err(|e| format!("returned {e}"))
+}
+
+impl Process for ExternalFilters {
+    fn process(&mut self, exp: Tree) -> crate::Result<Tree> {
+        m_trc!("{:?}", self);
+        let document_span = exp.span();
+        let mut document_filters = DocumentFilters::default();
+        let mut tree = document_filters.fold(exp);
+        let commands = self.commands.clone();
+        for command in commands {
+            tree = self.run(&command, tree, document_span);
+        }
+        for (command, span) in document_filters.0 {
+            if self.document_filters {
+                tree = self.run(&command, tree, span);
+            } else {
+                self.diagnostics.push(Diagnostic::warning(
+                    &format!(
+                        "filter `{command}` named by the document is not run, \
+                         allow it with --document-filters"
+                    ),
+                    span,
+                ));
+            }
+        }
+        Ok(tree)
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+/// creates a processor running the given filter commands, followed by the
+/// ones the document names, if `document_filters` is set
+pub fn new(commands: Vec<String>, document_filters: bool) -> Box<dyn Process> {
+    Box::new(ExternalFilters {
+        commands,
+        document_filters,
+        diagnostics: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn filter(commands: &[&str], input: &str) -> (String, Vec<String>) {
+        filter_allowing(commands, true, input)
+    }
+
+    fn filter_allowing(
+        commands: &[&str],
+        document_filters: bool,
+        input: &str,
+    ) -> (String, Vec<String>) {
+        let mut filters = ExternalFilters {
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+            document_filters,
+            diagnostics: Vec::new(),
+        };
+        let (tree, _) = Parser::parse(input);
+        let tree = filters.process(tree).unwrap();
+        let diagnostics = filters
+            .take_diagnostics()
+            .iter()
+            .map(|d| format!("{d}"))
+            .collect();
+        (format!("{tree:?}"), diagnostics)
+    }
+
+    #[test]
+    fn filters_transform_the_tree() {
+        let (tree, diagnostics) = filter(&["sed s/hello/bye/", "cat"], "hello");
+        assert_eq!(tree, "Document(DEFAULT, Literal(\"bye\"))");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn document_filters_run_after_command_line_filters() {
+        let (tree, diagnostics) = filter(
+            &["sed s/kk/qq/"],
+            "---\nfilters: sed s/qq/ww/, sed s/ww/zz/\n---\nkk",
+        );
+        assert_eq!(
+            tree,
+            "Document(DEFAULT, Block([MetaDataBlock(Block([Empty])), Literal(\"zz\")]))"
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn document_filters_only_run_when_allowed() {
+        let (tree, diagnostics) = filter_allowing(
+            &["sed s/kk/qq/"],
+            false,
+            "---\nfilters: sed s/qq/ww/\n---\nkk",
+        );
+        assert_eq!(
+            tree,
+            "Document(DEFAULT, Block([MetaDataBlock(Block([Empty])), Literal(\"qq\")]))"
+        );
+        assert_eq!(
+            diagnostics,
+            ["2:1: warning: filter `sed s/qq/ww/` named by the document is not run, \
+              allow it with --document-filters"]
+        );
+    }
+
+    #[test]
+    fn failures_are_reported_and_leave_the_tree_alone() {
+        let (tree, diagnostics) = filter(&["false", "echo nonsense", "/does/not/exist"], "hello");
+        assert_eq!(tree, "Document(DEFAULT, Literal(\"hello\"))");
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(
+            diagnostics[0],
+            "1:1: error: filter `false` failed with exit status: 1"
+        );
+        assert!(
+            diagnostics[1].starts_with("1:1: error: filter `echo nonsense` returned invalid JSON")
+        );
+        assert!(
+            diagnostics[2].starts_with("1:1: error: filter `/does/not/exist` could not be started")
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::Diagnostic;
+use crate::syntax::{empty, lit, Span, Tree};
+use crate::{m_trc, Process, Result};
+
+/// Footnotes processor resolves reference style footnotes: the definitions `[^label]: ...`
+/// are taken out of the tree and each reference `[^label]` is replaced by an inline
+/// `Tree::Footnote` with the content of its definition.
+#[derive(Debug, Default)]
+pub struct FootnoteResolver {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl FootnoteResolver {
+    /// takes the footnote definitions out of `tree`, leaving empty nodes behind
+    fn collect_definitions(
+        &mut self,
+        tree: &mut Tree,
+        definitions: &mut HashMap<String, (Tree, Span)>,
+    ) {
+        if let Tree::FootnoteDef(..) = tree {
+            let Tree::FootnoteDef(label, content, span) = std::mem::replace(tree, empty()) else {
+                unreachable!()
+            };
+            match definitions.entry(label) {
+                Entry::Occupied(entry) => self.diagnostics.push(Diagnostic::warning(
+                    &format!(
+                        "footnote [^{}] is defined more than once, using the first definition",
+                        entry.key()
+                    ),
+                    span,
+                )),
+                Entry::Vacant(entry) => {
+                    entry.insert((*content, span));
+                }
+            }
+            return;
+        }
+        for child in tree.children_mut() {
+            self.collect_definitions(child, definitions);
+        }
+    }
+
+    /// replaces the references in `tree` with footnotes, remembering the labels used.
+    /// `resolving` holds the labels of the definitions we are inside of, references
+    /// within definitions are resolved, too.
+    fn resolve(
+        &mut self,
+        tree: &mut Tree,
+        definitions: &HashMap<String, (Tree, Span)>,
+        used: &mut HashSet<String>,
+        resolving: &mut Vec<String>,
+    ) {
+        if let Tree::FootnoteRef(label, span) = tree {
+            let span = *span;
+            *tree = match definitions.get(label.as_str()) {
+                Some(_) if resolving.contains(label) => {
+                    self.diagnostics.push(Diagnostic::warning(
+                        &format!("footnote [^{label}] refers to itself"),
+                        span,
+                    ));
+                    lit(&format!("[^{label}]")).with_span(span)
+                }
+                Some((content, _)) => {
+                    used.insert(label.clone());
+                    let mut content = content.clone();
+                    resolving.push(label.clone());
+                    self.resolve(&mut content, definitions, used, resolving);
+                    resolving.pop();
+                    Tree::Footnote(Box::new(content), span)
+                }
+                None => {
+                    self.diagnostics.push(Diagnostic::warning(
+                        &format!("undefined footnote [^{label}]"),
+                        span,
+                    ));
+                    lit(&format!("[^{label}]")).with_span(span)
+                }
+            };
+            return;
+        }
+        for child in tree.children_mut() {
+            self.resolve(child, definitions, used, resolving);
+        }
+    }
+}
+
+impl Process for FootnoteResolver {
+    fn process(&mut self, mut exp: Tree) -> Result<Tree> {
+        m_trc!("{:?}", self);
+        let mut definitions = HashMap::new();
+        self.collect_definitions(&mut exp, &mut definitions);
+        let mut used = HashSet::new();
+        self.resolve(&mut exp, &definitions, &mut used, &mut vec![]);
+        let mut unused: Vec<(&String, &Span)> = definitions
+            .iter()
+            .filter(|(label, _)| !used.contains(*label))
+            .map(|(label, (_, span))| (label, span))
+            .collect();
+        unused.sort_by_key(|(_, span)| span.start);
+        for (label, span) in unused {
+            self.diagnostics.push(Diagnostic::warning(
+                &format!("footnote [^{label}] is never referenced"),
+                *span,
+            ));
+        }
+        Ok(exp)
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+pub fn new() -> Box<dyn Process> {
+    Box::new(FootnoteResolver::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn resolve(input: &str) -> (String, Vec<String>) {
+        let mut resolver = FootnoteResolver::default();
+        let (tree, _) = Parser::parse(input);
+        let tree = resolver.process(tree).unwrap();
+        let diagnostics = resolver
+            .take_diagnostics()
+            .iter()
+            .map(|d| format!("{d}"))
+            .collect();
+        (format!("{tree:?}"), diagnostics)
+    }
+
+    #[test]
+    fn reference_is_replaced_by_definition() {
+        let (tree, diagnostics) = resolve("a[^n]\n\n[^n]: note\n");
+        assert_eq!(
+            tree,
+            "Document(DEFAULT, Block([Literal(\"a\"), Footnote(Literal(\"note\")), LineBreak, Paragraph, Empty]))"
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn definition_may_come_first() {
+        let (tree, _) = resolve("[^n]: note\n*a[^n]*");
+        assert!(tree.contains("Bold(Block([Literal(\"a\"), Footnote(Literal(\"note\"))]))"));
+    }
+
+    #[test]
+    fn references_inside_definitions_are_resolved() {
+        let (tree, diagnostics) = resolve("# a[^1]\n[^1]: b[^2]\n[^2]: c[^2]\n");
+        assert!(tree.contains(
+            "Footnote(Block([Literal(\"b\"), Footnote(Block([Literal(\"c\"), Literal(\"[^2]\")]))]))"
+        ));
+        assert_eq!(diagnostics, ["3:8: warning: footnote [^2] refers to itself"]);
+    }
+
+    #[test]
+    fn undefined_reference_is_reported() {
+        let (tree, diagnostics) = resolve("a[^x]");
+        assert_eq!(
+            tree,
+            "Document(DEFAULT, Block([Literal(\"a\"), Literal(\"[^x]\")]))"
+        );
+        assert_eq!(diagnostics, ["1:2: warning: undefined footnote [^x]"]);
+    }
+
+    #[test]
+    fn unused_and_duplicate_definitions_are_reported() {
+        let (_, diagnostics) = resolve("[^a]: one\n[^b]: two\n[^a]: three\n");
+        assert_eq!(
+            diagnostics,
+            [
+                "3:1: warning: footnote [^a] is defined more than once, using the first definition",
+                "1:1: warning: footnote [^a] is never referenced",
+                "2:1: warning: footnote [^b] is never referenced"
+            ]
+        );
+    }
+}
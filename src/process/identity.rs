@@ -1,5 +1,5 @@
-use crate::m_trc;
-use crate::Process;
+use crate::syntax::Tree;
+use crate::{m_trc, Process, Result};
 
 /// Identity processor does nothing and just returns an
 /// identical AST
@@ -7,9 +7,9 @@ use crate::Process;
 pub struct Identity {}
 
 impl Process for Identity {
-    fn process(&mut self, exp: crate::syntax::Tree) -> crate::syntax::Tree {
+    fn process(&mut self, exp: Tree) -> Result<Tree> {
         m_trc!("{:?}", self);
-        exp
+        Ok(exp)
     }
 }
 pub fn new() -> Box<dyn Process> {
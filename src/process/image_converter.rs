@@ -4,10 +4,10 @@ use std::process::Command;
 use crate::{
     config::Config,
     m_dbg, m_trc,
-    syntax::{image, lit, Tree},
+    syntax::{fold_children, image, lit, Fold, Tree},
 };
 
-use crate::Process;
+use crate::{Error, Process, Result};
 
 /// ImageConverter processor currently only transforms
 /// path information in the image expression.
@@ -15,46 +15,59 @@ use crate::Process;
 #[derive(Debug)]
 pub struct ImageConverter<'a> {
     config: &'a Config,
+    /// the first error met, the remaining images are left as they are
+    error: Option<Error>,
 }
 
-impl ImageConverter<'_> {
-    /// walks the tree until it find Tree::Image nodes, uses recursive descent
-    fn walk(&mut self, exp: Tree) -> Tree {
-        match exp {
-            Tree::Document(dt, be) => Tree::Document(dt, Box::new(self.walk(*be))),
-            Tree::Cat(b1, b2) => self.walk(*b1).cat(self.walk(*b2)),
-            Tree::Image(caption, path, size_spec) => self.resolve_path(*caption, *path, *size_spec),
-            _ => exp,
+impl Fold for ImageConverter<'_> {
+    fn fold(&mut self, tree: Tree) -> Tree {
+        match tree {
+            Tree::Image(caption, path, size_spec, span) if self.error.is_none() => {
+                match self.resolve_path(&path) {
+                    Ok(path) => image(*caption, path, *size_spec).with_span(span),
+                    Err(e) => {
+                        self.error = Some(e);
+                        Tree::Image(caption, path, size_spec, span)
+                    }
+                }
+            }
+            _ => fold_children(self, tree),
         }
     }
+}
 
+impl ImageConverter<'_> {
     /// resolves path specs in image references using the source file making them absolute
-    fn resolve_path(&mut self, caption: Tree, path: Tree, size_spec: Tree) -> Tree {
-        let path = match path {
-            Tree::Literal(p) => {
+    fn resolve_path(&mut self, path: &Tree) -> Result<Tree> {
+        match path {
+            Tree::Literal(p, span) => {
                 let mut resolved_path = p.clone();
                 if !p.starts_with('/') {
                     let parent_dir_path = crate::parent_dir(&self.config.source_file);
-                    let joined = parent_dir_path.join(&p);
+                    let joined = parent_dir_path.join(p);
                     resolved_path = joined
                         .to_str()
-                        .unwrap_or_else(|| crate::die!("image path contains invalid UTF-8: {}", joined.display()))
+                        .ok_or_else(|| {
+                            Error::Process(format!(
+                                "image path contains invalid UTF-8: {}",
+                                joined.display()
+                            ))
+                        })?
                         .to_string();
                 }
                 m_dbg!("resolved path: {}", resolved_path);
-                let resolved_path = self.convert_svg_if_needed(&resolved_path);
-                lit(&resolved_path)
+                let resolved_path = self.convert_svg_if_needed(&resolved_path)?;
+                Ok(lit(&resolved_path).with_span(*span))
             }
-            _ => path,
-        };
-        image(caption, path, size_spec)
+            _ => Ok(path.clone()),
+        }
     }
 
     /// if `path` points to an SVG file, converts it to PDF using `rsvg-convert`
     /// and returns the PDF path. skips conversion when the PDF is already up to date.
-    fn convert_svg_if_needed(&self, path: &str) -> String {
+    fn convert_svg_if_needed(&self, path: &str) -> Result<String> {
         if !path.to_lowercase().ends_with(".svg") {
-            return path.to_string();
+            return Ok(path.to_string());
         }
         let svg_path = Path::new(path);
         let pdf_path = svg_path.with_extension("pdf");
@@ -72,37 +85,82 @@ impl ImageConverter<'_> {
 
         if needs_conversion {
             m_dbg!("converting svg to pdf: {}", path);
-            let pdf_str = pdf_path.to_str()
-                .unwrap_or_else(|| crate::die!("PDF path contains invalid UTF-8: {}", pdf_path.display()));
             Command::new("rsvg-convert")
-                .args(["-f", "pdf", "-o", pdf_str, path])
+                .args(["-f", "pdf", "-o", &pdf_str(&pdf_path)?, path])
                 .status()
-                .unwrap_or_else(|e| crate::die!("failed to run rsvg-convert: {e}"));
+                .map_err(|e| Error::io("failed to run rsvg-convert", e))?;
         } else {
             m_dbg!("svg pdf cache hit: {}", pdf_path.display());
         }
 
-        pdf_path.to_str()
-            .unwrap_or_else(|| crate::die!("PDF path contains invalid UTF-8: {}", pdf_path.display()))
-            .to_string()
+        pdf_str(&pdf_path)
     }
 }
+
+fn pdf_str(pdf_path: &Path) -> Result<String> {
+    pdf_path.to_str().map(str::to_string).ok_or_else(|| {
+        Error::Process(format!(
+            "PDF path contains invalid UTF-8: {}",
+            pdf_path.display()
+        ))
+    })
+}
+
 impl Process for ImageConverter<'_> {
-    fn process(&mut self, exp: crate::syntax::Tree) -> crate::syntax::Tree {
+    fn process(&mut self, exp: Tree) -> Result<Tree> {
         m_trc!("{:?}", self);
-        self.walk(exp)
+        let tree = self.fold(exp);
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(tree),
+        }
+    }
+}
+
+/// makes the relative image paths in `tree` absolute, resolving them against `dir`.
+/// used for documents that are joined to the source file, but live in another
+/// directory.
+pub fn rebase(tree: Tree, dir: &Path) -> Tree {
+    Rebase { dir }.fold(tree)
+}
+
+struct Rebase<'a> {
+    dir: &'a Path,
+}
+
+impl Fold for Rebase<'_> {
+    fn fold(&mut self, tree: Tree) -> Tree {
+        match tree {
+            Tree::Image(caption, path, size_spec, span) => {
+                let path = match *path {
+                    Tree::Literal(p, path_span) if !p.starts_with('/') => {
+                        let joined = self.dir.join(&p);
+                        let absolute = std::path::absolute(&joined).unwrap_or(joined);
+                        lit(&absolute.to_string_lossy()).with_span(path_span)
+                    }
+                    path => path,
+                };
+                Tree::Image(caption, Box::new(path), size_spec, span)
+            }
+            _ => fold_children(self, tree),
+        }
     }
 }
 
 pub fn new<'a>(c: &'a Config) -> Box<dyn Process + 'a> {
-    Box::new(ImageConverter { config: c })
+    Box::new(ImageConverter {
+        config: c,
+        error: None,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::Config;
-    use crate::syntax::{image, image_size, lit, DocType};
+    use crate::syntax::{
+        bold, footnote, image, image_size, list, list_item, lit, DocType, ListKind, Span,
+    };
     use crate::Tree;
     use crate::Process;
 
@@ -113,8 +171,11 @@ mod tests {
     }
 
     fn run(config: &Config, exp: Tree) -> Tree {
-        let mut conv = ImageConverter { config };
-        conv.process(exp)
+        let mut conv = ImageConverter {
+            config,
+            error: None,
+        };
+        conv.process(exp).unwrap()
     }
 
     // --- Path resolution ---
@@ -125,6 +186,7 @@ mod tests {
         let input = Tree::Document(
             DocType::DEFAULT,
             Box::new(image(lit("alt"), lit("/images/pic.png"), image_size(lit("100"), lit("100")))),
+            Span::default(),
         );
         let result = run(&config, input);
         // The absolute path should pass through unchanged
@@ -137,6 +199,7 @@ mod tests {
         let input = Tree::Document(
             DocType::DEFAULT,
             Box::new(image(lit("alt"), lit("pic.png"), image_size(lit("100"), lit("100")))),
+            Span::default(),
         );
         let result = run(&config, input);
         // Relative path should be prefixed with the source file's parent directory
@@ -146,18 +209,55 @@ mod tests {
     #[test]
     fn non_image_nodes_are_passed_through() {
         let config = make_config("/docs/file.md");
-        let input = Tree::Document(DocType::DEFAULT, Box::new(lit("hello")));
+        let input = Tree::Document(DocType::DEFAULT, Box::new(lit("hello")), Span::default());
         let result = run(&config, input);
         assert_eq!(format!("{result:?}"), "Document(DEFAULT, Literal(\"hello\"))");
     }
 
     #[test]
-    fn cat_is_walked_recursively() {
+    fn sequence_is_walked() {
         let config = make_config("/docs/file.md");
         let inner = image(lit("alt"), lit("pic.png"), image_size(lit("100"), lit("100")));
-        let input = Tree::Document(DocType::DEFAULT, Box::new(lit("text").cat(inner)));
+        let input = Tree::Document(
+            DocType::DEFAULT,
+            Box::new(lit("text").cat(inner)),
+            Span::default(),
+        );
         let result = run(&config, input);
-        // The relative path inside Cat should still be resolved
+        // The relative path inside the sequence should still be resolved
         assert!(format!("{result:?}").contains("/docs/pic.png"));
     }
+
+    #[test]
+    fn rebased_paths_are_not_resolved_again() {
+        let config = make_config("/docs/file.md");
+        let input = Tree::Document(
+            DocType::DEFAULT,
+            Box::new(
+                image(lit("alt"), lit("pic.png"), image_size(lit("100"), lit("100")))
+                    .cat(image(lit("alt"), lit("/abs.png"), image_size(lit("1"), lit("1")))),
+            ),
+            Span::default(),
+        );
+        let result = format!("{:?}", run(&config, rebase(input, Path::new("/chapters"))));
+        assert!(result.contains("\"/chapters/pic.png\""));
+        assert!(result.contains("\"/abs.png\""));
+    }
+
+    #[test]
+    fn images_in_nested_nodes_are_resolved() {
+        let config = make_config("/docs/file.md");
+        let pic = || image(lit("alt"), lit("pic.png"), image_size(lit("100"), lit("100")));
+        let input = Tree::Document(
+            DocType::DEFAULT,
+            Box::new(
+                list(list_item(pic(), 0), 0, ListKind::Bullet('*'))
+                    .cat(footnote(pic()))
+                    .cat(bold(pic())),
+            ),
+            Span::default(),
+        );
+        let result = format!("{:?}", run(&config, input));
+        assert_eq!(result.matches("/docs/pic.png").count(), 3);
+    }
 }
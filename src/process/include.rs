@@ -0,0 +1,324 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::process::image_converter;
+use crate::syntax::{block, fold_children, visit_children, Fold, Tree, Visit};
+use crate::{m_dbg, m_trc, Error, Process, Result};
+
+/// Include processor replaces `//include file.md` directives by the named file,
+/// parsed and with the includes in it resolved in turn. Relative paths are
+/// resolved against the directory of the including file. A part of the file
+/// can be selected with a line range, `file.md:10-20`, or with the title or
+/// anchor name of a heading, `file.md#Intro`, which selects the heading and
+/// everything below it up to the next heading of the same or a higher level.
+#[derive(Debug)]
+pub struct IncludeProcessor<'a> {
+    config: &'a Config,
+    /// the files being included, outermost first, to detect cycles
+    including: Vec<PathBuf>,
+    /// the first error met, the remaining includes are left as they are
+    error: Option<Error>,
+}
+
+/// the part of a file to be included
+#[derive(Debug, PartialEq)]
+enum Part {
+    All,
+    /// the lines from the first up to and including the second, counting from 1
+    Lines(usize, usize),
+    /// the section starting with the heading of this title or anchor name
+    Section(String),
+}
+
+impl Fold for IncludeProcessor<'_> {
+    fn fold(&mut self, tree: Tree) -> Tree {
+        match tree {
+            Tree::Include(spec, span) if self.error.is_none() => match self.include(&spec) {
+                Ok(mut included) => {
+                    // positions in the included file mean nothing in the including one
+                    included.map_spans(&mut |s| *s = span);
+                    included
+                }
+                Err(e) => {
+                    self.error = Some(e);
+                    Tree::Include(spec, span)
+                }
+            },
+            _ => fold_children(self, tree),
+        }
+    }
+}
+
+impl IncludeProcessor<'_> {
+    /// parses the file selected by `spec` and resolves the includes in it
+    fn include(&mut self, spec: &str) -> Result<Tree> {
+        let (path, part) = parse_spec(spec)?;
+        let path = crate::parent_dir(&self.config.source_file).join(path);
+        m_dbg!("including: {}", path.display());
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| Error::io(format!("cannot read '{}'", path.display()), e))?;
+        if self.including.contains(&canonical) {
+            let cycle: Vec<String> = self
+                .including
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(Error::Process(format!(
+                "include cycle: {}",
+                cycle.join(" -> ")
+            )));
+        }
+
+        let file_name = path.to_string_lossy();
+        let mut input = crate::read_input(&file_name)?;
+        if let Part::Lines(from, to) = part {
+            input = lines(&input, from, to);
+        }
+        let dir = crate::parent_dir(&file_name);
+        let tree = rebase(image_converter::rebase(crate::parse(&file_name, &input), dir), dir);
+        let mut children = contents(tree);
+        if let Part::Section(name) = &part {
+            children = section(children, name).ok_or_else(|| {
+                Error::Process(format!("no heading '{name}' in '{}'", path.display()))
+            })?;
+        }
+        // the line break ending the include directive takes the place of the last one
+        if matches!(children.last(), Some(Tree::LineBreak(_))) {
+            children.pop();
+        }
+
+        self.including.push(canonical);
+        let included = self.fold(block(children));
+        self.including.pop();
+        Ok(included)
+    }
+}
+
+/// splits an include spec into the path and the part of the file to be included
+fn parse_spec(spec: &str) -> Result<(&str, Part)> {
+    if let Some((path, heading)) = spec.split_once('#') {
+        return Ok((path, Part::Section(heading.to_string())));
+    }
+    match spec.rsplit_once(':') {
+        Some((path, range))
+            if !range.is_empty() && range.bytes().all(|c| c.is_ascii_digit() || c == b'-') =>
+        {
+            let invalid = || Error::Process(format!("invalid line range in include: {spec}"));
+            let (from, to) = range.split_once('-').unwrap_or((range, range));
+            let from = match from {
+                "" => 1,
+                from => from.parse().map_err(|_| invalid())?,
+            };
+            let to = match to {
+                "" => usize::MAX,
+                to => to.parse().map_err(|_| invalid())?,
+            };
+            if from == 0 || from > to {
+                return Err(invalid());
+            }
+            Ok((path, Part::Lines(from, to)))
+        }
+        _ => Ok((spec, Part::All)),
+    }
+}
+
+/// the lines `from` up to and including `to` of `input`, counting from 1
+fn lines(input: &str, from: usize, to: usize) -> String {
+    input
+        .lines()
+        .skip(from - 1)
+        .take(to - from + 1)
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+/// the top level nodes of a parsed document, without its meta data, which is
+/// left to the including document
+fn contents(tree: Tree) -> Vec<Tree> {
+    let content = match tree {
+        Tree::Document(_, content, _) => *content,
+        tree => tree,
+    };
+    let children = match content {
+        Tree::Block(children, _) => children,
+        content => vec![content],
+    };
+    children
+        .into_iter()
+        .filter(|child| !matches!(child, Tree::MetaDataBlock(..)))
+        .collect()
+}
+
+/// the heading named `name` and the nodes below it, up to the next heading of
+/// the same or a higher level
+fn section(children: Vec<Tree>, name: &str) -> Option<Vec<Tree>> {
+    let (start, level) = children.iter().enumerate().find_map(|(i, child)| match child {
+        Tree::Heading(title, level, anchor, _) if anchor == name || text(title) == name => {
+            Some((i, *level))
+        }
+        _ => None,
+    })?;
+    let end = children[start + 1..]
+        .iter()
+        .position(|child| matches!(child, Tree::Heading(_, l, _, _) if *l <= level))
+        .map_or(children.len(), |i| start + 1 + i);
+    Some(children.into_iter().take(end).skip(start).collect())
+}
+
+/// the plain text of a heading title
+fn text(tree: &Tree) -> String {
+    #[derive(Default)]
+    struct Text(String);
+
+    impl Visit for Text {
+        fn visit(&mut self, tree: &Tree) {
+            match tree {
+                Tree::Literal(s, _) | Tree::EscapeLit(s, _) => self.0.push_str(s),
+                Tree::Escaped(c, _) => self.0.push(*c),
+                _ => visit_children(self, tree),
+            }
+        }
+    }
+
+    let mut text = Text::default();
+    text.visit(tree);
+    text.0.trim().to_string()
+}
+
+/// makes the relative paths of the includes in `tree` absolute, resolving them
+/// against `dir`. used for documents that live in another directory than the
+/// source file.
+pub fn rebase(tree: Tree, dir: &Path) -> Tree {
+    Rebase { dir }.fold(tree)
+}
+
+struct Rebase<'a> {
+    dir: &'a Path,
+}
+
+impl Fold for Rebase<'_> {
+    fn fold(&mut self, tree: Tree) -> Tree {
+        match tree {
+            Tree::Include(spec, span) if !spec.starts_with('/') => {
+                let joined = self.dir.join(&spec);
+                let absolute = std::path::absolute(&joined).unwrap_or(joined);
+                Tree::Include(absolute.to_string_lossy().to_string(), span)
+            }
+            _ => fold_children(self, tree),
+        }
+    }
+}
+
+impl Process for IncludeProcessor<'_> {
+    fn process(&mut self, exp: Tree) -> Result<Tree> {
+        m_trc!("{:?}", self);
+        // a file including the source file is a cycle, too
+        self.including = Path::new(&self.config.source_file)
+            .canonicalize()
+            .into_iter()
+            .collect();
+        let tree = self.fold(exp);
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(tree),
+        }
+    }
+}
+
+pub fn new<'a>(c: &'a Config) -> Box<dyn Process + 'a> {
+    Box::new(IncludeProcessor {
+        config: c,
+        including: Vec::new(),
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// writes `files` into a fresh directory below the temp dir and returns it
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mato_include_{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        for (file, content) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn run(dir: &Path, file: &str) -> Result<String> {
+        let mut config = Config::default();
+        config.source_file = dir.join(file).to_string_lossy().to_string();
+        let input = fs::read_to_string(&config.source_file).unwrap();
+        let tree = crate::parse(&config.source_file, &input);
+        let result = new(&config).process(tree);
+        let _ = fs::remove_dir_all(dir);
+        result.map(|tree| format!("{tree:?}"))
+    }
+
+    #[test]
+    fn specs_select_parts() {
+        assert_eq!(parse_spec("a.md").unwrap(), ("a.md", Part::All));
+        assert_eq!(parse_spec("a.md:3-5").unwrap(), ("a.md", Part::Lines(3, 5)));
+        assert_eq!(parse_spec("a.md:3").unwrap(), ("a.md", Part::Lines(3, 3)));
+        assert_eq!(parse_spec("a.md:-5").unwrap(), ("a.md", Part::Lines(1, 5)));
+        assert_eq!(
+            parse_spec("a.md#The End").unwrap(),
+            ("a.md", Part::Section("The End".to_string()))
+        );
+        assert!(parse_spec("a.md:5-3").is_err());
+    }
+
+    #[test]
+    fn included_files_are_resolved_relative_to_the_including_file() {
+        let dir = write_files(
+            "relative",
+            &[
+                ("main.md", "a\n//include parts/one.md\nb\n"),
+                ("parts/one.md", "---\ntitle: One\n---\none\n//include two.md\n"),
+                ("parts/two.md", "two\n"),
+            ],
+        );
+        assert_eq!(
+            run(&dir, "main.md").unwrap(),
+            "Document(DEFAULT, Block([Literal(\"a\"), LineBreak, \
+             Block([Literal(\"one\"), LineBreak, Block([Literal(\"two\")])]), \
+             LineBreak, Literal(\"b\"), LineBreak]))"
+        );
+    }
+
+    #[test]
+    fn parts_are_selected() {
+        let dir = write_files(
+            "parts",
+            &[
+                ("main.md", "//include ch.md:2-3\n//include ch.md#Two\n"),
+                ("ch.md", "# One\nfirst\nsecond\n# Two\n## Deeper\nthird\n# Three\n"),
+            ],
+        );
+        let result = run(&dir, "main.md").unwrap();
+        assert!(result.contains("\"first\""));
+        assert!(result.contains("\"second\""));
+        assert!(result.contains("\"Deeper\""));
+        assert!(result.contains("\"third\""));
+        assert!(!result.contains("\"One\""));
+        assert!(!result.contains("\"Three\""));
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let dir = write_files(
+            "cycle",
+            &[("main.md", "//include a.md\n"), ("a.md", "//include main.md\n")],
+        );
+        let error = run(&dir, "main.md").unwrap_err().to_string();
+        assert!(error.starts_with("include cycle: "), "{error}");
+        assert!(error.ends_with("main.md"), "{error}");
+    }
+}
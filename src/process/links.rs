@@ -0,0 +1,202 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::Diagnostic;
+use crate::syntax::{empty, lit, Span, Tree};
+use crate::{m_trc, Process, Result};
+
+/// Links processor resolves reference links: the definitions `[label]: url` are taken
+/// out of the tree and each reference link `[text][label]`, `[label][]` or `[label]` is
+/// replaced by a link to the url of its definition. links have no title, the title of a
+/// definition, `[label]: url "title"`, is reported and left out.
+#[derive(Debug, Default)]
+pub struct LinkResolver {
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// labels match case-insensitively and regardless of how they are broken across lines
+fn normalize(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl LinkResolver {
+    /// takes the link definitions out of `tree`, leaving empty nodes behind
+    fn collect_definitions(
+        &mut self,
+        tree: &mut Tree,
+        definitions: &mut HashMap<String, (Tree, Span)>,
+    ) {
+        if let Tree::LinkDef(..) = tree {
+            let Tree::LinkDef(label, url, title, span) = std::mem::replace(tree, empty()) else {
+                unreachable!()
+            };
+            if !title.is_empty() {
+                self.diagnostics.push(Diagnostic::warning(
+                    &format!("link [{label}] has a title, which is not supported and left out"),
+                    span,
+                ));
+            }
+            match definitions.entry(normalize(&label)) {
+                Entry::Occupied(_) => self.diagnostics.push(Diagnostic::warning(
+                    &format!(
+                        "link [{label}] is defined more than once, using the first definition"
+                    ),
+                    span,
+                )),
+                Entry::Vacant(entry) => {
+                    entry.insert((*url, span));
+                }
+            }
+            return;
+        }
+        for child in tree.children_mut() {
+            self.collect_definitions(child, definitions);
+        }
+    }
+
+    /// replaces the reference links in `tree` with links, remembering the labels used
+    fn resolve(
+        &mut self,
+        tree: &mut Tree,
+        definitions: &HashMap<String, (Tree, Span)>,
+        used: &mut HashSet<String>,
+    ) {
+        for child in tree.children_mut() {
+            self.resolve(child, definitions, used);
+        }
+        if let Tree::LinkRef(..) = tree {
+            let Tree::LinkRef(label, text, suffix, span) = std::mem::replace(tree, empty()) else {
+                unreachable!()
+            };
+            let key = normalize(&label);
+            *tree = match definitions.get(&key) {
+                Some((url, _)) => {
+                    used.insert(key);
+                    match url {
+                        // like inline links, urls starting with # point into the document
+                        Tree::Literal(target, _) if target.starts_with('#') => {
+                            Tree::DocRef(target[1..].to_string(), text, span)
+                        }
+                        _ => Tree::HyperRef(text, Box::new(url.clone()), span),
+                    }
+                }
+                None => {
+                    // a shortcut `[label]` without definition is just text in brackets
+                    if !suffix.trim().is_empty() {
+                        self.diagnostics.push(Diagnostic::warning(
+                            &format!("undefined link [{label}]"),
+                            span,
+                        ));
+                    }
+                    lit("[")
+                        .cat(*text)
+                        .cat(lit(&format!("]{suffix}")))
+                        .with_span(span)
+                }
+            };
+        }
+    }
+}
+
+impl Process for LinkResolver {
+    fn process(&mut self, mut exp: Tree) -> Result<Tree> {
+        m_trc!("{:?}", self);
+        let mut definitions = HashMap::new();
+        self.collect_definitions(&mut exp, &mut definitions);
+        let mut used = HashSet::new();
+        self.resolve(&mut exp, &definitions, &mut used);
+        let mut unused: Vec<(&String, &Span)> = definitions
+            .iter()
+            .filter(|(label, _)| !used.contains(*label))
+            .map(|(label, (_, span))| (label, span))
+            .collect();
+        unused.sort_by_key(|(_, span)| span.start);
+        for (label, span) in unused {
+            self.diagnostics.push(Diagnostic::warning(
+                &format!("link [{label}] is never referenced"),
+                *span,
+            ));
+        }
+        Ok(exp)
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+pub fn new() -> Box<dyn Process> {
+    Box::new(LinkResolver::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn resolve(input: &str) -> (String, Vec<String>) {
+        let mut resolver = LinkResolver::default();
+        let (tree, _) = Parser::parse(input);
+        let tree = resolver.process(tree).unwrap();
+        let diagnostics = resolver
+            .take_diagnostics()
+            .iter()
+            .map(|d| format!("{d}"))
+            .collect();
+        (format!("{tree:?}"), diagnostics)
+    }
+
+    #[test]
+    fn full_reference_is_resolved() {
+        let (tree, diagnostics) = resolve("[text][ref]\n\n[ref]: http://example.com \"Title\"\n");
+        assert!(tree.contains("HyperRef(Literal(\"text\"), Literal(\"http://example.com\"))"));
+        assert_eq!(
+            diagnostics,
+            ["3:1: warning: link [ref] has a title, which is not supported and left out"]
+        );
+    }
+
+    #[test]
+    fn collapsed_and_shortcut_references_are_resolved() {
+        let (tree, _) = resolve("[Mato][] and [mato]\n[MATO]: http://mato.org\n");
+        assert_eq!(
+            tree.matches("HyperRef(Literal(\"Mato\"), Literal(\"http://mato.org\"))")
+                .count(),
+            1
+        );
+        assert!(tree.contains("HyperRef(Literal(\"mato\"), Literal(\"http://mato.org\"))"));
+    }
+
+    #[test]
+    fn anchor_definition_becomes_internal_link() {
+        let (tree, _) = resolve("[see][intro]\n[intro]: #introduction\n");
+        assert!(tree.contains("DocRef(\"introduction\", Literal(\"see\"))"));
+    }
+
+    #[test]
+    fn undefined_shortcut_stays_text() {
+        let (tree, diagnostics) = resolve("a [x] b");
+        assert_eq!(
+            tree,
+            "Document(DEFAULT, Block([Literal(\"a \"), Inline([Literal(\"[\"), Literal(\"x\"), Literal(\"] \")]), Literal(\"b\")]))"
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn undefined_unused_and_duplicate_definitions_are_reported() {
+        let (_, diagnostics) = resolve("[a][b]\n[c]: one\n[c]: two\n");
+        assert_eq!(
+            diagnostics,
+            [
+                "3:1: warning: link [c] is defined more than once, using the first definition",
+                "1:1: warning: undefined link [b]",
+                "2:1: warning: link [c] is never referenced"
+            ]
+        );
+    }
+}
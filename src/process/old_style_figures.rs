@@ -0,0 +1,117 @@
+use crate::syntax::{fold_children, lit, Fold, Tree};
+use crate::{m_trc, Process, Result};
+
+/// OldStyleFigures processor replaces the digits of the text body with groff's
+/// old style figures. figures in headings, list items and tables are meant to line
+/// up, so they are left as they are, as are the ones in code, links and images.
+#[derive(Debug, Default)]
+pub struct OldStyleFigures {}
+
+impl Fold for OldStyleFigures {
+    fn fold(&mut self, tree: Tree) -> Tree {
+        match tree {
+            Tree::Literal(s, span) => lit(&replace_old_style_figures(s)).with_span(span),
+            // the code block type is a name, not text
+            Tree::CodeBlock(b1, b2, span) => Tree::CodeBlock(b1, Box::new(self.fold(*b2)), span),
+            Tree::ListItem(..)
+            | Tree::Table(..)
+            | Tree::Heading(..)
+            | Tree::SmallCaps(..)
+            | Tree::Color(..)
+            | Tree::InlineCode(..)
+            | Tree::Quote(..)
+            | Tree::RightSidenote(..)
+            | Tree::HyperRef(..)
+            | Tree::DocRef(..)
+            | Tree::ImageSizeSpec(..)
+            | Tree::Image(..)
+            | Tree::FootnoteDef(..)
+            | Tree::LinkRef(..)
+            | Tree::LinkDef(..) => tree,
+            _ => fold_children(self, tree),
+        }
+    }
+}
+
+/// replaces 0-9 with old style figure references
+fn replace_old_style_figures(s: String) -> String {
+    let mut result = String::new();
+    for c in s.chars() {
+        match c {
+            '0' => result.push_str("\\[zero.oldstyle]"),
+            '1' => result.push_str("\\[one.oldstyle]"),
+            '2' => result.push_str("\\[two.oldstyle]"),
+            '3' => result.push_str("\\[three.oldstyle]"),
+            '4' => result.push_str("\\[four.oldstyle]"),
+            '5' => result.push_str("\\[five.oldstyle]"),
+            '6' => result.push_str("\\[six.oldstyle]"),
+            '7' => result.push_str("\\[seven.oldstyle]"),
+            '8' => result.push_str("\\[eight.oldstyle]"),
+            '9' => result.push_str("\\[nine.oldstyle]"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+impl Process for OldStyleFigures {
+    fn process(&mut self, exp: Tree) -> Result<Tree> {
+        m_trc!("{:?}", self);
+        Ok(self.fold(exp))
+    }
+}
+
+pub fn new() -> Box<dyn Process> {
+    Box::new(OldStyleFigures {})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::{heading, Span};
+
+    fn figures(exp: Tree) -> Tree {
+        OldStyleFigures {}.process(exp).unwrap()
+    }
+
+    #[test]
+    fn replaces_digits_with_oldstyle() {
+        let input = Tree::Document(
+            crate::syntax::DocType::DEFAULT,
+            Box::new(lit("abc 123 def")),
+            Span::default(),
+        );
+        let result = figures(input);
+        assert_eq!(
+            format!("{result:?}"),
+            "Document(DEFAULT, Literal(\"abc \\\\[one.oldstyle]\\\\[two.oldstyle]\\\\[three.oldstyle] def\"))"
+        );
+    }
+
+    #[test]
+    fn numerals_not_replaced_inside_list_items() {
+        let item_content = lit("item 42");
+        let input = Tree::Document(
+            crate::syntax::DocType::DEFAULT,
+            Box::new(Tree::List(
+                Box::new(Tree::ListItem(Box::new(item_content), 0, Span::default())),
+                0,
+                crate::syntax::ListKind::Bullet('*'),
+                Span::default(),
+            )),
+            Span::default(),
+        );
+        let result = figures(input);
+        // The "42" inside the list item should not be replaced
+        assert!(format!("{result:?}").contains("\"item 42\""));
+    }
+
+    #[test]
+    fn numerals_not_replaced_in_headings() {
+        let input = heading(lit("Part 2"), 1, "").cat(lit(" 2"));
+        assert_eq!(
+            format!("{:?}", figures(input)),
+            "Inline([Heading(Literal(\"Part 2\"), 1, \"\"), Literal(\" \\\\[two.oldstyle]\")])"
+        );
+    }
+}
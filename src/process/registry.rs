@@ -0,0 +1,198 @@
+//! processors known by name, so that processing chains can be put together on the
+//! command line
+use crate::config::Config;
+use crate::process::chain::{self, Chain};
+use crate::process::{
+    canonicalize, code_block, external, footnotes, identity, image_converter, include, links,
+    old_style_figures,
+};
+use crate::Process;
+
+/// a processor that can be named on the command line
+pub struct Entry {
+    pub name: &'static str,
+    pub description: &'static str,
+    create: for<'a> fn(&'a Config) -> Box<dyn Process + 'a>,
+}
+
+/// all processors there are, in the order they are usually run in
+pub const PROCESSORS: &[Entry] = &[
+    Entry {
+        name: "identity",
+        description: "does nothing",
+        create: |_| identity::new(),
+    },
+    Entry {
+        name: "include",
+        description: "replaces //include directives by the files they name",
+        create: include::new,
+    },
+    Entry {
+        name: "footnotes",
+        description: "resolves footnote references",
+        create: |_| footnotes::new(),
+    },
+    Entry {
+        name: "links",
+        description: "resolves reference links",
+        create: |_| links::new(),
+    },
+    Entry {
+        name: "external",
+        description: "pipes the tree through external filters",
+        create: |config| external::new(config.filters.clone(), config.document_filters),
+    },
+    Entry {
+        name: "canonicalize",
+        description: "removes empty nodes and merges bold and italics",
+        create: |_| canonicalize::new(),
+    },
+    Entry {
+        name: "old-style-figures",
+        description: "replaces digits in the text body with old style figures",
+        create: |_| old_style_figures::new(),
+    },
+    Entry {
+        name: "image-converter",
+        description: "resolves image paths and converts SVG images to PDF",
+        create: image_converter::new,
+    },
+    Entry {
+        name: "code-block",
+        description: "renders pic code blocks",
+        create: |_| code_block::new(),
+    },
+];
+
+/// the processors run for groff output, unless told otherwise
+pub const DEFAULT_PROCESSORS: &[&str] = &[
+    "include",
+    "footnotes",
+    "links",
+    "external",
+    "canonicalize",
+    "old-style-figures",
+    "image-converter",
+    "code-block",
+];
+
+fn find(name: &str) -> Result<&'static Entry, String> {
+    PROCESSORS
+        .iter()
+        .find(|entry| entry.name == name)
+        .ok_or_else(|| format!("unknown processor: {name}"))
+}
+
+/// determines the processors to run. `process` is a comma separated list of processors
+/// replacing `defaults`, in which `default` stands for all of `defaults`. the processors
+/// in the comma separated list `no_process` are left out.
+pub fn resolve(defaults: &[&str], process: &str, no_process: &str) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    if process.trim().is_empty() {
+        names.extend(defaults.iter().map(|name| name.to_string()));
+    }
+    for name in process.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if name == "default" {
+            names.extend(defaults.iter().map(|name| name.to_string()));
+        } else {
+            names.push(find(name)?.name.to_string());
+        }
+    }
+    for name in no_process
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+    {
+        find(name)?;
+        names.retain(|n| n != name);
+    }
+    Ok(names)
+}
+
+/// chains the named processors in the given order
+pub fn create_chain<'a>(names: &[String], config: &'a Config) -> Result<Chain<'a>, String> {
+    let mut processors = names
+        .iter()
+        .map(|name| find(name).map(|entry| (entry.create)(config)))
+        .collect::<Result<Vec<_>, _>>()?;
+    // a chain has two ends at least
+    while processors.len() < 2 {
+        processors.push(identity::new());
+    }
+    let mut processors = processors.into_iter();
+    let first = processors.next().unwrap();
+    let second = processors.next().unwrap();
+    Ok(processors.fold(chain::new(first, second), Chain::append))
+}
+
+/// lists the processors with their descriptions, one per line
+pub fn describe() -> String {
+    PROCESSORS
+        .iter()
+        .map(|entry| {
+            let default = if DEFAULT_PROCESSORS.contains(&entry.name) {
+                " (default)"
+            } else {
+                ""
+            };
+            format!("{:<20}{}{default}\n", entry.name, entry.description)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_used_unless_told_otherwise() {
+        assert_eq!(
+            resolve(&["links", "footnotes"], "", ""),
+            Ok(vec!["links".into(), "footnotes".into()])
+        );
+    }
+
+    #[test]
+    fn processors_are_added_removed_and_reordered() {
+        let defaults = ["footnotes", "links", "canonicalize"];
+        assert_eq!(
+            resolve(&defaults, "default, identity", "links"),
+            Ok(vec![
+                "footnotes".into(),
+                "canonicalize".into(),
+                "identity".into()
+            ])
+        );
+        assert_eq!(
+            resolve(&defaults, "links,footnotes", ""),
+            Ok(vec!["links".into(), "footnotes".into()])
+        );
+    }
+
+    #[test]
+    fn unknown_processors_are_rejected() {
+        assert_eq!(
+            resolve(&[], "frobnicate", ""),
+            Err("unknown processor: frobnicate".into())
+        );
+        assert_eq!(
+            resolve(&[], "", "frobnicate"),
+            Err("unknown processor: frobnicate".into())
+        );
+    }
+
+    #[test]
+    fn chains_are_created_for_any_number_of_processors() {
+        let config = Config::default();
+        for count in 0..4 {
+            let names: Vec<String> = DEFAULT_PROCESSORS[..count]
+                .iter()
+                .map(|n| n.to_string())
+                .collect();
+            assert!(create_chain(&names, &config).is_ok());
+        }
+        assert!(
+            format!("{:?}", create_chain(&["links".into()], &config).unwrap()).contains("Identity")
+        );
+    }
+}
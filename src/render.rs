@@ -1,3 +1,8 @@
 //! components related to rendering a syntax tree to a concrete target language, like groff or latex
+pub mod device;
+pub mod epub;
 pub mod groff;
+pub mod html;
+pub mod json;
+pub mod latex;
 pub mod markdown;
\ No newline at end of file
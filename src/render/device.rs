@@ -0,0 +1,146 @@
+//! output devices known by name, selected with `-T` like in groff
+use crate::config::Config;
+use crate::process::registry::DEFAULT_PROCESSORS;
+use crate::render::{groff, html, json, latex, markdown};
+use crate::{Error, Render, Result};
+
+/// a device, with the renderer and the processors it needs
+pub struct Device {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// processors run for this device, unless told otherwise
+    pub processors: &'static [&'static str],
+    /// writes a binary file instead of text
+    pub binary: bool,
+    create: for<'a> fn(&'a Config) -> Result<Box<dyn Render + 'a>>,
+}
+
+impl Device {
+    /// creates the renderer of this device
+    pub fn renderer<'a>(&self, config: &'a Config) -> Result<Box<dyn Render + 'a>> {
+        (self.create)(config)
+    }
+}
+
+/// all devices there are
+pub const DEVICES: &[Device] = &[
+    Device {
+        name: "pdf",
+        description: "PDF, typeset by groff using mom",
+        processors: DEFAULT_PROCESSORS,
+        binary: true,
+        create: |config| Ok(Box::new(groff::mom::new(config)?)),
+    },
+    Device {
+        name: "mom",
+        description: "groff source using the mom macros",
+        processors: DEFAULT_PROCESSORS,
+        binary: false,
+        create: |config| Ok(Box::new(groff::mom::new(config)?)),
+    },
+    Device {
+        // small caps and old style figures of mom's fonts do not exist on a terminal,
+        // nor do PDF images
+        name: "man",
+        description: "groff source using the man macros",
+        processors: &["include", "footnotes", "links", "external"],
+        binary: false,
+        create: |_| Ok(Box::new(groff::man::new())),
+    },
+    Device {
+        // the renderer escapes text itself, canonicalized text would be escaped twice
+        name: "mdoc",
+        description: "groff source using the mdoc macros",
+        processors: &["include", "footnotes", "links", "external"],
+        binary: false,
+        create: |_| Ok(Box::new(groff::mandoc::new())),
+    },
+    Device {
+        // groff escapes and PDF conversions are of no use in a browser, so small caps
+        // and old style figures are left to the style sheet and images to the browser
+        name: "html",
+        description: "HTML5 page",
+        processors: &["include", "footnotes", "links", "external"],
+        binary: false,
+        create: |config| Ok(Box::new(html::new(config)?)),
+    },
+    Device {
+        // the pages of the book are rendered like for html, `epub::package` puts them
+        // together
+        name: "epub",
+        description: "EPUB 3 e-book",
+        processors: &["include", "footnotes", "links", "external"],
+        binary: true,
+        create: |config| Ok(Box::new(html::new(config)?)),
+    },
+    Device {
+        // LaTeX has small caps, old style figures and images of its own
+        name: "latex",
+        description: "LaTeX source",
+        processors: &["include", "footnotes", "links", "external"],
+        binary: false,
+        create: |config| Ok(Box::new(latex::new(config)?)),
+    },
+    Device {
+        // formatting stays close to the source: includes are inlined and filters run,
+        // references are kept as written
+        name: "md",
+        description: "formatted markdown",
+        processors: &["include", "external"],
+        binary: false,
+        create: |_| Ok(Box::new(markdown::Renderer::new())),
+    },
+    Device {
+        // references are resolved, but nothing is prepared for groff
+        name: "json",
+        description: "the syntax tree as JSON",
+        processors: &["include", "footnotes", "links", "external"],
+        binary: false,
+        create: |_| Ok(Box::new(json::new())),
+    },
+];
+
+/// looks up the device named `name`
+pub fn find(name: &str) -> Result<&'static Device> {
+    DEVICES
+        .iter()
+        .find(|device| device.name == name)
+        .ok_or_else(|| Error::Config(format!("unknown device: {name}")))
+}
+
+/// lists the devices with their descriptions, one per line
+pub fn describe() -> String {
+    DEVICES
+        .iter()
+        .map(|device| format!("{:<20}{}\n", device.name, device.description))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn devices_are_found_by_name() {
+        assert_eq!(find("mdoc").unwrap().name, "mdoc");
+        assert_eq!(
+            find("dvi").err().map(|e| e.to_string()),
+            Some("unknown device: dvi".to_string())
+        );
+    }
+
+    #[test]
+    fn device_processors_are_known() {
+        for device in DEVICES {
+            for name in device.processors {
+                assert!(
+                    crate::process::registry::PROCESSORS
+                        .iter()
+                        .any(|entry| entry.name == *name),
+                    "{}: {name}",
+                    device.name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,481 @@
+//! packages the html rendering of a document as an EPUB 3 book
+//!
+//! every top-level heading, or a chapter mark not directly following one,
+//! starts a chapter, which becomes a page of its own in the book. title, author
+//! and language come from the meta data block, local images are copied into the
+//! book.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::render::html::{self, escape};
+use crate::syntax::{block, fold_children, lit, visit_children, DocType, Fold, Tree, Visit};
+use crate::{m_dbg, parent_dir, Error, Result};
+
+mod zip;
+
+/// the image formats EPUB readers must support, with their media types
+const IMAGE_TYPES: &[(&str, &str)] = &[
+    ("gif", "image/gif"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("png", "image/png"),
+    ("svg", "image/svg+xml"),
+    ("webp", "image/webp"),
+];
+
+#[derive(Debug, Default)]
+struct Chapter {
+    /// the text of the top-level heading, if there is one
+    title: Option<String>,
+    nodes: Vec<Tree>,
+}
+
+impl Chapter {
+    fn has_content(&self) -> bool {
+        self.nodes.iter().any(|node| !is_blank(node))
+    }
+}
+
+/// renders `tree` as an EPUB file
+pub fn package(config: &Config, tree: Tree) -> Result<Vec<u8>> {
+    let (doc_type, content) = match tree {
+        Tree::Document(doc_type, content, _) => (doc_type, *content),
+        tree => (DocType::DEFAULT, tree),
+    };
+    let meta_data = html::meta_data(&content);
+    let title = match html::lookup(&meta_data, "title") {
+        Some(title) => title.to_string(),
+        None => file_stem(&config.source_file),
+    };
+    let language = html::lookup(&meta_data, "lang")
+        .map(str::to_string)
+        .unwrap_or_else(|| match html::language_tag(&config.lang) {
+            // undetermined, the language is required
+            "" => "und".to_string(),
+            tag => tag.to_string(),
+        });
+
+    let mut images = Images {
+        dir: parent_dir(&config.source_file).to_path_buf(),
+        files: Vec::new(),
+        error: None,
+    };
+    let content = images.fold(content);
+    if let Some(e) = images.error {
+        return Err(e);
+    }
+
+    let chapters = chapters(content);
+    let mut anchors = HashMap::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        for name in anchor_names(&chapter.nodes) {
+            anchors.entry(name).or_insert_with(|| page_name(i));
+        }
+    }
+
+    let default_style = format!("{}{}", html::DEFAULT_STYLE, include_str!("epub-style.css"));
+    let mut renderer = html::with_default_style(config, &default_style)?;
+    let mut pages = Vec::new();
+    let mut toc = Vec::new();
+    for (i, chapter) in chapters.into_iter().enumerate() {
+        let page_title = chapter.title.unwrap_or_else(|| {
+            if i == 0 {
+                title.clone()
+            } else {
+                format!("{}", i + 1)
+            }
+        });
+        let mut body = renderer.render_body(doc_type.clone(), block(chapter.nodes));
+        // links to anchors on other pages need the page
+        for (name, page) in &anchors {
+            if *page != page_name(i) {
+                let name = escape(name);
+                body = body.replace(
+                    &format!("href=\"#{name}\""),
+                    &format!("href=\"{page}#{name}\""),
+                );
+            }
+        }
+        let class = format!("{doc_type:?}").to_lowercase();
+        pages.push((
+            page_name(i),
+            xhtml(
+                &page_title,
+                &language,
+                &format!("<body class=\"{class}\">\n{body}</body>"),
+            ),
+        ));
+        toc.push(format!(
+            "<li><a href=\"{}\">{}</a></li>\n",
+            page_name(i),
+            escape(&page_title)
+        ));
+    }
+    let nav = xhtml(
+        &title,
+        &language,
+        &format!(
+            "<body>\n<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{}</ol>\n</nav>\n</body>",
+            escape(&title),
+            toc.concat()
+        ),
+    );
+
+    let (year, month, day, hour, minute, second) = civil(timestamp());
+    let mut zip = zip::Writer::new(year, month, day, hour, minute, second);
+    // the media type comes first, so that the file can be recognized by it
+    zip.add("mimetype", b"application/epub+zip");
+    zip.add("META-INF/container.xml", CONTAINER.as_bytes());
+    let modified = format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z");
+    let identifier = match html::lookup(&meta_data, "identifier") {
+        Some(identifier) => identifier.to_string(),
+        // the same book gets the same identifier
+        None => {
+            let pages: String = pages.iter().map(|(_, page)| page.as_str()).collect();
+            format!("urn:mato:{:08x}", zip::crc32(pages.as_bytes()))
+        }
+    };
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+         <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::new();
+    for (name, _) in &pages {
+        let id = name.trim_end_matches(".xhtml");
+        manifest.push_str(&format!(
+            "<item id=\"{id}\" href=\"{name}\" media-type=\"application/xhtml+xml\"/>\n"
+        ));
+        spine.push_str(&format!("<itemref idref=\"{id}\"/>\n"));
+    }
+    for (i, (_, name)) in images.files.iter().enumerate() {
+        manifest.push_str(&format!(
+            "<item id=\"image-{}\" href=\"{name}\" media-type=\"{}\"/>\n",
+            i + 1,
+            media_type(name).unwrap_or_default()
+        ));
+    }
+    let mut metadata = format!(
+        "<dc:identifier id=\"id\">{}</dc:identifier>\n\
+         <dc:title>{}</dc:title>\n\
+         <dc:language>{}</dc:language>\n",
+        escape(&identifier),
+        escape(&title),
+        escape(&language)
+    );
+    if let Some(author) = html::lookup(&meta_data, "author") {
+        metadata.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape(author)));
+    }
+    metadata.push_str(&format!(
+        "<meta property=\"dcterms:modified\">{modified}</meta>\n"
+    ));
+    zip.add(
+        "OEBPS/content.opf",
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"id\">\n\
+             <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n{metadata}</metadata>\n\
+             <manifest>\n{manifest}</manifest>\n\
+             <spine>\n{spine}</spine>\n\
+             </package>\n"
+        )
+        .as_bytes(),
+    );
+    zip.add("OEBPS/nav.xhtml", nav.as_bytes());
+    zip.add("OEBPS/style.css", renderer.style().as_bytes());
+    for (name, page) in &pages {
+        zip.add(&format!("OEBPS/{name}"), page.as_bytes());
+    }
+    for (path, name) in &images.files {
+        m_dbg!("adding image: {}", path.display());
+        let image = fs::read(path)
+            .map_err(|e| Error::io(format!("cannot read '{}'", path.display()), e))?;
+        zip.add(&format!("OEBPS/{name}"), &image);
+    }
+    Ok(zip.finish())
+}
+
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+<rootfiles>
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>
+</rootfiles>
+</container>
+";
+
+/// the file name of the page of the `i`th chapter
+fn page_name(i: usize) -> String {
+    format!("chapter-{}.xhtml", i + 1)
+}
+
+/// an XHTML page with `body`, using the style sheet of the book
+fn xhtml(title: &str, language: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" \
+         lang=\"{language}\" xml:lang=\"{language}\">\n\
+         <head>\n<title>{}</title>\n\
+         <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n</head>\n\
+         {body}\n</html>\n",
+        escape(title),
+        language = escape(language)
+    )
+}
+
+fn file_stem(source_file: &str) -> String {
+    match Path::new(source_file).file_stem() {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => "Untitled".to_string(),
+    }
+}
+
+fn media_type(file_name: &str) -> Option<&'static str> {
+    let extension = Path::new(file_name)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+    IMAGE_TYPES
+        .iter()
+        .find(|(e, _)| *e == extension)
+        .map(|(_, media_type)| *media_type)
+}
+
+/// nodes that show nothing on a page of their own
+fn is_blank(tree: &Tree) -> bool {
+    match tree {
+        Tree::Paragraph(_) | Tree::LineBreak(_) | Tree::VSpace(_) | Tree::Empty(_) => true,
+        // groff requests, left out by the html renderer
+        Tree::Literal(s, _) => s.trim().is_empty() || s.starts_with('.'),
+        Tree::MetaDataBlock(block, _) => {
+            let meta_data = html::meta_data(block);
+            ["title", "subtitle", "author"]
+                .iter()
+                .all(|key| html::lookup(&meta_data, key).is_none())
+        }
+        _ => false,
+    }
+}
+
+/// the top-level nodes of `content`, with the blocks of included files opened up
+fn top_level(content: Tree, nodes: &mut Vec<Tree>) {
+    match content {
+        Tree::Block(children, _) => {
+            for child in children {
+                top_level(child, nodes);
+            }
+        }
+        node => nodes.push(node),
+    }
+}
+
+/// splits `content` into chapters. what comes before the first one, like the
+/// title from the meta data, is a chapter, too, unless there is nothing to see
+fn chapters(content: Tree) -> Vec<Chapter> {
+    let mut nodes = Vec::new();
+    top_level(content, &mut nodes);
+    let mut chapters = vec![Chapter::default()];
+    for node in nodes {
+        let current = chapters.last().unwrap();
+        let starts_chapter = match &node {
+            Tree::Heading(_, 0, _, _) => current.has_content(),
+            // a chapter mark below a heading belongs to its chapter
+            Tree::ChapterMark(..) => current
+                .nodes
+                .iter()
+                .any(|node| !is_blank(node) && !matches!(node, Tree::Heading(_, 0, _, _))),
+            _ => false,
+        };
+        if starts_chapter {
+            chapters.push(Chapter::default());
+        }
+        let current = chapters.last_mut().unwrap();
+        if let Tree::Heading(title, 0, _, _) = &node {
+            if current.title.is_none() {
+                current.title = Some(html::text(title).trim().to_string());
+            }
+        }
+        current.nodes.push(node);
+    }
+    chapters.retain(Chapter::has_content);
+    chapters
+}
+
+/// the anchor names of the headings in `nodes`
+fn anchor_names(nodes: &[Tree]) -> Vec<String> {
+    #[derive(Default)]
+    struct Names(Vec<String>);
+
+    impl Visit for Names {
+        fn visit(&mut self, tree: &Tree) {
+            match tree {
+                Tree::Heading(_, _, name, _) if !name.is_empty() => self.0.push(name.clone()),
+                _ => visit_children(self, tree),
+            }
+        }
+    }
+
+    let mut names = Names::default();
+    for node in nodes {
+        names.visit(node);
+    }
+    names.0
+}
+
+/// points images to their copies in the book and collects them. remote images
+/// are errors
+struct Images {
+    /// the directory relative image paths are resolved against
+    dir: PathBuf,
+    /// the images found and their names in the book
+    files: Vec<(PathBuf, String)>,
+    error: Option<Error>,
+}
+
+impl Fold for Images {
+    fn fold(&mut self, tree: Tree) -> Tree {
+        match tree {
+            Tree::Image(caption, path, size_spec, span) if self.error.is_none() => {
+                let path = match *path {
+                    Tree::Literal(p, path_span) => match self.add(&p) {
+                        Ok(name) => lit(&name).with_span(path_span),
+                        Err(e) => {
+                            self.error = Some(e);
+                            Tree::Literal(p, path_span)
+                        }
+                    },
+                    path => path,
+                };
+                Tree::Image(caption, Box::new(path), size_spec, span)
+            }
+            _ => fold_children(self, tree),
+        }
+    }
+}
+
+impl Images {
+    /// the name in the book of the image at `path`
+    fn add(&mut self, path: &str) -> Result<String> {
+        // EPUB 3 wants images in the book, a reader may well be offline
+        if path.contains("://") {
+            return Err(Error::Process(format!(
+                "cannot put remote image '{path}' into an EPUB, download it and refer to the file"
+            )));
+        }
+        let source = self.dir.join(path);
+        if let Some((_, name)) = self.files.iter().find(|(s, _)| *s == source) {
+            return Ok(name.clone());
+        }
+        if media_type(path).is_none() {
+            return Err(Error::Process(format!(
+                "cannot put image '{path}' into an EPUB, only GIF, JPEG, PNG, SVG and WebP are supported"
+            )));
+        }
+        let extension = source.extension().unwrap_or_default().to_string_lossy();
+        let name = format!("images/image-{}.{}", self.files.len() + 1, extension);
+        self.files.push((source, name.clone()));
+        Ok(name)
+    }
+}
+
+/// seconds since the epoch the book is dated at. `SOURCE_DATE_EPOCH` is honored
+/// for reproducible builds
+fn timestamp() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        })
+}
+
+/// year, month, day, hour, minute and second in UTC of `secs` since the epoch
+fn civil(secs: u64) -> (u64, u64, u64, u64, u64, u64) {
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = secs / 86400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    let time = secs % 86400;
+    (year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::*;
+
+    fn titles(input: &str) -> Vec<Option<String>> {
+        let tree = crate::parse("", input);
+        let content = match tree {
+            Tree::Document(_, content, _) => *content,
+            tree => tree,
+        };
+        chapters(content).into_iter().map(|c| c.title).collect()
+    }
+
+    fn contains(haystack: &[u8], needle: &str) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle.as_bytes())
+    }
+
+    #[test]
+    fn chapters_start_with_top_level_headings() {
+        assert_eq!(
+            titles("# One\n## Sub\ntext\n# Two\ntext\n"),
+            vec![Some("One".to_string()), Some("Two".to_string())]
+        );
+    }
+
+    #[test]
+    fn chapter_marks_below_headings_do_not_start_chapters() {
+        assert_eq!(
+            titles("intro\n\n# One\n\n>>(1)\n\ntext\n\n>>(2)\n\nmore\n"),
+            vec![None, Some("One".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn links_to_other_pages_name_the_page() {
+        let config = Config::default();
+        let tree = crate::parse(
+            "",
+            "# One /one/\n\n[two](#two)\n\n# Two /two/\n\n[one](#one)\n",
+        );
+        let epub = package(&config, tree).unwrap();
+        assert_eq!(&epub[30..58], b"mimetypeapplication/epub+zip");
+        assert!(contains(&epub, "<a href=\"chapter-2.xhtml#two\">two</a>"));
+        assert!(contains(&epub, "<a href=\"chapter-1.xhtml#one\">one</a>"));
+        assert!(contains(&epub, "<dc:language>und</dc:language>"));
+        assert!(contains(
+            &epub,
+            "<li><a href=\"chapter-2.xhtml\">Two</a></li>"
+        ));
+    }
+
+    #[test]
+    fn unsupported_images_are_errors() {
+        let config = Config::default();
+        let tree = block(vec![image(lit("cap"), lit("a.pdf"), empty())]);
+        assert!(package(&config, tree).is_err());
+        let tree = block(vec![image(lit("cap"), lit("https://a.org/a.png"), empty())]);
+        assert!(package(&config, tree).is_err());
+    }
+
+    #[test]
+    fn dates_are_civil() {
+        assert_eq!(civil(0), (1970, 1, 1, 0, 0, 0));
+        assert_eq!(civil(951_827_696), (2000, 2, 29, 12, 34, 56));
+    }
+}
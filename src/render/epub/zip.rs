@@ -0,0 +1,130 @@
+//! a minimal ZIP writer. files are stored without compression, which is all an
+//! EPUB needs and spares us a deflate implementation.
+
+/// the CRC-32 checksum ZIP uses, the one of IEEE 802.3
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// collects files into a ZIP archive, in the order they are added
+pub struct Writer {
+    /// local headers and file contents
+    data: Vec<u8>,
+    /// the central directory, appended by `finish`
+    directory: Vec<u8>,
+    entries: u16,
+    /// modification time and date of all files, in MS-DOS format
+    time: u16,
+    date: u16,
+}
+
+impl Writer {
+    /// a writer dating the files at the given calendar time, in UTC
+    pub fn new(year: u64, month: u64, day: u64, hour: u64, minute: u64, second: u64) -> Writer {
+        // MS-DOS dates start in 1980
+        let year = year.clamp(1980, 2107) - 1980;
+        Writer {
+            data: Vec::new(),
+            directory: Vec::new(),
+            entries: 0,
+            time: ((hour << 11) | (minute << 5) | (second / 2)) as u16,
+            date: ((year << 9) | (month << 5) | day) as u16,
+        }
+    }
+
+    /// adds a file named `name`, a path with `/` as separator
+    pub fn add(&mut self, name: &str, content: &[u8]) {
+        let offset = self.data.len() as u32;
+        let mut header = Vec::new();
+        // version needed to extract, flags, method stored
+        put16(&mut header, 10);
+        put16(&mut header, 0);
+        put16(&mut header, 0);
+        put16(&mut header, self.time);
+        put16(&mut header, self.date);
+        put32(&mut header, crc32(content));
+        put32(&mut header, content.len() as u32);
+        put32(&mut header, content.len() as u32);
+        put16(&mut header, name.len() as u16);
+        // no extra field
+        put16(&mut header, 0);
+
+        put32(&mut self.data, 0x0403_4b50);
+        self.data.extend_from_slice(&header);
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(content);
+
+        put32(&mut self.directory, 0x0201_4b50);
+        // version made by
+        put16(&mut self.directory, 10);
+        self.directory.extend_from_slice(&header);
+        // comment length, disk number, internal and external attributes
+        put16(&mut self.directory, 0);
+        put16(&mut self.directory, 0);
+        put16(&mut self.directory, 0);
+        put32(&mut self.directory, 0);
+        put32(&mut self.directory, offset);
+        self.directory.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+    }
+
+    /// the archive with all files added
+    pub fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.directory.len() as u32;
+        self.data.append(&mut self.directory);
+        put32(&mut self.data, 0x0605_4b50);
+        // number of this disk and of the one with the directory
+        put16(&mut self.data, 0);
+        put16(&mut self.data, 0);
+        put16(&mut self.data, self.entries);
+        put16(&mut self.data, self.entries);
+        put32(&mut self.data, size);
+        put32(&mut self.data, offset);
+        // comment length
+        put16(&mut self.data, 0);
+        self.data
+    }
+}
+
+fn put16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn files_are_stored_in_order() {
+        let mut writer = Writer::new(2024, 5, 17, 12, 30, 0);
+        writer.add("mimetype", b"application/epub+zip");
+        writer.add("a.txt", b"a");
+        let zip = writer.finish();
+        assert!(zip.starts_with(b"PK\x03\x04"));
+        // the name and the content follow the 30 bytes of the local header
+        assert_eq!(&zip[30..58], b"mimetypeapplication/epub+zip");
+        assert_eq!(zip.windows(4).filter(|w| w == b"PK\x01\x02").count(), 2);
+        let end = &zip[zip.len() - 22..];
+        assert!(end.starts_with(b"PK\x05\x06"));
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 2);
+    }
+}
@@ -1,3 +1,32 @@
 pub mod man;
 pub mod mandoc;
 pub mod mom;
+
+/// groff input for a character escaped in markdown. the backslash is groff's escape
+/// character, dots and apostrophes would start a request at the beginning of a line and
+/// `^` and `~` would be typeset as accents.
+pub fn escaped_char(c: char) -> String {
+    match c {
+        '\\' => "\\[rs]".to_string(),
+        '.' | '\'' => format!("\\&{c}"),
+        '^' => "\\[ha]".to_string(),
+        '~' => "\\[ti]".to_string(),
+        _ => c.to_string(),
+    }
+}
+
+/// escapes preformatted text for `.EX` or `.Bd -literal`, where groff would still see
+/// requests and escapes
+pub fn escape_code(s: &str) -> String {
+    s.replace('\\', "\\e")
+        .split('\n')
+        .map(|line| {
+            if line.starts_with(['.', '\'']) {
+                format!("\\&{line}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
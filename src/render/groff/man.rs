@@ -1,33 +1,203 @@
+use crate::syntax::{Alignment, ListKind, Tree};
 use crate::Render;
 
 pub struct ManRenderer {
     in_synopsis: bool,
     sy_open: bool,
+    /// kinds of the lists we are in, ordered ones count their items up
+    lists: Vec<ListKind>,
+    /// the header line has been written from the meta data
+    th_written: bool,
+    /// footnotes and sidenotes of the current section, set at its end
+    notes: Vec<String>,
+    /// notes are numbered through the whole page
+    note_count: usize,
+    /// notes cannot nest, nested ones are set in parentheses
+    in_note: bool,
 }
 
 pub fn new() -> ManRenderer {
     ManRenderer {
         in_synopsis: false,
         sy_open: false,
+        lists: Vec::new(),
+        th_written: false,
+        notes: Vec::new(),
+        note_count: 0,
+        in_note: false,
+    }
+}
+
+/// quotes a macro argument, if it has to be
+fn argument(s: &str) -> String {
+    let s = s.trim().trim_matches('"');
+    if !s.is_empty() && !s.contains([' ', '\t']) {
+        s.to_string()
+    } else {
+        format!("\"{}\"", s.replace('"', "\\(dq"))
+    }
+}
+
+impl ManRenderer {
+    /// renders a footnote or sidenote as a numbered reference to the notes at the end
+    /// of the section
+    fn render_note(&mut self, content: Tree) -> String {
+        if self.in_note {
+            return format!(" ({})", self.render(content));
+        }
+        self.in_note = true;
+        let note = self.render(content);
+        self.in_note = false;
+        self.note_count += 1;
+        let n = self.note_count;
+        self.notes.push(format!(".IP [{n}] 4\n{}\n", note.trim()));
+        format!("[{n}]")
+    }
+
+    /// the notes of the section ending here
+    fn render_notes(&mut self) -> String {
+        if self.notes.is_empty() {
+            return String::new();
+        }
+        format!(".SS Notes\n{}", self.notes.drain(..).collect::<String>())
+    }
+
+    /// renders the meta data as the header line, named by its `name` or `title`
+    fn render_meta_data(&mut self, block: Tree) -> String {
+        let children = match block {
+            Tree::Block(children, _) => children,
+            item => vec![item],
+        };
+        let items: Vec<(String, String)> = children
+            .into_iter()
+            .filter_map(|child| match child {
+                Tree::MetaDataItem(key, value, _) => Some((key, value)),
+                _ => None,
+            })
+            .collect();
+        let value = |key: &str| {
+            items
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.as_str())
+        };
+        let Some(name) = value("name").or(value("title")) else {
+            return String::new();
+        };
+        let mut fields = vec![argument(name), argument(value("section").unwrap_or("1"))];
+        let optional = [value("date"), value("source"), value("manual")];
+        // empty fields are only written in front of others
+        let given = optional
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |i| i + 1);
+        fields.extend(optional[..given].iter().map(|v| argument(v.unwrap_or(""))));
+        self.th_written = true;
+        format!(".TH {}\n", fields.join(" "))
+    }
+
+    /// renders a table as `tbl` source with a bold header row, separated from the body by a rule
+    fn render_table(
+        &mut self,
+        alignments: &[Alignment],
+        header: Tree,
+        rows: Vec<Tree>,
+        caption: Tree,
+    ) -> String {
+        let columns: Vec<&str> = alignments
+            .iter()
+            .map(|alignment| match alignment {
+                Alignment::Center => "c",
+                Alignment::Right => "r",
+                Alignment::Left | Alignment::Default => "l",
+            })
+            .collect();
+        let header = match header {
+            Tree::TableRow(cells, _) => self.render_table_row(cells, true),
+            header => self.render(header),
+        };
+        let rows: String = rows.into_iter().map(|row| self.render(row)).collect();
+        let caption = match caption {
+            Tree::Empty(_) => String::new(),
+            caption => format!(".P\n{}\n", self.render(caption).trim()),
+        };
+        format!(
+            ".TS\n{}.\n{}_\n{}.TE\n{}",
+            columns.join(" "),
+            header,
+            rows,
+            caption
+        )
+    }
+
+    /// renders the cells of a table row separated by tabs, as `tbl` expects them
+    fn render_table_row(&mut self, cells: Vec<Tree>, bold: bool) -> String {
+        let cells: Vec<String> = cells
+            .into_iter()
+            .map(|cell| {
+                let cell = self.render(cell).replace('\t', " ");
+                let cell = if bold {
+                    format!("\\f[B]{cell}\\f[]")
+                } else {
+                    cell
+                };
+                // cells spanning lines, like bold text, need to be text blocks
+                if cell.contains('\n') {
+                    format!("T{{\n{cell}\nT}}")
+                } else {
+                    cell
+                }
+            })
+            .collect();
+        format!("{}\n", cells.join("\t"))
     }
 }
 
 impl Render for ManRenderer {
     fn render(&mut self, tree: crate::syntax::Tree) -> String {
         match tree {
-            crate::syntax::Tree::Document(_, t) => self.render(*t),
-            crate::syntax::Tree::Paragraph() => ".P\n".to_owned(),
-            crate::syntax::Tree::PreformattedLiteral(_) => todo!(),
-            crate::syntax::Tree::Literal(l) => l,
-            crate::syntax::Tree::EscapeLit(s) => match s.as_str() {
+            crate::syntax::Tree::Document(_, t, _) => {
+                let mut man = self.render(*t);
+                man.push_str(&self.render_notes());
+                // text starting with an apostrophe would be taken for a control line.
+                // the one of a leading dot is escaped already, see `EscapeLit`
+                let mut man = man
+                    .split('\n')
+                    .map(|line| {
+                        if line.starts_with('\'') {
+                            format!("\\&{line}")
+                        } else {
+                            line.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                // tells man to run the tables through tbl
+                if man.starts_with(".TS\n") || man.contains("\n.TS\n") {
+                    man.insert_str(0, "'\\\" t\n");
+                }
+                man
+            }
+            crate::syntax::Tree::Paragraph(_) => ".P\n".to_owned(),
+            crate::syntax::Tree::PreformattedLiteral(s, _) => super::escape_code(&s),
+            crate::syntax::Tree::Literal(l, _) => l,
+            crate::syntax::Tree::EscapeLit(s, _) => match s.as_str() {
                 "." => "\\&.".to_string(),
+                "\\" => "\\[rs]".to_string(),
                 _ => s,
             },
-            crate::syntax::Tree::DropCap(_, _) => todo!(),
-            crate::syntax::Tree::Color(_) => todo!(),
-            crate::syntax::Tree::ChapterMark(_) => todo!(),
-            crate::syntax::Tree::Heading(t, level, _) => match level {
-                0 => format!(".TH {}\n", self.render(*t)),
+            crate::syntax::Tree::Escaped(c, _) => super::escaped_char(c),
+            // a terminal has no drop caps, colors or margins
+            crate::syntax::Tree::DropCap(c, _, _) => (c as char).to_string(),
+            crate::syntax::Tree::Color(_, _) => "".to_owned(),
+            crate::syntax::Tree::ChapterMark(_, _) => "".to_owned(),
+            crate::syntax::Tree::Heading(t, level, _, _) => match level {
+                // the header line is written from the meta data already, with the title
+                0 if self.th_written => self.render_notes(),
+                0 => {
+                    let notes = self.render_notes();
+                    format!("{notes}.TH {}\n", self.render(*t))
+                }
                 1 => {
                     let section_header_name = self.render(*t);
                     let mut sy_closing = "";
@@ -40,20 +210,32 @@ impl Render for ManRenderer {
                             self.sy_open = false;
                         }
                     };
-                    format!("{sy_closing}.\n.SH {section_header_name}")
+                    let notes = self.render_notes();
+                    format!("{sy_closing}{notes}.\n.SH {section_header_name}")
                 }
                 2 => format!(".SS {}\n", self.render(*t)),
                 _ => self.render(*t),
             },
-            crate::syntax::Tree::Bold(t) => format!("\\c\n.B {}\\c\n", self.render(*t)),
-            crate::syntax::Tree::Italic(t) => {
+            // without an argument, the macros would set the next line
+            crate::syntax::Tree::Bold(t, _) | crate::syntax::Tree::Italic(t, _)
+                if matches!(*t, crate::syntax::Tree::Empty(_)) =>
+            {
+                "".to_owned()
+            }
+            crate::syntax::Tree::Bold(t, _) => format!("\\c\n.B {}\\c\n", self.render(*t)),
+            crate::syntax::Tree::Italic(t, _) => {
                 let italic_text = self.render(*t);
                 format!("\\c\n.I {italic_text}\\c\n")
             }
-            crate::syntax::Tree::BoldItalic(_) => todo!(),
-            crate::syntax::Tree::SmallCaps(_) => todo!(),
-            crate::syntax::Tree::CodeBlock(_, _) => todo!(),
-            crate::syntax::Tree::InlineCode(t) => {
+            crate::syntax::Tree::BoldItalic(t, _) => format!("\\f[BI]{}\\f[]", self.render(*t)),
+            // there are no small caps, capitals a size smaller come close
+            crate::syntax::Tree::SmallCaps(t, _) => {
+                format!("\\s-1{}\\s+1", self.render(*t).to_uppercase())
+            }
+            crate::syntax::Tree::CodeBlock(_, t, _) => {
+                format!(".EX\n{}\n.EE\n", self.render(*t).trim_end_matches('\n'))
+            }
+            crate::syntax::Tree::InlineCode(t, _) => {
                 if self.in_synopsis {
                     let mut sy_closing = "";
                     if self.sy_open {
@@ -65,23 +247,143 @@ impl Render for ManRenderer {
                     self.render(*t)
                 }
             }
-            crate::syntax::Tree::Quote(_) => todo!(),
-            crate::syntax::Tree::Footnote(_) => todo!(),
-            crate::syntax::Tree::RightSidenote(_) => todo!(),
-            crate::syntax::Tree::HyperRef(_, _) => todo!(),
-            crate::syntax::Tree::DocRef(_, _) => todo!(),
-            crate::syntax::Tree::Cat(t1, t2) => format!("{}{}", self.render(*t1), self.render(*t2)),
-            crate::syntax::Tree::List(t, _) => format!(".\n{}", self.render(*t)),
-            crate::syntax::Tree::ListItem(t, _) => format!(".TP\n.B {}", self.render(*t)),
-            crate::syntax::Tree::MetaDataBlock(_) => todo!(),
-            crate::syntax::Tree::MetaDataItem(_, _) => todo!(),
-            crate::syntax::Tree::ImageSizeSpec(_, _) => todo!(),
-            crate::syntax::Tree::Image(_, _, _) => todo!(),
-            crate::syntax::Tree::LineBreak() => "\n".to_owned(),
-            crate::syntax::Tree::VSpace() => "".to_owned(),
-            crate::syntax::Tree::Empty() => "".to_owned(),
-            crate::syntax::Tree::EmDash => "\\(em".to_owned(),
-            crate::syntax::Tree::EnDash => "\\(en".to_owned(),
+            crate::syntax::Tree::Quote(t, _) => format!("\\(lq{}\\(rq", self.render(*t)),
+            crate::syntax::Tree::Footnote(t, _) | crate::syntax::Tree::RightSidenote(t, _) => {
+                self.render_note(*t)
+            }
+            crate::syntax::Tree::HyperRef(t, url, _) => {
+                let url = self.render(*url);
+                format!("\\c\n.UR {url}\n{}\n.UE\n", self.render(*t))
+            }
+            // a page cannot link into itself
+            crate::syntax::Tree::DocRef(_, t, _) => self.render(*t),
+            crate::syntax::Tree::Block(children, _) | crate::syntax::Tree::Inline(children, _) => {
+                let mut man = String::new();
+                for child in children {
+                    let child = self.render(child);
+                    // a line starting with a space would break the line, after a `\c`, too
+                    if child.starts_with(' ') && man.ends_with('\n') {
+                        man.push_str("\\&");
+                    }
+                    man.push_str(&child);
+                }
+                man
+            }
+            crate::syntax::Tree::List(t, _, kind, _) => {
+                self.lists.push(kind);
+                let items = self.render(*t);
+                self.lists.pop();
+                // what follows the list would be indented like its last item
+                if self.lists.is_empty() {
+                    format!(".\n{items}.P\n")
+                } else {
+                    format!(".\n{items}")
+                }
+            }
+            crate::syntax::Tree::ListItem(t, _, _) => match self.lists.last_mut() {
+                Some(ListKind::Ordered(number, delimiter)) => {
+                    let tag = format!("{number}{delimiter}");
+                    *number += 1;
+                    format!(".IP {tag}\n{}\n", self.render(*t))
+                }
+                _ => format!(".IP \\(bu\n{}\n", self.render(*t)),
+            },
+            crate::syntax::Tree::MetaDataBlock(t, _) => self.render_meta_data(*t),
+            crate::syntax::Tree::MetaDataItem(_, _, _) => "".to_owned(),
+            crate::syntax::Tree::ImageSizeSpec(_, _, _) => "".to_owned(),
+            // a terminal cannot show images, their caption stands in for them
+            crate::syntax::Tree::Image(caption, path, _, _) => {
+                let caption = match *caption {
+                    crate::syntax::Tree::Empty(_) => self.render(*path),
+                    caption => self.render(caption),
+                };
+                format!(".P\n[{}]\n", caption.trim())
+            }
+            crate::syntax::Tree::LineBreak(_) => "\n".to_owned(),
+            crate::syntax::Tree::VSpace(_) => "".to_owned(),
+            crate::syntax::Tree::Empty(_) => "".to_owned(),
+            crate::syntax::Tree::EmDash(_) => "\\(em".to_owned(),
+            // two dashes are long options rather than ranges in a man page
+            crate::syntax::Tree::EnDash(_) => "\\-\\-".to_owned(),
+            crate::syntax::Tree::Table(alignments, header, rows, caption, _) => {
+                self.render_table(&alignments, *header, rows, *caption)
+            }
+            crate::syntax::Tree::TableRow(cells, _) => self.render_table_row(cells, false),
+            crate::syntax::Tree::FootnoteRef(label, _) => format!("[^{label}]"),
+            crate::syntax::Tree::FootnoteDef(_, _, _) => "".to_owned(),
+            crate::syntax::Tree::LinkRef(_, t, suffix, _) => {
+                format!("[{}]{suffix}", self.render(*t))
+            }
+            crate::syntax::Tree::LinkDef(_, _, _, _) => "".to_owned(),
+            crate::syntax::Tree::Include(_, _) => "".to_owned(),
+            crate::syntax::Tree::BlockQuote(t, _) => {
+                format!(".RS\n{}\n.RE\n", self.render(*t).trim_end_matches('\n'))
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn man(input: &str) -> String {
+        new().render(crate::parse("", input))
+    }
+
+    #[test]
+    fn meta_data_makes_the_header_line() {
+        let page = man("---\nname: mato\nsection: 7\nsource: \"MaTo Tools\"\n---\n# mato\n");
+        assert!(
+            page.starts_with(".TH mato 7 \"\" \"MaTo Tools\"\n"),
+            "{page}"
+        );
+        assert_eq!(page.matches(".TH").count(), 1);
+    }
+
+    #[test]
+    fn code_blocks_are_examples() {
+        assert_eq!(man("```\n.x \\y\n```\n"), ".EX\n\\&.x \\ey\n.EE\n\n");
+    }
+
+    #[test]
+    fn links_are_urls() {
+        assert!(man("see [site](https://a.org)\n").contains(".UR https://a.org\nsite\n.UE\n"));
+    }
+
+    #[test]
+    fn notes_end_the_section() {
+        let page = man("## ONE\n\na^(first) b\n\n## TWO\n\nc^(second)\n");
+        let one = page.find(".SH ONE").unwrap();
+        let first = page.find(".IP [1] 4\nfirst").unwrap();
+        let two = page.find(".SH TWO").unwrap();
+        let second = page.find(".IP [2] 4\nsecond").unwrap();
+        assert!(one < first && first < two && two < second, "{page}");
+        assert!(page.contains("a[1] b"));
+    }
+
+    #[test]
+    fn bullet_items_end_their_line() {
+        let page = man("* one\n* two\n\nafter\n");
+        assert!(
+            page.contains(".IP \\(bu\none\n.IP \\(bu\ntwo\n.P\n"),
+            "{page}"
+        );
+        assert!(page.contains("\nafter"), "{page}");
+    }
+
+    #[test]
+    fn text_lines_are_no_control_lines() {
+        let page = man("'apostrophe line\n.dot line\n\n*mato*'s [link](u)\n");
+        assert!(page.starts_with("\\&'apostrophe line\n\\&.dot line\n"), "{page}");
+        assert!(page.contains(".B mato\\c\n\\&'s"), "{page}");
+        assert!(!page.contains("\n'"), "{page}");
+    }
+
+    #[test]
+    fn tables_need_tbl() {
+        let page = man("| a | b |\n|---|--:|\n| 1 | 2 |\n");
+        assert!(page.starts_with("'\\\" t\n"), "{page}");
+        assert!(page.contains(".TS\nl r.\n\\f[B]a\\f[]\t\\f[B]b\\f[]\n_\n1\t2\n.TE\n"));
+    }
+}
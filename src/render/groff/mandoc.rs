@@ -1,97 +1,1039 @@
+//! renders the syntax tree as a manual page using the semantic mdoc macros
+//!
+//! mdoc is line based: inline macros like `.Sy` need lines of their own, so the
+//! rendering of a node starts with a line break if it is a macro. text and
+//! macros are joined in `join`, and `tidy` removes what mandoc would complain
+//! about at the end.
+use std::collections::HashMap;
+
+use crate::syntax::{visit_children, ListKind, Tree, Visit};
 use crate::Render;
 
+/// the sections with a structure of their own
+#[derive(Debug, PartialEq)]
+enum Section {
+    /// before the first section
+    Prologue,
+    /// names and description of the page, separated by a dash
+    Name,
+    /// the usage, set with `.Nm`, `.Fl` and `.Ar`
+    Synopsis,
+    Other,
+}
+
 pub struct ManDocRenderer {
-    in_title: bool,
-    title_open: bool,
+    section: Section,
+    /// the name of the page, which `.Nm` stands for
+    name: String,
+    /// `.Dd`, `.Dt` and `.Os` have been written
+    prologue_written: bool,
+    /// the description from the meta data, for a missing NAME section
+    description: String,
+    /// the text of the NAME section, made into `.Nm` and `.Nd` at its end
+    name_text: String,
+    /// kinds of the lists we are in
+    lists: Vec<ListKind>,
+    /// the `.Bl` line of the list just rendered, a list right after continues it
+    last_list: Option<String>,
+    /// depth of the optional parts of the synopsis
+    options: usize,
+    /// a dash in the synopsis, making the next word a flag
+    dash: bool,
+    /// a paragraph has been started, `.Pp` is written in front of its first text
+    paragraph: bool,
+    /// nothing has been written since the last heading
+    section_start: bool,
+    /// titles of the headings with an anchor name, which `.Sx` refers to
+    anchors: HashMap<String, String>,
+    /// footnotes and sidenotes of the current section, set at its end
+    notes: Vec<String>,
+    /// notes are numbered through the page
+    note_count: usize,
+    /// notes cannot nest, nested ones are set in parentheses
+    in_note: bool,
 }
 
 pub fn new() -> ManDocRenderer {
     ManDocRenderer {
-        in_title: true,
-        title_open: true,
+        section: Section::Prologue,
+        name: String::new(),
+        prologue_written: false,
+        description: String::new(),
+        name_text: String::new(),
+        lists: Vec::new(),
+        last_list: None,
+        options: 0,
+        dash: false,
+        paragraph: false,
+        section_start: true,
+        anchors: HashMap::new(),
+        notes: Vec::new(),
+        note_count: 0,
+        in_note: false,
+    }
+}
+
+/// the plain text of a tree, for macro arguments
+fn text(tree: &Tree) -> String {
+    #[derive(Default)]
+    struct Text(String);
+
+    impl Visit for Text {
+        fn visit(&mut self, tree: &Tree) {
+            match tree {
+                Tree::Literal(s, _) | Tree::EscapeLit(s, _) | Tree::PreformattedLiteral(s, _) => {
+                    self.0.push_str(s)
+                }
+                Tree::Escaped(c, _) => self.0.push(*c),
+                // two dashes, as in long options
+                Tree::EnDash(_) => self.0.push_str("--"),
+                Tree::EmDash(_) => self.0.push_str("---"),
+                Tree::LineBreak(_) => self.0.push(' '),
+                // notes are set apart, see `render_note`
+                Tree::Footnote(..) | Tree::RightSidenote(..) => (),
+                _ => visit_children(self, tree),
+            }
+        }
+    }
+
+    let mut text = Text::default();
+    text.visit(tree);
+    text.0
+}
+
+/// the words of `s` as macro arguments. words looking like macro names or
+/// delimiters are escaped, so that they are shown as they are
+fn arguments(s: &str) -> String {
+    s.split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            let macro_like = (2..=3).contains(&word.len())
+                && chars.next().is_some_and(|c| c.is_ascii_uppercase())
+                && chars.all(|c| c.is_ascii_lowercase());
+            let delimiter = word.len() == 1 && "([.,:;)]!?|".contains(word);
+            if macro_like || delimiter {
+                format!("\\&{word}")
+            } else {
+                word.replace('"', "\\(dq")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// a flag, `-v` or `--verbose`, as `Fl` arguments, or else an `Ar` one
+fn flag_or_argument(word: &str) -> String {
+    let argument = match word.strip_prefix('-') {
+        Some(flag) => format!("Fl {}", arguments(flag)),
+        None => format!("Ar {}", arguments(word)),
+    };
+    argument.trim_end().to_string()
+}
+
+/// the name and section of a reference to another page, like `groff(1)`
+fn cross_reference(word: &str) -> Option<(&str, &str)> {
+    let (name, section) = word.strip_suffix(')')?.split_once('(')?;
+    let valid_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.+".contains(c));
+    let valid_section = section.starts_with(|c: char| c.is_ascii_digit())
+        && section.chars().all(|c| c.is_ascii_alphanumeric());
+    (valid_name && valid_section).then_some((name, section))
+}
+
+/// the words of `s` split like the shell does, keeping quoted parts together
+fn words(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in s.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// a date as mdoc wants it, `Month day, year`. `29.11.2024` and `2024-11-29`
+/// are understood, other dates are taken as they are
+fn date(date: Option<&str>) -> String {
+    const MONTHS: [&str; 12] = [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ];
+    let Some(date) = date else {
+        return "$Mdocdate$".to_string();
+    };
+    let parts: Vec<&str> = date.split(['.', '-']).collect();
+    let numbers: Vec<usize> = parts.iter().filter_map(|p| p.parse().ok()).collect();
+    let (day, month, year) = match (parts.len(), numbers.as_slice()) {
+        (3, [year, month, day]) if date.contains('-') => (*day, *month, *year),
+        (3, [day, month, year]) if date.contains('.') => (*day, *month, *year),
+        _ => return date.to_string(),
+    };
+    match MONTHS.get(month.wrapping_sub(1)) {
+        Some(month) if (1..=31).contains(&day) => format!("{month} {day}, {year}"),
+        _ => date.to_string(),
+    }
+}
+
+/// removes blank lines and white space at the ends of lines and starts each
+/// sentence on a line of its own, as mandoc wants it. literal displays are
+/// left as they are
+fn tidy(mdoc: &str) -> String {
+    let mut tidy = String::new();
+    let mut literal = false;
+    for line in mdoc.lines() {
+        if literal {
+            literal = line != ".Ed";
+            tidy.push_str(line);
+            tidy.push('\n');
+            continue;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('.') {
+            literal = line.starts_with(".Bd -literal");
+            tidy.push_str(line);
+            tidy.push('\n');
+            continue;
+        }
+        for sentence in sentences(line) {
+            if sentence.starts_with(['.', '\'']) {
+                tidy.push_str("\\&");
+            }
+            tidy.push_str(sentence);
+            tidy.push('\n');
+        }
+    }
+    tidy
+}
+
+/// a text line split after the end of each sentence
+fn sentences(line: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let bytes = line.as_bytes();
+    for i in 1..bytes.len().saturating_sub(2) {
+        let ends = b".!?".contains(&bytes[i])
+            && (bytes[i - 1].is_ascii_lowercase() || bytes[i - 1] == b')')
+            && bytes[i + 1] == b' '
+            && bytes[i + 2].is_ascii_uppercase();
+        if ends {
+            sentences.push(&line[start..=i]);
+            start = i + 2;
+        }
+    }
+    sentences.push(&line[start..]);
+    sentences
+}
+
+/// titles of the headings with an anchor name
+fn anchors(tree: &Tree) -> HashMap<String, String> {
+    #[derive(Default)]
+    struct Anchors(HashMap<String, String>);
+
+    impl Visit for Anchors {
+        fn visit(&mut self, tree: &Tree) {
+            match tree {
+                Tree::Heading(title, _, name, _) if !name.is_empty() => {
+                    self.0.insert(name.clone(), text(title).trim().to_string());
+                }
+                _ => visit_children(self, tree),
+            }
+        }
+    }
+
+    let mut anchors = Anchors::default();
+    anchors.visit(tree);
+    anchors.0
+}
+
+impl ManDocRenderer {
+    /// `.Dd`, `.Dt` and `.Os`, which start the page
+    fn render_prologue(
+        &mut self,
+        name: &str,
+        section: Option<&str>,
+        date_: Option<&str>,
+        os: Option<&str>,
+    ) -> String {
+        self.prologue_written = true;
+        self.name = name.to_string();
+        let os = match os {
+            Some(os) => format!(" {}", arguments(os)),
+            None => String::new(),
+        };
+        format!(
+            "\n.Dd {}\n.Dt {} {}\n.Os{os}\n",
+            date(date_),
+            arguments(&name.to_uppercase()),
+            section.unwrap_or("1")
+        )
+    }
+
+    /// the prologue from the meta data, with the keys `name` or `title`,
+    /// `section`, `date` and `os` or `source`. the first word of the name or
+    /// title is the name of the page
+    fn render_meta_data(&mut self, block: &Tree) -> String {
+        #[derive(Default)]
+        struct Items(Vec<(String, String)>);
+
+        impl Visit for Items {
+            fn visit(&mut self, tree: &Tree) {
+                match tree {
+                    Tree::MetaDataItem(key, value, _) => self
+                        .0
+                        .push((key.clone(), value.trim().trim_matches('"').to_string())),
+                    _ => visit_children(self, tree),
+                }
+            }
+        }
+
+        let mut items = Items::default();
+        items.visit(block);
+        let value = |key: &str| {
+            items
+                .0
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.clone())
+        };
+        if let Some(description) = value("description") {
+            self.description = description;
+        }
+        // a title is more than the name of the page, `name - description` at best
+        let title = value("title").unwrap_or_default();
+        if let Some((_, description)) = title.split_once(" - ") {
+            if self.description.is_empty() {
+                self.description = description.trim().to_string();
+            }
+        }
+        let name = value("name").unwrap_or(title);
+        match words(&name).first() {
+            Some(name) => self.render_prologue(
+                name,
+                value("section").as_deref(),
+                value("date").as_deref(),
+                value("os").or(value("source")).as_deref(),
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// the prologue from a top-level heading, giving name, section, date and
+    /// operating system like a `.TH` line of the man macros. a title like
+    /// `name - description` gives the name and description instead
+    fn render_title(&mut self, title: &Tree) -> String {
+        if self.prologue_written {
+            return String::new();
+        }
+        let title = text(title);
+        let words = words(&title);
+        let Some(name) = words.first() else {
+            return String::new();
+        };
+        if !words
+            .get(1)
+            .is_none_or(|section| section.starts_with(|c: char| c.is_ascii_digit()))
+        {
+            if let Some((_, description)) = title.split_once(" - ") {
+                self.description = description.trim().to_string();
+            }
+            return self.render_prologue(name, None, None, None);
+        }
+        let os = words.get(3..).map(|os| os.join(" "));
+        self.render_prologue(
+            name,
+            words.get(1).map(String::as_str),
+            words.get(2).map(String::as_str),
+            os.as_deref().filter(|os| !os.is_empty()),
+        )
+    }
+
+    fn render_section_heading(&mut self, title: &Tree, level: u8) -> String {
+        let title = text(title).trim().to_string();
+        let mut mdoc = String::from("\n");
+        if level == 1 {
+            mdoc.push_str(&self.end_section());
+            if self.section == Section::Prologue && !title.eq_ignore_ascii_case("NAME") {
+                mdoc.push_str(&self.render_name_section());
+            }
+            self.section = match title.to_uppercase().as_str() {
+                "NAME" => Section::Name,
+                "SYNOPSIS" => Section::Synopsis,
+                _ => Section::Other,
+            };
+            mdoc.push_str(&format!(".Sh {}\n", arguments(&title)));
+        } else {
+            mdoc.push_str(&self.start_description());
+            mdoc.push_str(&format!(".Ss {}\n", arguments(&title)));
+        }
+        self.section_start = true;
+        self.paragraph = false;
+        self.last_list = None;
+        mdoc
+    }
+
+    /// the NAME section made from the name and description of the prologue,
+    /// for pages starting with another section. mandoc expects NAME first
+    fn render_name_section(&self) -> String {
+        let name = match self.name.as_str() {
+            "" => "untitled",
+            name => name,
+        };
+        let description = match self.description.as_str() {
+            "" => name,
+            description => description,
+        };
+        format!(
+            ".Sh NAME\n.Nm {}\n.Nd {}\n",
+            arguments(name),
+            arguments(description)
+        )
+    }
+
+    /// the NAME and DESCRIPTION sections for text before the first section
+    fn start_description(&mut self) -> String {
+        if self.section != Section::Prologue {
+            return String::new();
+        }
+        self.section = Section::Other;
+        self.section_start = true;
+        format!("\n{}.Sh DESCRIPTION\n", self.render_name_section())
+    }
+
+    /// what ends with a section: the names and description of the NAME
+    /// section and the notes
+    fn end_section(&mut self) -> String {
+        let mut mdoc = String::new();
+        if self.section == Section::Name {
+            let name_text = std::mem::take(&mut self.name_text);
+            let (names, description) = match name_text.split_once(" - ") {
+                Some((names, description)) => (names.to_string(), description.trim().to_string()),
+                None => (self.name.clone(), name_text.trim().to_string()),
+            };
+            let names: Vec<String> = names
+                .split(',')
+                .map(|name| arguments(name.trim()))
+                .filter(|name| !name.is_empty())
+                .collect();
+            if self.name.is_empty() {
+                self.name = names.first().cloned().unwrap_or_default();
+            }
+            mdoc.push_str(&format!(".Nm {}\n", names.join(" ,\n.Nm ")));
+            mdoc.push_str(&format!(".Nd {}\n", arguments(&description)));
+        }
+        if !self.notes.is_empty() {
+            mdoc.push_str(".Ss Notes\n.Bl -tag -width Ds\n");
+            let first = self.note_count - self.notes.len() + 1;
+            for (i, note) in self.notes.drain(..).enumerate() {
+                mdoc.push_str(&format!(".It \\&[{}]\n{note}\n", first + i));
+            }
+            mdoc.push_str(".El\n");
+        }
+        mdoc
+    }
+
+    /// renders a footnote or sidenote as a numbered reference to the notes at the
+    /// end of the section
+    fn render_note(&mut self, content: Tree) -> String {
+        if self.in_note {
+            return format!(" ({})", text(&content).trim());
+        }
+        self.in_note = true;
+        let note = self.render_nested(content);
+        self.in_note = false;
+        self.notes.push(note.trim().to_string());
+        self.note_count += 1;
+        format!("[{}]", self.note_count)
+    }
+
+    /// renders the children of a block, like a list item, without a paragraph
+    /// in front of the first text
+    fn render_nested(&mut self, content: Tree) -> String {
+        let paragraph = std::mem::replace(&mut self.paragraph, false);
+        let section_start = std::mem::replace(&mut self.section_start, true);
+        let last_list = self.last_list.take();
+        let mdoc = self.render(content);
+        self.paragraph = paragraph;
+        self.section_start = section_start;
+        self.last_list = last_list;
+        mdoc
+    }
+
+    fn render_sequence(&mut self, children: Vec<Tree>) -> String {
+        let mut mdoc = String::new();
+        // runs of text are joined as one, a dot in front of a word is no delimiter
+        let mut text = String::new();
+        for child in children {
+            if self.section != Section::Synopsis
+                && matches!(child, Tree::Literal(..) | Tree::EscapeLit(..) | Tree::Escaped(..))
+            {
+                mdoc.push_str(&self.start_description());
+                text.push_str(&self.render(child));
+                continue;
+            }
+            self.join_text(&mut mdoc, std::mem::take(&mut text));
+            let heading = matches!(child, Tree::Heading(_, level, _, _) if level <= 2);
+            let content = !matches!(
+                child,
+                Tree::Heading(..)
+                    | Tree::MetaDataBlock(..)
+                    | Tree::Paragraph(_)
+                    | Tree::LineBreak(_)
+                    | Tree::VSpace(_)
+                    | Tree::Empty(_)
+                    | Tree::Block(..)
+                    | Tree::Inline(..)
+            );
+            if content {
+                mdoc.push_str(&self.start_description());
+            }
+            let rendered = self.render(child);
+            if heading || self.section == Section::Synopsis {
+                mdoc.push_str(&rendered);
+            } else if !rendered.trim().is_empty() {
+                self.join(&mut mdoc, &rendered);
+            } else if rendered.contains('\n') {
+                mdoc.push('\n');
+            }
+        }
+        self.join_text(&mut mdoc, text);
+        mdoc
+    }
+
+    /// appends a run of text to `mdoc`, if there is any
+    fn join_text(&mut self, mdoc: &mut String, text: String) {
+        if !text.trim().is_empty() {
+            self.join(mdoc, &text);
+        }
+    }
+
+    /// appends the rendering of a node to `mdoc`. macros start with a line break
+    fn join(&mut self, mdoc: &mut String, rendered: &str) {
+        let mut rendered = rendered;
+        let list = rendered.starts_with("\n.Bl ");
+        if self.paragraph {
+            self.paragraph = false;
+            // lists and displays have space above them already
+            if !self.section_start && !list && !rendered.starts_with("\n.Bd ") {
+                mdoc.push_str("\n.Pp\n");
+            }
+        }
+        self.section_start = false;
+        // a list interrupted by blank lines only is continued
+        let head = rendered.lines().nth(1).map(str::to_string);
+        if list && self.last_list.is_some() && self.last_list == head {
+            if let Some(end) = mdoc.trim_end().strip_suffix(".El") {
+                mdoc.truncate(end.len());
+                rendered = &rendered[rendered[1..].find('\n').map_or(0, |i| i + 1)..];
+            }
+        }
+        self.last_list = if list { head } else { None };
+        if rendered.starts_with('\n') {
+            mdoc.push_str(rendered);
+            return;
+        }
+        // punctuation after a macro goes on its line, as a delimiter
+        let last_line = mdoc
+            .trim_end_matches('\n')
+            .rsplit('\n')
+            .next()
+            .unwrap_or("");
+        if mdoc.ends_with('\n') && last_line.starts_with('.') && !last_line.starts_with(".Pp") {
+            let end = rendered
+                .find(|c: char| !".,:;!?)]".contains(c))
+                .unwrap_or(rendered.len());
+            if end > 0
+                && rendered[end..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace)
+            {
+                mdoc.truncate(mdoc.trim_end_matches('\n').len());
+                for c in rendered[..end].chars() {
+                    mdoc.push(' ');
+                    mdoc.push(c);
+                }
+                mdoc.push('\n');
+                rendered = &rendered[end..];
+            }
+        }
+        // text starting a line would be taken for a macro or a control line
+        if (mdoc.is_empty() || mdoc.ends_with('\n')) && rendered.starts_with(['.', '\'']) {
+            mdoc.push_str("\\&");
+        }
+        mdoc.push_str(rendered);
+    }
+
+    /// renders text, with references to other pages, like `groff(1)`, as `.Xr`
+    fn render_text(&self, s: &str) -> String {
+        let mut mdoc = String::new();
+        for word in s.split_inclusive(char::is_whitespace) {
+            let trimmed = word.trim_end();
+            let bare = trimmed.trim_end_matches(['.', ',', ':', ';', '!', '?']);
+            match cross_reference(bare) {
+                Some((name, section)) => {
+                    let punctuation: Vec<String> = trimmed[bare.len()..]
+                        .chars()
+                        .map(|c| format!(" {c}"))
+                        .collect();
+                    mdoc.push_str(&format!("\n.Xr {name} {section}{}\n", punctuation.concat()));
+                }
+                None => mdoc.push_str(word),
+            }
+        }
+        mdoc
+    }
+
+    /// bold or italic text. the page name and flags are marked up as such
+    fn render_emphasis(&self, macro_name: &str, content: &Tree) -> String {
+        let s = text(content);
+        let s = s.trim();
+        if s.is_empty() {
+            String::new()
+        } else if s == self.name {
+            "\n.Nm\n".to_string()
+        } else if s.starts_with('-') && !s.contains(' ') {
+            format!("\n.{}\n", flag_or_argument(s))
+        } else {
+            format!("\n.{macro_name} {}\n", arguments(s))
+        }
+    }
+
+    /// inline code: the page name, flags, references to other pages or literal text
+    fn render_code(&self, content: &Tree) -> String {
+        let s = text(content);
+        let s = s.trim();
+        if s == self.name {
+            "\n.Nm\n".to_string()
+        } else if let Some((name, section)) = cross_reference(s) {
+            format!("\n.Xr {name} {section}\n")
+        } else if s.starts_with('-') {
+            let flags: Vec<String> = s.split(", ").map(flag_or_argument).collect();
+            format!("\n.{}\n", flags.join(" , "))
+        } else {
+            format!("\n.Ql {}\n", arguments(s))
+        }
+    }
+
+    /// renders the usage in the SYNOPSIS section: inline code is the name of the
+    /// command, brackets make options, and emphasized words flags, if they start
+    /// with a dash, and arguments otherwise
+    fn render_synopsis(&mut self, tree: Tree) -> String {
+        match tree {
+            Tree::InlineCode(t, _) => {
+                self.options = 0;
+                self.dash = false;
+                format!("\n.Nm {}", arguments(&text(&t)))
+            }
+            Tree::Bold(t, _) | Tree::Italic(t, _) | Tree::BoldItalic(t, _) => {
+                self.render_synopsis_words(&text(&t), true)
+            }
+            Tree::Block(children, _) | Tree::Inline(children, _) => children
+                .into_iter()
+                .map(|child| self.render_synopsis(child))
+                .collect(),
+            Tree::Paragraph(_) | Tree::LineBreak(_) => "\n".to_string(),
+            // options look like references to links, when the links processor did not run
+            Tree::LinkRef(_, t, suffix, _) => {
+                let mut mdoc = self.render_synopsis_words("[", false);
+                mdoc.push_str(&self.render_synopsis(*t));
+                mdoc.push_str(&self.render_synopsis_words(&format!("]{suffix}"), false));
+                mdoc
+            }
+            tree @ (Tree::Literal(..)
+            | Tree::EscapeLit(..)
+            | Tree::Escaped(..)
+            | Tree::EnDash(_)
+            | Tree::EmDash(_)) => self.render_synopsis_words(&text(&tree), false),
+            tree => {
+                self.section = Section::Other;
+                let mdoc = self.render(tree);
+                self.section = Section::Synopsis;
+                mdoc
+            }
+        }
+    }
+
+    fn render_synopsis_words(&mut self, s: &str, emphasized: bool) -> String {
+        let mut mdoc = String::new();
+        let mut word = String::new();
+        let mut after_bar = false;
+        for c in s.chars().chain([' ']) {
+            if !matches!(c, '[' | ']' | '|') && !c.is_whitespace() {
+                word.push(c);
+                continue;
+            }
+            if word == "-" {
+                self.dash = true;
+            } else if !word.is_empty() {
+                let word = if std::mem::take(&mut self.dash) {
+                    format!("-{word}")
+                } else {
+                    word.clone()
+                };
+                let argument = if emphasized || word.starts_with('-') {
+                    flag_or_argument(&word)
+                } else {
+                    format!("Ar {}", arguments(&word))
+                };
+                if self.options > 0 || after_bar {
+                    mdoc.push_str(&format!(" {argument}"));
+                } else {
+                    mdoc.push_str(&format!("\n.{argument}"));
+                }
+                after_bar = false;
+            }
+            word.clear();
+            match c {
+                '[' if self.options == 0 => mdoc.push_str("\n.Op"),
+                '[' => mdoc.push_str(" Oo"),
+                ']' if self.options > 1 => mdoc.push_str(" Oc"),
+                '|' => {
+                    mdoc.push_str(" |");
+                    after_bar = true;
+                }
+                _ => (),
+            }
+            match c {
+                '[' => self.options += 1,
+                ']' => self.options = self.options.saturating_sub(1),
+                _ => (),
+            }
+        }
+        mdoc
+    }
+
+    /// renders a list. bullet lists whose items all start with inline code, like
+    /// the options of a command, are tagged with it
+    fn render_list(&mut self, items: Tree, kind: ListKind) -> String {
+        let children = match items {
+            Tree::Block(children, _) => children,
+            item => vec![item],
+        };
+        let tagged = matches!(kind, ListKind::Bullet(_))
+            && children.iter().all(|child| match child {
+                Tree::ListItem(content, _, _) => {
+                    matches!(first(content), Some(Tree::InlineCode(..)) | None)
+                }
+                _ => true,
+            });
+        let head = match kind {
+            // mdoc always numbers from 1, there is no way to pass a start number
+            ListKind::Ordered(..) => ".Bl -enum",
+            ListKind::Bullet(_) if tagged => ".Bl -tag -width Ds",
+            ListKind::Bullet(_) => ".Bl -bullet",
+        };
+        self.lists.push(kind);
+        let mut mdoc = format!("\n{head}\n");
+        for child in children {
+            match child {
+                Tree::ListItem(content, _, _) => {
+                    let mut content = match *content {
+                        Tree::Block(children, _) | Tree::Inline(children, _) => children,
+                        Tree::Empty(_) => continue,
+                        content => vec![content],
+                    };
+                    let tag = if tagged && !content.is_empty() {
+                        let code = content.remove(0);
+                        self.render_code(&code).trim().replacen(".Ql", "Ql", 1)
+                    } else {
+                        String::new()
+                    };
+                    let tag = tag.strip_prefix('.').unwrap_or(&tag).to_string();
+                    let content = self.render_nested(crate::syntax::block(content));
+                    mdoc.push_str(&format!(".It {tag}\n{content}\n"));
+                }
+                child => {
+                    let nested = self.render_nested(child);
+                    mdoc.push_str(&nested);
+                    mdoc.push('\n');
+                }
+            }
+        }
+        self.lists.pop();
+        mdoc.push_str(".El\n");
+        mdoc
+    }
+
+    /// renders a table as a list of columns, with a bold header row
+    fn render_table(&mut self, header: Tree, rows: Vec<Tree>, caption: Tree) -> String {
+        let cells = |row: &Tree| match row {
+            Tree::TableRow(cells, _) => cells.iter().map(|c| text(c).trim().to_string()).collect(),
+            _ => Vec::new(),
+        };
+        let header: Vec<String> = cells(&header);
+        let rows: Vec<Vec<String>> = rows.iter().map(cells).collect();
+        let widths: Vec<String> = (0..header.len())
+            .map(|i| {
+                let widest = rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .chain(header.get(i))
+                    .max_by_key(|cell| cell.chars().count())
+                    .cloned()
+                    .unwrap_or_default();
+                format!("\"{}\"", widest.replace('"', "\\(dq"))
+            })
+            .collect();
+        let mut mdoc = format!("\n.Bl -column {}\n", widths.join(" "));
+        let header: Vec<String> = header
+            .iter()
+            .map(|cell| format!("Sy {}", arguments(cell)))
+            .collect();
+        mdoc.push_str(&format!(".It {}\n", header.join(" Ta ")));
+        for row in rows {
+            let row: Vec<String> = row.iter().map(|cell| arguments(cell)).collect();
+            mdoc.push_str(&format!(".It {}\n", row.join(" Ta ")));
+        }
+        mdoc.push_str(".El\n");
+        if !matches!(caption, Tree::Empty(_)) {
+            mdoc.push_str(&format!(".Pp\n{}\n", text(&caption).trim()));
+        }
+        mdoc
+    }
+}
+
+/// the first node of a list item
+fn first(content: &Tree) -> Option<&Tree> {
+    match content {
+        Tree::Block(children, _) | Tree::Inline(children, _) => children.first(),
+        Tree::Empty(_) => None,
+        content => Some(content),
     }
 }
 
 impl Render for ManDocRenderer {
-    fn render(&mut self, tree: crate::syntax::Tree) -> String {
+    fn render(&mut self, tree: Tree) -> String {
+        match self.section {
+            // the text of the NAME section is collected, see `end_section`
+            Section::Name if !matches!(tree, Tree::Heading(..) | Tree::Block(..)) => {
+                self.name_text.push_str(&text(&tree));
+                return String::new();
+            }
+            Section::Synopsis if !matches!(tree, Tree::Heading(..)) => {
+                return self.render_synopsis(tree);
+            }
+            _ => (),
+        }
         match tree {
-            crate::syntax::Tree::Document(_, t) => format!(".Dd $Mdocdate$\n{}", self.render(*t)),
-            crate::syntax::Tree::Paragraph() => ".Pp\n".to_owned(),
-            crate::syntax::Tree::PreformattedLiteral(_) => todo!(),
-            crate::syntax::Tree::Literal(l) => l,
-            crate::syntax::Tree::EscapeLit(s) => match s.as_str() {
-                "." => "\\&.".to_string(),
+            Tree::Document(_, t, _) => {
+                self.anchors = anchors(&t);
+                let mut mdoc = self.render_sequence(vec![*t]);
+                mdoc.push('\n');
+                mdoc.push_str(&self.end_section());
+                if !self.prologue_written {
+                    mdoc.insert_str(0, &self.render_prologue("untitled", None, None, None));
+                }
+                // a final line break is added on output
+                tidy(&mdoc).trim_end().to_string()
+            }
+            Tree::Paragraph(_) => {
+                self.paragraph = true;
+                String::new()
+            }
+            Tree::PreformattedLiteral(s, _) => super::escape_code(&s),
+            Tree::Literal(l, _) => self.render_text(&l),
+            Tree::EscapeLit(s, _) => match s.as_str() {
+                "\\" => "\\[rs]".to_string(),
                 _ => s,
             },
-            crate::syntax::Tree::DropCap(c, _) => format!("{}", c as char),
-            crate::syntax::Tree::Color(_) => "".to_owned(),
-            crate::syntax::Tree::ChapterMark(_) => "".to_owned(),
-            crate::syntax::Tree::Heading(t, level, _) => match level {
-                0 => {
-                    let title = self.render(*t);
-                    format!(
-                        ".Dt {} 7\n.Os\n.Sh Title\n.Nm {}\n",
-                        title.to_uppercase(),
-                        title
-                    ) // TODO section number configurable
+            Tree::Escaped(c, _) => super::escaped_char(c),
+            // a terminal has no drop caps, colors or margins
+            Tree::DropCap(c, _, _) => format!("{}", c as char),
+            Tree::Color(_, _) => "".to_owned(),
+            Tree::ChapterMark(_, _) => "".to_owned(),
+            Tree::Heading(t, level, _, _) => match level {
+                0 => self.render_title(&t),
+                1 | 2 => self.render_section_heading(&t, level),
+                // mdoc has two levels of headings only
+                _ => {
+                    self.paragraph = true;
+                    format!("\n.Sy {}\n", arguments(text(&t).trim()))
                 }
-                1 => {
-                    let section_header_name = self.render(*t);
-                    if self.in_title {
-                        // the first level 1 (read ##) header ends the title section
-                        self.in_title = false;
-                        self.title_open = false;
-                        format!(".Nd {section_header_name}")
-                    } else {
-                        format!(".Sh {section_header_name}")
-                    }
-                }
-                2 => format!(".Ss {}", self.render(*t)),
-                _ => self.render(*t),
             },
-            crate::syntax::Tree::Bold(t) => format!("\\c\n.B {}\\c\n", self.render(*t)),
-            crate::syntax::Tree::Italic(t) => {
-                let italic_text = self.render(*t);
-                format!("\\c\n.I {italic_text}\\c\n")
-            }
-            crate::syntax::Tree::BoldItalic(_) => todo!(),
-            crate::syntax::Tree::SmallCaps(_) => todo!(),
-            crate::syntax::Tree::CodeBlock(_, _) => todo!(),
-            crate::syntax::Tree::InlineCode(t) => {
-                if self.in_title {
-                    let mut sy_closing = "";
-                    if self.title_open {
-                        sy_closing = ".YS\n";
-                    }
-                    self.title_open = true;
-                    format!("{}.SY {}\n", sy_closing, self.render(*t))
+            Tree::Bold(t, _) | Tree::BoldItalic(t, _) => self.render_emphasis("Sy", &t),
+            Tree::Italic(t, _) => self.render_emphasis("Em", &t),
+            // there are no small caps
+            Tree::SmallCaps(t, _) => self.render(*t),
+            Tree::CodeBlock(_, t, _) => format!(
+                "\n.Bd -literal -offset indent\n{}\n.Ed\n",
+                super::escape_code(&text(&t)).trim_end_matches('\n')
+            ),
+            Tree::InlineCode(t, _) => self.render_code(&t),
+            Tree::Quote(t, _) => format!("\n.Dq {}\n", arguments(&text(&t))),
+            Tree::Footnote(t, _) | Tree::RightSidenote(t, _) => self.render_note(*t),
+            Tree::HyperRef(t, url, _) => {
+                let url = text(&url);
+                let link_text = text(&t);
+                if link_text.trim() == url.trim() {
+                    format!("\n.Lk {}\n", url.trim())
                 } else {
-                    self.render(*t)
+                    format!("\n.Lk {} {}\n", url.trim(), arguments(&link_text))
                 }
             }
-            crate::syntax::Tree::Quote(_) => todo!(),
-            crate::syntax::Tree::Footnote(_) => todo!(),
-            crate::syntax::Tree::RightSidenote(_) => todo!(),
-            crate::syntax::Tree::HyperRef(_, _) => todo!(),
-            crate::syntax::Tree::DocRef(_, _) => todo!(),
-            crate::syntax::Tree::Cat(t1, t2) => format!("{}{}", self.render(*t1), self.render(*t2)),
-            crate::syntax::Tree::List(t, _) => format!(".\n{}", self.render(*t)),
-            crate::syntax::Tree::ListItem(t, _) => format!(".TP\n.B {}", self.render(*t)),
-            crate::syntax::Tree::MetaDataBlock(_) => todo!(),
-            crate::syntax::Tree::MetaDataItem(_, _) => todo!(),
-            crate::syntax::Tree::ImageSizeSpec(_, _) => todo!(),
-            crate::syntax::Tree::Image(_, _, _) => todo!(),
-            crate::syntax::Tree::LineBreak() => {
-                if self.in_title {
-                    "".to_owned()
-                } else {
-                    "\n".to_owned()
-                }
+            Tree::DocRef(target, t, _) => match self.anchors.get(&target) {
+                Some(title) => format!("\n.Sx {}\n", arguments(title)),
+                None => self.render(*t),
+            },
+            Tree::Block(children, _) | Tree::Inline(children, _) => self.render_sequence(children),
+            Tree::List(t, _, kind, _) => self.render_list(*t, kind),
+            Tree::ListItem(t, _, _) => format!("\n.It\n{}\n", self.render_nested(*t)),
+            Tree::MetaDataBlock(t, _) => self.render_meta_data(&t),
+            Tree::MetaDataItem(_, _, _) => "".to_owned(),
+            Tree::ImageSizeSpec(_, _, _) => "".to_owned(),
+            // a terminal cannot show images, their caption stands in for them
+            Tree::Image(caption, path, _, _) => {
+                let caption = match text(&caption).trim() {
+                    "" => text(&path),
+                    caption => caption.to_string(),
+                };
+                format!("\n.Bd -centered\n\\&[{}]\n.Ed\n", caption.trim())
+            }
+            Tree::LineBreak(_) => "\n".to_owned(),
+            Tree::VSpace(_) => "".to_owned(),
+            Tree::Empty(_) => "".to_owned(),
+            Tree::EmDash(_) => "\\(em".to_owned(),
+            Tree::EnDash(_) => "\\(en".to_owned(),
+            Tree::Table(_, header, rows, caption, _) => self.render_table(*header, rows, *caption),
+            Tree::TableRow(cells, _) => {
+                let cells: Vec<String> = cells.iter().map(|c| arguments(&text(c))).collect();
+                format!("\n.It {}\n", cells.join(" Ta "))
             }
-            crate::syntax::Tree::VSpace() => "".to_owned(),
-            crate::syntax::Tree::Empty() => "".to_owned(),
-            crate::syntax::Tree::EmDash => "\\(em".to_owned(),
-            crate::syntax::Tree::EnDash => "\\(en".to_owned(),
+            Tree::FootnoteRef(label, _) => format!("[^{label}]"),
+            Tree::FootnoteDef(_, _, _) => "".to_owned(),
+            Tree::LinkRef(_, t, suffix, _) => format!("[{}]{suffix}", text(&t)),
+            Tree::LinkDef(_, _, _, _) => "".to_owned(),
+            Tree::Include(_, _) => "".to_owned(),
+            Tree::BlockQuote(t, _) => format!(
+                "\n.Bd -ragged -offset indent\n{}\n.Ed\n",
+                self.render_nested(*t).trim_matches('\n')
+            ),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mdoc(input: &str) -> String {
+        new().render(crate::parse("", input))
+    }
+
+    #[test]
+    fn meta_data_makes_the_prologue() {
+        let page = mdoc("---\nname: mato\nsection: 7\ndate: 2024-11-29\nos: BSD\n---\n## NAME\n\nmato - transform markdown\n");
+        assert!(
+            page.starts_with(".Dd November 29, 2024\n.Dt MATO 7\n.Os BSD\n"),
+            "{page}"
+        );
+        assert!(
+            page.contains(".Sh NAME\n.Nm mato\n.Nd transform markdown"),
+            "{page}"
+        );
+    }
+
+    #[test]
+    fn titles_give_the_page_name_only() {
+        let page = mdoc("---\ntitle: \"A & B <c>\"\n---\n\nText.\n");
+        assert!(page.contains(".Dt A 1\n"), "{page}");
+        assert!(page.contains(".Nm A\n"), "{page}");
+        assert!(!page.contains("<c>"), "{page}");
+        let page = mdoc("---\ntitle: mato - transform markdown\n---\n\nText.\n");
+        assert!(
+            page.contains(".Dt MATO 1\n.Os\n.Sh NAME\n.Nm mato\n.Nd transform markdown\n"),
+            "{page}"
+        );
+    }
+
+    #[test]
+    fn synopsis_uses_semantic_macros() {
+        let page = mdoc("# mato 1\n\n## SYNOPSIS\n\n`mato` [-*v*] [-*T* _device_] _file_\n");
+        assert!(
+            page.contains(".Sh SYNOPSIS\n.Nm mato\n.Op Fl v\n.Op Fl T Ar device\n.Ar file"),
+            "{page}"
+        );
+        assert!(!page.contains(".SY"));
+    }
+
+    #[test]
+    fn options_are_a_tagged_list() {
+        let page =
+            mdoc("# mato 1\n\n## OPTIONS\n\n- `-v, --verbose`\n  Talk more.\n\n- `-h`\n  Help.\n");
+        assert!(
+            page.contains(
+                ".Bl -tag -width Ds\n.It Fl v , Fl -verbose\nTalk more.\n.It Fl h\nHelp.\n.El"
+            ),
+            "{page}"
+        );
+    }
+
+    #[test]
+    fn lists_code_and_links() {
+        let page = mdoc("# mato 1\n\n## DESCRIPTION\n\n1. one\n2. two\n\n* a\n* b\n\n```\n.x\n```\n\nsee [site](https://a.org) and groff(1).\n");
+        assert!(
+            page.contains(".Bl -enum\n.It\none\n.It\ntwo\n.El"),
+            "{page}"
+        );
+        assert!(page.contains(".Bl -bullet\n.It\na\n.It\nb\n.El"), "{page}");
+        assert!(
+            page.contains(".Bd -literal -offset indent\n\\&.x\n.Ed"),
+            "{page}"
+        );
+        assert!(page.contains(".Lk https://a.org site"), "{page}");
+        assert!(page.ends_with("\nand\n.Xr groff 1 ."), "{page}");
+    }
+
+    #[test]
+    fn pages_start_with_a_name_section() {
+        let page = mdoc("# mato - transform markdown\n\nText.\n");
+        assert_eq!(
+            page,
+            ".Dd $Mdocdate$\n.Dt MATO 1\n.Os\n.Sh NAME\n.Nm mato\n.Nd transform markdown\n.Sh DESCRIPTION\nText."
+        );
+    }
+
+    #[test]
+    fn text_lines_never_start_with_a_dot() {
+        let page = mdoc("# mato 1\n\n## DESCRIPTION\n\n.starts with dot\n\nsome text\n.and another\n");
+        assert!(
+            page.contains(".Sh DESCRIPTION\n\\&.starts with dot\n"),
+            "{page}"
+        );
+        assert!(page.contains("some text\n\\&.and another"), "{page}");
+    }
+
+    #[test]
+    fn notes_end_the_section() {
+        let page = mdoc("# mato 1\n\n## ONE\n\na^(first) b\n\n## TWO\n\nc^(second)\n");
+        let first = page.find(".It \\&[1]\nfirst").unwrap();
+        let two = page.find(".Sh TWO").unwrap();
+        let second = page.find(".It \\&[2]\nsecond").unwrap();
+        assert!(first < two && two < second, "{page}");
+    }
+}
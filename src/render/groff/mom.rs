@@ -3,28 +3,37 @@ use std::collections::HashMap;
 use std::fmt::Display;
 
 use crate::config::Config;
-use crate::{locate_and_load_preamble, m_dbg, Render};
-use crate::{syntax::DocType, Tree};
+use crate::{locate_and_load_preamble, m_dbg, Render, Result};
+use crate::{syntax::Alignment, syntax::DocType, syntax::ListKind, syntax::Span, Tree};
 
 /// empty struct to attach Renderer implementation on
 pub struct Renderer<'a> {
     ctx: HashMap<String, String>,
     document_started: bool,
     doc_type: DocType,
-    default_preamble: String,
+    /// the preamble found for the source file, or the default one
+    preamble: String,
     config: &'a Config,
+    /// mom cannot nest block quotes. quotes directly nested in others are set one after
+    /// another with growing indents, those nested deeper, like in a list, as paragraphs
+    in_block_quote: bool,
+    /// mom cannot nest footnotes either, nested ones are set in parentheses
+    in_footnote: bool,
 }
 
-pub fn new(config: &Config) -> Renderer<'_> {
-    let default_mom_preamble = include_str!("default-preamble.mom").to_string();
+/// creates a renderer, reading the preamble to be used
+pub fn new(config: &Config) -> Result<Renderer<'_>> {
+    let default_mom_preamble = include_str!("default-preamble.mom");
 
-    Renderer {
+    Ok(Renderer {
         ctx: HashMap::new(),
         document_started: false,
         doc_type: DocType::DEFAULT,
-        default_preamble: default_mom_preamble,
+        preamble: locate_and_load_preamble(config, PREAMBLE_FILE_NAME, default_mom_preamble)?,
         config,
-    }
+        in_block_quote: false,
+        in_footnote: false,
+    })
 }
 
 const PREAMBLE_FILE_NAME: &str = "preamble.mom";
@@ -53,6 +62,18 @@ impl Renderer<'_> {
     /// need to emulate this by passing in the parent style as a
     /// parameter, `parent_format`.
     fn render_with_parent_format(&mut self, exp: Tree, parent_format: &str) -> String {
+        match exp {
+            // sequences are rendered here, so that rendering their children does not
+            // go through the big match below, which takes a lot of stack space per call
+            Tree::Block(children, _) | Tree::Inline(children, _) => children
+                .into_iter()
+                .map(|child| self.render_with_parent_format(child, parent_format))
+                .collect(),
+            _ => self.render_node(exp, parent_format),
+        }
+    }
+
+    fn render_node(&mut self, exp: Tree, parent_format: &str) -> String {
         // abbreviates call to self.render_with_default_format(...)
         macro_rules! rnd {
             ($( $args:expr ), *) => {
@@ -68,20 +89,12 @@ impl Renderer<'_> {
         }
 
         match exp {
-            Tree::Document(dt, be) => {
+            Tree::Document(dt, be, _) => {
                 self.doc_type = dt.clone();
                 let mut result = format!("{dt}");
 
                 if !self.config.skip_preamble {
-                    result = format!(
-                        "{}\n{}",
-                        result,
-                        locate_and_load_preamble(
-                            self.config,
-                            PREAMBLE_FILE_NAME,
-                            &self.default_preamble
-                        )
-                    );
+                    result = format!("{}\n{}", result, self.preamble);
                 }
 
                 for (key, value) in self.ctx.clone().into_iter() {
@@ -132,31 +145,34 @@ impl Renderer<'_> {
                 };
                 format!("{}{}{}", result, body, trailer)
             }
-            Tree::Paragraph() => ".PP\n".to_string(),
-            Tree::LineBreak() => "\n".to_string(),
-            Tree::Literal(s) | Tree::PreformattedLiteral(s) => s,
-            Tree::EscapeLit(s) => match s.as_str() {
+            Tree::Paragraph(_) => ".PP\n".to_string(),
+            Tree::LineBreak(_) => "\n".to_string(),
+            Tree::Literal(s, _) | Tree::PreformattedLiteral(s, _) => s,
+            Tree::EscapeLit(s, _) => match s.as_str() {
                 "." => "\\&.".to_string(),
+                "\\" => "\\[rs]".to_string(),
                 _ => s,
             },
-            Tree::Bold(b_exp) => {
+            Tree::Escaped(c, _) => super::escaped_char(c),
+            Tree::Bold(b_exp, _) => {
                 format!("\\*[BD]{}\\*[{}]", rnd_pf!(*b_exp, "BD"), parent_format)
             }
-            Tree::SmallCaps(be) => rnd_pf!(*be, parent_format),
-            Tree::Italic(b_exp) => {
+            Tree::SmallCaps(be, _) => rnd_pf!(*be, parent_format),
+            Tree::Italic(b_exp, _) => {
                 format!("\\*[IT]{}\\*[{}]", rnd_pf!(*b_exp, "IT"), parent_format)
             }
-            Tree::BoldItalic(be) => {
+            Tree::BoldItalic(be, _) => {
                 format!("\\*[BDI]{}\\*[{}]", rnd_pf!(*be, "BDI"), parent_format)
             }
             // Currently there seems to be a bug: https://savannah.gnu.org/bugs/index.php?64561
             // Exp::CodeBlock(b_exp) => format!(".QUOTE_STYLE INDENT 1\n.QUOTE\n.CODE\n.BOX OUTLINED black INSET 18p\n{}.BOX OFF\n.QUOTE OFF", self.render(*b_exp)),
-            Tree::CodeBlock(_b1, b2) => format!(
+            Tree::CodeBlock(_b1, b2, _) => format!(
                 ".QUOTE_STYLE INDENT 1\n.QUOTE\n.CODE\n{}.QUOTE OFF",
                 rnd!(*b2)
             ),
-            Tree::InlineCode(b_exp) => format!("\\*[CODE]{}\\*[CODE OFF]", rnd!(*b_exp)),
-            Tree::Heading(b_exp, level, name) => {
+            Tree::InlineCode(b_exp, _) => format!("\\*[CODE]{}\\*[CODE OFF]", rnd!(*b_exp)),
+            Tree::Heading(mut b_exp, level, name, _) => {
+                let footnotes = self.render_heading_footnotes(&mut b_exp);
                 let heading_out = match self.doc_type {
                     DocType::CHAPTER => {
                         if level == 0 {
@@ -235,15 +251,15 @@ impl Renderer<'_> {
                         }
                     }
                 };
-                heading_out
+                format!("{heading_out}{footnotes}")
             }
-            Tree::Color(b_exp) => {
+            Tree::Color(b_exp, _) => {
                 format!(".COLOR {}\n", rnd!(*b_exp))
             }
-            Tree::ChapterMark(b_exp) => {
+            Tree::ChapterMark(b_exp, _) => {
                 let content = rnd!(*b_exp);
                 let watermark_content = if let Some(stripped) = content.strip_prefix(".COLOR ") {
-                    stripped.splitn(2, '\n').nth(1).unwrap_or("").to_string()
+                    stripped.split_once('\n').map_or("", |(_, rest)| rest).to_string()
                 } else {
                     content.clone()
                 };
@@ -252,46 +268,38 @@ impl Renderer<'_> {
                     content, watermark_content
                 )
             }
-            Tree::RightSidenote(b_exp) => {
+            Tree::RightSidenote(b_exp, _) => {
                 format!("\n.MN RIGHT\n.PT_SIZE -2\n{}\n.MN OFF\n", rnd!(*b_exp))
             }
-            Tree::Quote(b_exp) => format!("\"{}\"", rnd!(*b_exp)),
-            Tree::Footnote(b_exp) => {
-                format!("\\c\n.FOOTNOTE\n{}\n.FOOTNOTE END\n", rnd!(*b_exp))
-            }
-            Tree::HyperRef(b_exp1, b_exp2) => {
+            Tree::Quote(b_exp, _) => format!("\"{}\"", rnd!(*b_exp)),
+            Tree::Footnote(b_exp, _) => self.render_footnote(*b_exp),
+            Tree::HyperRef(b_exp1, b_exp2, _) => {
                 format!(
                     "\\c\n.PDF_WWW_LINK {} \"{}\"\\c\n",
                     rnd!(*b_exp2),
                     rnd!(*b_exp1)
                 )
             }
-            Tree::DocRef(target, link_text) => {
+            Tree::DocRef(target, link_text, _) => {
                 format!("\\c\n.PDF_LINK {} \"{}\"\\c\n", target, rnd!(*link_text))
             }
-            Tree::Cat(b_exp1, b_exp2) => {
-                format!(
-                    "{}{}",
-                    rnd_pf!(*b_exp1, parent_format),
-                    rnd_pf!(*b_exp2, parent_format)
-                )
-            }
-            Tree::Empty() => String::new(),
-            Tree::List(b_exp, _) => {
-                format!(".LIST\n.SHIFT_LIST 18p\n.PARA_INDENT 0\n{}.LIST OFF\n", rnd!(*b_exp))
+            Tree::Block(..) | Tree::Inline(..) => {
+                unreachable!("handled by render_with_parent_format")
             }
-            Tree::ListItem(b_exp, _) => match *b_exp {
-                Tree::Empty() => String::new(),
+            Tree::Empty(_) => String::new(),
+            Tree::List(b_exp, _, kind, _) => self.render_list(*b_exp, kind),
+            Tree::ListItem(b_exp, _, _) => match *b_exp {
+                Tree::Empty(_) => String::new(),
                 _ => format!(".ITEM\n.PP\n{}\n", rnd!(*b_exp)),
             },
-            Tree::MetaDataBlock(b_exp) => rnd!(*b_exp),
-            Tree::MetaDataItem(key, value) => {
+            Tree::MetaDataBlock(b_exp, _) => rnd!(*b_exp),
+            Tree::MetaDataItem(key, value, _) => {
                 format!(".{} {}\n", key.to_uppercase().replace(' ', "_"), value)
             }
-            Tree::ImageSizeSpec(b_exp1, b_exp2) => {
+            Tree::ImageSizeSpec(b_exp1, b_exp2, _) => {
                 format!("{}p {}p", rnd!(*b_exp1), rnd!(*b_exp2))
             }
-            Tree::Image(b_exp, path, size_spec) => {
+            Tree::Image(b_exp, path, size_spec, _) => {
                 format!(
                     ".PDF_IMAGE {} {} LABEL \"{}\"",
                     rnd!(*path),
@@ -299,12 +307,185 @@ impl Renderer<'_> {
                     rnd!(*b_exp)
                 )
             }
-            Tree::VSpace() => "\n.SP 1v".to_owned(),
-            Tree::DropCap(c, l) => format!("\n.DROPCAP {} {}\n", c as char, l),
-            Tree::EmDash => "\\(em".to_owned(),
-            Tree::EnDash => "\\(en".to_owned(),
+            Tree::VSpace(_) => "\n.SP 1v".to_owned(),
+            Tree::DropCap(c, l, _) => format!("\n.DROPCAP {} {}\n", c as char, l),
+            Tree::EmDash(_) => "\\(em".to_owned(),
+            Tree::EnDash(_) => "\\(en".to_owned(),
+            Tree::Table(alignments, header, rows, caption, _) => {
+                self.render_table(&alignments, *header, rows, *caption)
+            }
+            Tree::TableRow(cells, _) => self.render_table_row(cells, parent_format),
+            Tree::BlockQuote(b_exp, _) => self.render_block_quote(*b_exp),
+            Tree::FootnoteRef(label, _) => format!("[^{label}]"),
+            Tree::FootnoteDef(..) => String::new(),
+            Tree::LinkRef(_, b_exp, suffix, _) => format!("[{}]{suffix}", rnd!(*b_exp)),
+            Tree::LinkDef(..) => String::new(),
+            Tree::Include(..) => String::new(),
+        }
+    }
+    fn render_footnote(&mut self, content: Tree) -> String {
+        if self.in_footnote {
+            return format!(" ({})", self.render_with_default_format(content));
+        }
+        self.in_footnote = true;
+        let content = self.render_with_default_format(content);
+        self.in_footnote = false;
+        format!("\\c\n.FOOTNOTE\n{content}\n.FOOTNOTE END\n")
+    }
+    /// takes the footnotes out of a heading, which cannot contain them, and renders them
+    /// to be placed behind it
+    fn render_heading_footnotes(&mut self, heading: &mut Tree) -> String {
+        if let Tree::Footnote(..) = heading {
+            let Tree::Footnote(content, _) = std::mem::replace(heading, Tree::Empty(Span::default()))
+            else {
+                unreachable!()
+            };
+            let content = self.render_with_default_format(*content);
+            return format!("\n.FOOTNOTE\n{content}\n.FOOTNOTE END");
+        }
+        heading
+            .children_mut()
+            .into_iter()
+            .map(|child| self.render_heading_footnotes(child))
+            .collect()
+    }
+    /// renders a block quote. a nested quote ends the outer one and is set as a quote of its
+    /// own with a larger `.QUOTE_INDENT`, the outer one continues behind it. each level is
+    /// indented by mom's default of three paragraph indents.
+    fn render_block_quote(&mut self, quote: Tree) -> String {
+        if self.in_block_quote {
+            let quote = self.render_with_default_format(quote);
+            return format!(".PP\n{}\n", quote.trim_end_matches('\n'));
+        }
+        self.in_block_quote = true;
+        let mut segments = vec![];
+        self.block_quote_segments(quote, 1, &mut segments);
+        self.in_block_quote = false;
+        let mut out = String::new();
+        let mut indent = 1;
+        for (depth, text) in segments {
+            let mut text = text.trim_matches('\n');
+            while let Some(rest) = text.strip_prefix(".PP\n") {
+                text = rest;
+            }
+            while let Some(rest) = text.strip_suffix("\n.PP") {
+                text = rest;
+            }
+            if text.is_empty() || text == ".PP" {
+                continue;
+            }
+            if depth != indent {
+                out.push_str(&format!(".QUOTE_INDENT {}\n", 3 * depth));
+                indent = depth;
+            }
+            out.push_str(&format!(".BLOCKQUOTE\n{text}\n.BLOCKQUOTE OFF\n"));
         }
+        if indent != 1 {
+            out.push_str(".QUOTE_INDENT 3\n");
+        }
+        out
+    }
+    /// splits `quote` into the runs of content around the quotes nested in it, each with the
+    /// depth it is set at
+    fn block_quote_segments(
+        &mut self,
+        quote: Tree,
+        depth: usize,
+        segments: &mut Vec<(usize, String)>,
+    ) {
+        let children = match quote {
+            Tree::Block(children, _) => children,
+            quote => vec![quote],
+        };
+        let mut text = String::new();
+        for child in children {
+            if let Tree::BlockQuote(nested, _) = child {
+                segments.push((depth, std::mem::take(&mut text)));
+                self.block_quote_segments(*nested, depth + 1, segments);
+            } else {
+                text.push_str(&self.render_with_default_format(child));
+            }
+        }
+        segments.push((depth, text));
+    }
+    /// renders a list, ordered ones numbered with digits from their start number on
+    fn render_list(&mut self, items: Tree, kind: ListKind) -> String {
+        let (list_type, reset) = match kind {
+            ListKind::Bullet(_) => (String::new(), String::new()),
+            ListKind::Ordered(1, delimiter) => (format!(" DIGIT {delimiter}"), String::new()),
+            ListKind::Ordered(start, delimiter) => (
+                format!(" DIGIT {delimiter}"),
+                format!(".RESET_LIST {start}\n"),
+            ),
+        };
+        format!(
+            ".LIST{list_type}\n.SHIFT_LIST 18p\n.PARA_INDENT 0\n{reset}{}.LIST OFF\n",
+            self.render_with_default_format(items)
+        )
     }
+    /// renders a table as `tbl` source with a bold header row, separated from the body by a rule
+    fn render_table(
+        &mut self,
+        alignments: &[Alignment],
+        header: Tree,
+        rows: Vec<Tree>,
+        caption: Tree,
+    ) -> String {
+        let caption = match caption {
+            Tree::Empty(_) => String::new(),
+            caption => format!(
+                " CAPTION \"{}\"",
+                self.render_with_default_format(caption)
+                    .replace('"', "\\(dq")
+                    .replace('\n', " ")
+            ),
+        };
+        let columns: Vec<&str> = alignments
+            .iter()
+            .map(|alignment| match alignment {
+                Alignment::Center => "c",
+                Alignment::Right => "r",
+                Alignment::Left | Alignment::Default => "l",
+            })
+            .collect();
+        let header = self.render_with_parent_format(header, "BD");
+        let rows: String = rows
+            .into_iter()
+            .map(|row| self.render_with_default_format(row))
+            .collect();
+        format!(
+            ".TS CENTER{}\n{}.\n{}_\n{}.TE\n",
+            caption,
+            columns.join(" "),
+            header,
+            rows
+        )
+    }
+
+    /// renders the cells of a table row separated by tabs, as `tbl` expects them
+    fn render_table_row(&mut self, cells: Vec<Tree>, parent_format: &str) -> String {
+        let cells: Vec<String> = cells
+            .into_iter()
+            .map(|cell| {
+                let cell = self
+                    .render_with_parent_format(cell, parent_format)
+                    .replace('\t', " ");
+                let cell = if parent_format == "ROM" {
+                    cell
+                } else {
+                    format!("\\*[{parent_format}]{cell}\\*[ROM]")
+                };
+                // cells spanning lines, like links, need to be text blocks
+                if cell.contains('\n') {
+                    format!("T{{\n{cell}\nT}}")
+                } else {
+                    cell
+                }
+            })
+            .collect();
+        format!("{}\n", cells.join("\t"))
+    }
+
     fn render_with_default_format(&mut self, exp: Tree) -> String {
         self.render_with_parent_format(exp, "ROM")
     }
@@ -0,0 +1,756 @@
+//! renders the syntax tree as a self-contained HTML5 page
+//!
+//! the tree has no paragraph nodes enclosing text, `Paragraph` only separates
+//! them, like `.PP` does for groff. so paragraphs are opened by the first text
+//! after a separator or block, and closed by the next one.
+use crate::config::Config;
+use crate::syntax::{visit_children, Alignment, DocType, ListKind, Tree, Visit};
+use crate::{locate_and_load_preamble, Render, Result};
+
+pub(crate) const STYLE_FILE_NAME: &str = "style.css";
+pub(crate) const DEFAULT_STYLE: &str = include_str!("default-style.css");
+
+pub struct Renderer<'a> {
+    config: &'a Config,
+    /// the style sheet found for the source file, or the default one
+    style: String,
+    doc_type: DocType,
+    /// a `<p>` has been opened and not closed yet
+    in_paragraph: bool,
+    /// depth of inline content we are in, where no paragraphs are opened
+    inline: usize,
+    /// a `<section>` of a chapter or slide has been opened
+    in_section: bool,
+    /// rendered footnotes, collected at the end of the page
+    footnotes: Vec<String>,
+    /// footnotes cannot nest, nested ones are set in parentheses
+    in_footnote: bool,
+}
+
+/// creates a renderer, reading the style sheet to be used
+pub fn new(config: &Config) -> Result<Renderer<'_>> {
+    with_default_style(config, DEFAULT_STYLE)
+}
+
+/// creates a renderer, which falls back to `default_style` if no style sheet is found
+pub(crate) fn with_default_style<'a>(
+    config: &'a Config,
+    default_style: &str,
+) -> Result<Renderer<'a>> {
+    Ok(Renderer {
+        config,
+        style: locate_and_load_preamble(config, STYLE_FILE_NAME, default_style)?,
+        doc_type: DocType::DEFAULT,
+        in_paragraph: false,
+        inline: 0,
+        in_section: false,
+        footnotes: Vec::new(),
+        in_footnote: false,
+    })
+}
+
+/// escapes the characters with a meaning in HTML text and attribute values
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// the language tag for a groff hyphenation language
+pub(crate) fn language_tag(lang: &str) -> &str {
+    match lang {
+        "den" => "de",
+        lang => lang,
+    }
+}
+
+/// the `lang` attribute for a groff hyphenation language
+fn language(lang: &str) -> String {
+    match language_tag(lang) {
+        "" => String::new(),
+        tag => format!(" lang=\"{}\"", escape(tag)),
+    }
+}
+
+/// nodes which are text or part of it, and so belong into a paragraph
+fn is_phrasing(tree: &Tree) -> bool {
+    matches!(
+        tree,
+        Tree::Literal(..)
+            | Tree::PreformattedLiteral(..)
+            | Tree::EscapeLit(..)
+            | Tree::Escaped(..)
+            | Tree::DropCap(..)
+            | Tree::ChapterMark(..)
+            | Tree::Bold(..)
+            | Tree::Italic(..)
+            | Tree::BoldItalic(..)
+            | Tree::SmallCaps(..)
+            | Tree::InlineCode(..)
+            | Tree::Quote(..)
+            | Tree::Footnote(..)
+            | Tree::RightSidenote(..)
+            | Tree::HyperRef(..)
+            | Tree::DocRef(..)
+            | Tree::EmDash(..)
+            | Tree::EnDash(..)
+            | Tree::FootnoteRef(..)
+            | Tree::LinkRef(..)
+    )
+}
+
+/// nodes ending the paragraph before them
+fn is_block(tree: &Tree) -> bool {
+    matches!(
+        tree,
+        Tree::Paragraph(..)
+            | Tree::Heading(..)
+            | Tree::CodeBlock(..)
+            | Tree::List(..)
+            | Tree::MetaDataBlock(..)
+            | Tree::Image(..)
+            | Tree::Table(..)
+            | Tree::BlockQuote(..)
+    )
+}
+
+/// the plain text of a tree, for attribute values
+pub(crate) fn text(tree: &Tree) -> String {
+    #[derive(Default)]
+    struct Text(String);
+
+    impl Visit for Text {
+        fn visit(&mut self, tree: &Tree) {
+            match tree {
+                Tree::Literal(s, _) | Tree::EscapeLit(s, _) | Tree::PreformattedLiteral(s, _) => {
+                    self.0.push_str(s)
+                }
+                Tree::Escaped(c, _) => self.0.push(*c),
+                _ => visit_children(self, tree),
+            }
+        }
+    }
+
+    let mut text = Text::default();
+    text.visit(tree);
+    text.0
+}
+
+/// the meta data items of a document, with the quotes groff needs removed
+pub(crate) fn meta_data(tree: &Tree) -> Vec<(String, String)> {
+    #[derive(Default)]
+    struct Items(Vec<(String, String)>);
+
+    impl Visit for Items {
+        fn visit(&mut self, tree: &Tree) {
+            match tree {
+                Tree::MetaDataItem(key, value, _) => self
+                    .0
+                    .push((key.clone(), value.trim_matches('"').to_string())),
+                Tree::MetaDataBlock(..) | Tree::Block(..) | Tree::Document(..) => {
+                    visit_children(self, tree)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut items = Items::default();
+    items.visit(tree);
+    items.0
+}
+
+pub(crate) fn lookup<'m>(meta_data: &'m [(String, String)], key: &str) -> Option<&'m str> {
+    meta_data
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value.as_str())
+}
+
+impl Renderer<'_> {
+    fn render_node(&mut self, tree: Tree) -> String {
+        // groff requests passed through with `//`. text starting with a dot is parsed
+        // into an `EscapeLit`, so these are the only literals doing so
+        if matches!(&tree, Tree::Literal(s, _) if s.starts_with('.')) {
+            return String::new();
+        }
+        if self.inline == 0 && is_phrasing(&tree) {
+            let start = self.open_paragraph(&tree);
+            return format!("{start}{}", self.render_inline(tree));
+        }
+        match tree {
+            Tree::Document(dt, content, _) => self.render_document(dt, *content),
+            Tree::Block(children, _) | Tree::Inline(children, _) => self.render_sequence(children),
+            Tree::Paragraph(_) if self.inline > 0 => "\n".to_string(),
+            Tree::Paragraph(_) => self.close_paragraph(),
+            Tree::LineBreak(_) => "\n".to_string(),
+            Tree::Heading(title, level, name, _) => {
+                let mut html = self.close_paragraph();
+                if level == 0 && matches!(self.doc_type, DocType::CHAPTER | DocType::SLIDES) {
+                    html.push_str(&self.close_section());
+                    self.in_section = true;
+                    let class = format!("{:?}", self.doc_type).to_lowercase();
+                    html.push_str(&format!("<section class=\"{class}\">\n"));
+                }
+                let h = (level + 1).min(6);
+                let id = if name.is_empty() {
+                    String::new()
+                } else {
+                    format!(" id=\"{}\"", escape(&name))
+                };
+                let title = self.render_inline(*title);
+                format!("{html}<h{h}{id}>{}</h{h}>\n", title.trim_end())
+            }
+            Tree::CodeBlock(code_type, content, _) => {
+                let close = self.close_paragraph();
+                let class = match text(&code_type).as_str() {
+                    "" => String::new(),
+                    code_type => format!(" class=\"language-{}\"", escape(code_type)),
+                };
+                format!(
+                    "{close}<pre><code{class}>{}</code></pre>\n",
+                    escape(&text(&content))
+                )
+            }
+            Tree::List(items, _, kind, _) => {
+                let close = self.close_paragraph();
+                format!("{close}{}", self.render_list(*items, kind))
+            }
+            Tree::ListItem(content, _, _) => match *content {
+                Tree::Empty(_) => String::new(),
+                content => format!("<li>{}</li>\n", self.render_inline(content)),
+            },
+            Tree::MetaDataBlock(block, _) => {
+                let close = self.close_paragraph();
+                format!("{close}{}", render_header(&meta_data(&block)))
+            }
+            Tree::Image(caption, path, size_spec, _) if self.inline > 0 => {
+                self.render_image(&caption, &path, &size_spec)
+            }
+            Tree::Image(caption, path, size_spec, _) => {
+                let close = self.close_paragraph();
+                let image = self.render_image(&caption, &path, &size_spec);
+                let caption = match *caption {
+                    Tree::Empty(_) => String::new(),
+                    caption => format!("<figcaption>{}</figcaption>", self.render_inline(caption)),
+                };
+                format!("{close}<figure>{image}{caption}</figure>\n")
+            }
+            Tree::Table(alignments, header, rows, caption, _) => {
+                let close = self.close_paragraph();
+                format!(
+                    "{close}{}",
+                    self.render_table(&alignments, *header, rows, *caption)
+                )
+            }
+            Tree::TableRow(cells, _) => self.render_table_row(cells, "td", &[]),
+            Tree::BlockQuote(content, _) => {
+                let close = self.close_paragraph();
+                let content = self.render_node(*content);
+                let end = self.close_paragraph();
+                format!("{close}<blockquote>\n{content}{end}</blockquote>\n")
+            }
+            // a color on its own has nothing to color, see `render_sequence`
+            Tree::Color(..)
+            | Tree::MetaDataItem(..)
+            | Tree::ImageSizeSpec(..)
+            | Tree::VSpace(_)
+            | Tree::Empty(_) => String::new(),
+            Tree::FootnoteDef(..) | Tree::LinkDef(..) | Tree::Include(..) => String::new(),
+            phrasing => self.render_phrasing(phrasing),
+        }
+    }
+
+    /// renders nodes that are part of a paragraph
+    fn render_phrasing(&mut self, tree: Tree) -> String {
+        match tree {
+            Tree::Literal(s, _) | Tree::PreformattedLiteral(s, _) | Tree::EscapeLit(s, _) => {
+                escape(&s)
+            }
+            Tree::Escaped(c, _) => escape(&c.to_string()),
+            Tree::DropCap(c, lines, _) => format!(
+                "<span class=\"drop-cap\" style=\"--lines: {lines}\">{}</span>",
+                escape(&(c as char).to_string())
+            ),
+            Tree::ChapterMark(content, _) => format!(
+                "<span class=\"chapter-mark\">{}</span>",
+                self.render_inline(*content)
+            ),
+            Tree::Bold(content, _) => format!("<strong>{}</strong>", self.render_inline(*content)),
+            Tree::Italic(content, _) => format!("<em>{}</em>", self.render_inline(*content)),
+            Tree::BoldItalic(content, _) => {
+                format!("<strong><em>{}</em></strong>", self.render_inline(*content))
+            }
+            Tree::SmallCaps(content, _) => format!(
+                "<span class=\"small-caps\">{}</span>",
+                self.render_inline(*content)
+            ),
+            Tree::InlineCode(content, _) => {
+                format!("<code>{}</code>", self.render_inline(*content))
+            }
+            Tree::Quote(content, _) => format!("<q>{}</q>", self.render_inline(*content)),
+            Tree::Footnote(content, _) => self.render_footnote(*content),
+            Tree::RightSidenote(content, _) => format!(
+                "<span class=\"sidenote\">{}</span>",
+                self.render_inline(*content)
+            ),
+            Tree::HyperRef(link_text, url, _) => format!(
+                "<a href=\"{}\">{}</a>",
+                escape(&text(&url)),
+                self.render_inline(*link_text)
+            ),
+            Tree::DocRef(target, link_text, _) => format!(
+                "<a href=\"#{}\">{}</a>",
+                escape(&target),
+                self.render_inline(*link_text)
+            ),
+            Tree::EmDash(_) => "\u{2014}".to_string(),
+            Tree::EnDash(_) => "\u{2013}".to_string(),
+            Tree::FootnoteRef(label, _) => escape(&format!("[^{label}]")),
+            Tree::LinkRef(_, link_text, suffix, _) => {
+                format!("[{}]{}", self.render_inline(*link_text), escape(&suffix))
+            }
+            tree => self.render_node(tree),
+        }
+    }
+
+    /// renders `tree` as part of the paragraph or other element we are in
+    fn render_inline(&mut self, tree: Tree) -> String {
+        self.inline += 1;
+        let html = self.render_phrasing(tree);
+        self.inline -= 1;
+        html
+    }
+
+    /// renders the nodes of a sequence. a color applies to the nodes following it,
+    /// while groff keeps it until it is changed, here it ends with the paragraph.
+    fn render_sequence(&mut self, children: Vec<Tree>) -> String {
+        let mut html = String::new();
+        let mut children = children.into_iter().peekable();
+        while let Some(child) = children.next() {
+            let Tree::Color(name, _) = child else {
+                let link = matches!(child, Tree::HyperRef(..));
+                html.push_str(&self.render_node(child));
+                if link && children.peek().is_some_and(Tree::starts_with_word) {
+                    html.push(' ');
+                }
+                continue;
+            };
+            let name: String = text(&name)
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+                .collect();
+            let mut colored = Vec::new();
+            while let Some(next) =
+                children.next_if(|next| !is_block(next) && !matches!(next, Tree::Color(..)))
+            {
+                colored.push(next);
+            }
+            if self.inline == 0 && !self.in_paragraph {
+                self.in_paragraph = true;
+                html.push_str("<p>");
+            }
+            html.push_str(&format!(
+                "<span style=\"color: var(--{name}, {name})\">{}</span>",
+                self.render_inline(crate::syntax::block(colored))
+            ));
+        }
+        html
+    }
+
+    /// starts a paragraph for `tree`, unless one is started already or it is only space
+    fn open_paragraph(&mut self, tree: &Tree) -> &'static str {
+        let blank = matches!(tree, Tree::Literal(s, _) if s.trim().is_empty());
+        if self.in_paragraph || blank {
+            return "";
+        }
+        self.in_paragraph = true;
+        "<p>"
+    }
+
+    fn close_paragraph(&mut self) -> String {
+        if self.in_paragraph {
+            self.in_paragraph = false;
+            "</p>\n".to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    fn close_section(&mut self) -> String {
+        if self.in_section {
+            self.in_section = false;
+            "</section>\n".to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    fn render_document(&mut self, doc_type: DocType, content: Tree) -> String {
+        let meta_data = meta_data(&content);
+        let body = self.render_body(doc_type, content);
+        let mut head = String::from("<meta charset=\"utf-8\">\n");
+        head.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+        if let Some(title) = lookup(&meta_data, "title") {
+            head.push_str(&format!("<title>{}</title>\n", escape(title)));
+        }
+        if let Some(author) = lookup(&meta_data, "author") {
+            head.push_str(&format!(
+                "<meta name=\"author\" content=\"{}\">\n",
+                escape(author)
+            ));
+        }
+        if !self.style.is_empty() {
+            head.push_str(&format!("<style>\n{}</style>\n", self.style));
+        }
+        format!(
+            "<!DOCTYPE html>\n<html{}>\n<head>\n{head}</head>\n<body class=\"{}\">\n{body}</body>\n</html>",
+            language(&self.config.lang),
+            format!("{:?}", self.doc_type).to_lowercase()
+        )
+    }
+
+    /// renders `content` as the inside of a `<body>`, followed by its footnotes
+    pub(crate) fn render_body(&mut self, doc_type: DocType, content: Tree) -> String {
+        self.doc_type = doc_type;
+        let mut body = self.render_node(content);
+        body.push_str(&self.close_paragraph());
+        body.push_str(&self.close_section());
+        let footnotes = std::mem::take(&mut self.footnotes);
+        if !footnotes.is_empty() {
+            body.push_str("<aside class=\"footnotes\">\n<ol>\n");
+            for (i, footnote) in footnotes.iter().enumerate() {
+                let n = i + 1;
+                body.push_str(&format!(
+                    "<li id=\"fn{n}\">{footnote}<a href=\"#fnref{n}\">\u{21a9}</a></li>\n"
+                ));
+            }
+            body.push_str("</ol>\n</aside>\n");
+        }
+        body
+    }
+
+    /// the style sheet to be used
+    pub(crate) fn style(&self) -> &str {
+        &self.style
+    }
+
+    /// renders a footnote as a numbered reference to the list of footnotes at the end
+    fn render_footnote(&mut self, content: Tree) -> String {
+        if self.in_footnote {
+            return format!(" ({})", self.render_inline(content));
+        }
+        // the footnote is rendered on its own, with paragraphs, outside of the text
+        let in_paragraph = std::mem::replace(&mut self.in_paragraph, false);
+        let inline = std::mem::replace(&mut self.inline, 0);
+        self.in_footnote = true;
+        let mut footnote = self.render_node(content);
+        footnote.push_str(&self.close_paragraph());
+        self.in_footnote = false;
+        self.inline = inline;
+        self.in_paragraph = in_paragraph;
+        self.footnotes.push(footnote);
+        let n = self.footnotes.len();
+        format!("<sup id=\"fnref{n}\"><a href=\"#fn{n}\">{n}</a></sup>")
+    }
+
+    /// renders a list. the tree places nested lists after the item they belong to,
+    /// HTML inside of it
+    fn render_list(&mut self, items: Tree, kind: ListKind) -> String {
+        let (open, close) = match kind {
+            ListKind::Bullet(_) => ("<ul>".to_string(), "</ul>"),
+            ListKind::Ordered(1, _) => ("<ol>".to_string(), "</ol>"),
+            ListKind::Ordered(start, _) => (format!("<ol start=\"{start}\">"), "</ol>"),
+        };
+        let children = match items {
+            Tree::Block(children, _) => children,
+            item => vec![item],
+        };
+        let mut html = format!("{open}\n");
+        let mut in_item = false;
+        for child in children {
+            match child {
+                Tree::ListItem(content, _, _) => {
+                    if in_item {
+                        html.push_str("</li>\n");
+                    }
+                    html.push_str(&format!("<li>{}", self.render_inline(*content)));
+                    in_item = true;
+                }
+                Tree::List(..) => {
+                    html.push('\n');
+                    html.push_str(&self.render_node(child));
+                }
+                child => html.push_str(&self.render_node(child)),
+            }
+        }
+        if in_item {
+            html.push_str("</li>\n");
+        }
+        format!("{html}{close}\n")
+    }
+
+    fn render_image(&mut self, caption: &Tree, path: &Tree, size_spec: &Tree) -> String {
+        let size = match size_spec {
+            Tree::ImageSizeSpec(width, height, _) => format!(
+                " style=\"width: {}pt; height: {}pt\"",
+                escape(&text(width)),
+                escape(&text(height))
+            ),
+            _ => String::new(),
+        };
+        // closed like in XHTML, which the EPUB pages are
+        format!(
+            "<img src=\"{}\" alt=\"{}\"{size}/>",
+            escape(&text(path)),
+            escape(&text(caption))
+        )
+    }
+
+    /// renders a table with a header row and an optional caption
+    fn render_table(
+        &mut self,
+        alignments: &[Alignment],
+        header: Tree,
+        rows: Vec<Tree>,
+        caption: Tree,
+    ) -> String {
+        let mut html = String::from("<table>\n");
+        if !matches!(caption, Tree::Empty(_)) {
+            html.push_str(&format!(
+                "<caption>{}</caption>\n",
+                self.render_inline(caption)
+            ));
+        }
+        if let Tree::TableRow(cells, _) = header {
+            html.push_str("<thead>\n");
+            html.push_str(&self.render_table_row(cells, "th", alignments));
+            html.push_str("</thead>\n");
+        }
+        html.push_str("<tbody>\n");
+        for row in rows {
+            if let Tree::TableRow(cells, _) = row {
+                html.push_str(&self.render_table_row(cells, "td", alignments));
+            }
+        }
+        format!("{html}</tbody>\n</table>\n")
+    }
+
+    fn render_table_row(
+        &mut self,
+        cells: Vec<Tree>,
+        tag: &str,
+        alignments: &[Alignment],
+    ) -> String {
+        let cells: String = cells
+            .into_iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let style = match alignments.get(i) {
+                    Some(Alignment::Left) => " style=\"text-align: left\"",
+                    Some(Alignment::Center) => " style=\"text-align: center\"",
+                    Some(Alignment::Right) => " style=\"text-align: right\"",
+                    Some(Alignment::Default) | None => "",
+                };
+                format!("<{tag}{style}>{}</{tag}>", self.render_inline(cell))
+            })
+            .collect();
+        format!("<tr>{cells}</tr>\n")
+    }
+}
+
+/// renders the title, subtitle and author of the meta data
+fn render_header(meta_data: &[(String, String)]) -> String {
+    let mut header = String::new();
+    if let Some(title) = lookup(meta_data, "title") {
+        header.push_str(&format!("<h1 class=\"title\">{}</h1>\n", escape(title)));
+    }
+    for key in ["subtitle", "author"] {
+        if let Some(value) = lookup(meta_data, key) {
+            header.push_str(&format!("<p class=\"{key}\">{}</p>\n", escape(value)));
+        }
+    }
+    if header.is_empty() {
+        header
+    } else {
+        format!("<header>\n{header}</header>\n")
+    }
+}
+
+impl Render for Renderer<'_> {
+    fn render(&mut self, tree: Tree) -> String {
+        self.render_node(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::*;
+    use crate::Process;
+
+    fn render(tree: Tree) -> String {
+        let mut config = Config::default();
+        config.skip_preamble = true;
+        new(&config).unwrap().render(tree)
+    }
+
+    /// renders the body of the page for `input`
+    fn body(input: &str) -> String {
+        let (tree, _) = crate::parser::Parser::parse(input);
+        let mut config = Config::default();
+        config.skip_preamble = true;
+        let mut chain =
+            crate::create_chain_of(&["footnotes".to_string(), "links".to_string()], &config)
+                .unwrap();
+        let tree = chain.process(tree).unwrap();
+        let html = new(&config).unwrap().render(tree);
+        let start = html.find("<body").unwrap();
+        let start = start + html[start..].find('\n').unwrap() + 1;
+        html[start..html.rfind("</body>").unwrap()].to_string()
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(
+            render(lit("a < b & \"c\"")),
+            "<p>a &lt; b &amp; &quot;c&quot;"
+        );
+    }
+
+    #[test]
+    fn paragraphs_are_closed_by_separators_and_blocks() {
+        assert_eq!(
+            body("one\ntwo\n\nthree\n# Head\n"),
+            "<p>one\ntwo\n</p>\n<p>three\n</p>\n<h1>Head</h1>\n\n"
+        );
+    }
+
+    #[test]
+    fn groff_requests_are_left_out() {
+        assert_eq!(body("a\n\n//.TOC\n"), "<p>a\n</p>\n\n");
+    }
+
+    #[test]
+    fn inline_formatting() {
+        assert_eq!(
+            body("*b* _i_ {sc} `c` \"q\""),
+            "<p><strong>b</strong> <em>i</em> <span class=\"small-caps\">sc</span> \
+             <code>c</code> <q>q</q></p>\n"
+        );
+    }
+
+    #[test]
+    fn links_keep_the_space_after_them() {
+        assert_eq!(
+            body("a [l](u) b <https://y.org> too, [l](u), c"),
+            "<p>a <a href=\"u\">l</a> b <a href=\"https://y.org\">https://y.org</a> too, \
+             <a href=\"u\">l</a>, c</p>\n"
+        );
+    }
+
+    #[test]
+    fn named_headings_are_link_targets() {
+        assert_eq!(
+            body("## Intro /intro/\n\nsee [there](#intro)"),
+            "<h2 id=\"intro\">Intro</h2>\n\n<p>see <a href=\"#intro\">there</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn footnotes_are_collected_at_the_end() {
+        assert_eq!(
+            body("a^(note) b"),
+            "<p>a<sup id=\"fnref1\"><a href=\"#fn1\">1</a></sup> b</p>\n\
+             <aside class=\"footnotes\">\n<ol>\n\
+             <li id=\"fn1\"><p>note</p>\n<a href=\"#fnref1\">\u{21a9}</a></li>\n\
+             </ol>\n</aside>\n"
+        );
+    }
+
+    #[test]
+    fn sidenotes_and_drop_caps() {
+        assert_eq!(
+            body("%%Drop >(side)"),
+            "<p><span class=\"drop-cap\" style=\"--lines: 3\">D</span>rop \
+             <span class=\"sidenote\">side</span></p>\n"
+        );
+    }
+
+    #[test]
+    fn nested_lists_are_placed_inside_items() {
+        assert_eq!(
+            body("* a\n  * b\n* c\n"),
+            "<ul>\n<li>a\n<ul>\n<li>b</li>\n</ul>\n</li>\n<li>c</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn ordered_lists_keep_their_start() {
+        assert!(body("3. x\n4. y\n").starts_with("<ol start=\"3\">\n<li>x</li>\n"));
+    }
+
+    #[test]
+    fn tables_are_aligned() {
+        assert_eq!(
+            body("| a | b |\n|:-:|---|\n| 1 | 2 |\n\nTable: cap\n"),
+            "<table>\n<caption>cap</caption>\n<thead>\n\
+             <tr><th style=\"text-align: center\">a</th><th>b</th></tr>\n</thead>\n<tbody>\n\
+             <tr><td style=\"text-align: center\">1</td><td>2</td></tr>\n</tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn code_blocks_are_preformatted() {
+        assert_eq!(
+            body("```rust\nif a < b {}\n```\n"),
+            "<pre><code class=\"language-rust\">if a &lt; b {}\n</code></pre>\n\n"
+        );
+    }
+
+    #[test]
+    fn colors_last_until_the_paragraph_ends() {
+        assert_eq!(
+            body("a \\{red}b\n\nc"),
+            "<p>a <span style=\"color: var(--red, red)\">b\n</span></p>\n<p>c</p>\n"
+        );
+    }
+
+    #[test]
+    fn meta_data_makes_the_header() {
+        let (tree, _) = crate::parser::Parser::parse("---\ntitle: \"A & B\"\nauthor: Me\n---\nx\n");
+        let mut config = Config::default();
+        config.skip_preamble = true;
+        config.lang = "den".to_string();
+        let html = new(&config).unwrap().render(tree);
+        assert!(html.contains("<title>A &amp; B</title>"));
+        assert!(html.contains("<meta name=\"author\" content=\"Me\">"));
+        assert!(html.contains(
+            "<header>\n<h1 class=\"title\">A &amp; B</h1>\n<p class=\"author\">Me</p>\n</header>\n"
+        ));
+        assert!(html.starts_with("<!DOCTYPE html>\n<html lang=\"de\">\n"));
+    }
+
+    #[test]
+    fn chapters_are_sections() {
+        let (tree, _) =
+            crate::parser::Parser::parse("---\ndoctype: CHAPTER\n---\n# One\nx\n# Two\ny\n");
+        let html = render(tree);
+        assert_eq!(html.matches("<section class=\"chapter\">").count(), 2);
+        assert_eq!(html.matches("</section>").count(), 2);
+    }
+
+    #[test]
+    fn images_are_figures() {
+        assert_eq!(
+            render(image(
+                lit("cap"),
+                lit("a.png"),
+                image_size(lit("10"), lit("20"))
+            )),
+            "<figure><img src=\"a.png\" alt=\"cap\" style=\"width: 10pt; height: 20pt\"/>\
+             <figcaption>cap</figcaption></figure>\n"
+        );
+    }
+}
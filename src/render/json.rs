@@ -0,0 +1,17 @@
+//! renders the syntax tree as JSON, so that it can be inspected or processed by other tools
+use crate::syntax::{json, Tree};
+
+use crate::Render;
+
+#[derive(Default)]
+pub struct JsonRenderer {}
+
+impl Render for JsonRenderer {
+    fn render(&mut self, tree: Tree) -> String {
+        json::to_json(&tree)
+    }
+}
+
+pub fn new() -> JsonRenderer {
+    JsonRenderer {}
+}
@@ -0,0 +1,531 @@
+//! renders the syntax tree as LaTeX source
+//!
+//! the document class follows the document type, the packages come from the
+//! preamble. chapters need a class that has them, so documents with chapter
+//! marks are set as a report, like those of type CHAPTER.
+use crate::config::Config;
+use crate::render::html::{lookup, meta_data, text};
+use crate::syntax::{visit_children, Alignment, DocType, ListKind, Tree, Visit};
+use crate::{locate_and_load_preamble, Render, Result};
+
+const PREAMBLE_FILE_NAME: &str = "preamble.tex";
+const DEFAULT_PREAMBLE: &str = include_str!("default-preamble.tex");
+
+pub struct Renderer<'a> {
+    config: &'a Config,
+    /// the preamble found for the source file, or the default one
+    preamble: String,
+    doc_type: DocType,
+    /// the document class has chapters
+    chapters: bool,
+    /// a chapter has just been started, a chapter mark following belongs to it
+    chapter_started: bool,
+    /// a top-level heading has been rendered, later ones start a new page of slides
+    slide_started: bool,
+    /// footnotes cannot nest, nested ones are set in parentheses
+    in_footnote: bool,
+}
+
+/// creates a renderer, reading the preamble to be used
+pub fn new(config: &Config) -> Result<Renderer<'_>> {
+    Ok(Renderer {
+        config,
+        preamble: locate_and_load_preamble(config, PREAMBLE_FILE_NAME, DEFAULT_PREAMBLE)?,
+        doc_type: DocType::DEFAULT,
+        chapters: false,
+        chapter_started: false,
+        slide_started: false,
+        in_footnote: false,
+    })
+}
+
+/// escapes the characters with a meaning in LaTeX text
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// escapes the characters hyperref does not take as they are in a URL
+fn escape_url(url: &str) -> String {
+    url.replace('\\', "/")
+        .replace('%', "\\%")
+        .replace('#', "\\#")
+        .replace(['{', '}'], "")
+}
+
+/// the babel language for a groff hyphenation language
+fn babel_language(lang: &str) -> &str {
+    match lang {
+        "den" | "de" => "ngerman",
+        "en" => "english",
+        "fr" => "french",
+        lang => lang,
+    }
+}
+
+/// documents with chapter marks are divided into chapters
+fn has_chapter_marks(tree: &Tree) -> bool {
+    #[derive(Default)]
+    struct ChapterMarks(bool);
+
+    impl Visit for ChapterMarks {
+        fn visit(&mut self, tree: &Tree) {
+            match tree {
+                Tree::ChapterMark(..) => self.0 = true,
+                _ => visit_children(self, tree),
+            }
+        }
+    }
+
+    let mut marks = ChapterMarks::default();
+    marks.visit(tree);
+    marks.0
+}
+
+/// nodes rendering nothing, which leave a chapter just started
+fn is_blank(tree: &Tree) -> bool {
+    match tree {
+        Tree::Paragraph(_)
+        | Tree::LineBreak(_)
+        | Tree::VSpace(_)
+        | Tree::Empty(_)
+        | Tree::MetaDataBlock(..) => true,
+        Tree::Literal(s, _) => s.trim().is_empty(),
+        _ => false,
+    }
+}
+
+impl Renderer<'_> {
+    fn render_document(&mut self, doc_type: DocType, content: Tree) -> String {
+        self.doc_type = doc_type;
+        self.chapters = matches!(self.doc_type, DocType::CHAPTER) || has_chapter_marks(&content);
+        let meta_data = meta_data(&content);
+        let class = if self.chapters { "report" } else { "article" };
+        let mut latex = format!("\\documentclass{{{class}}}\n");
+        match babel_language(&self.config.lang) {
+            "" => (),
+            language => latex.push_str(&format!("\\usepackage[{language}]{{babel}}\n")),
+        }
+        latex.push_str(&self.preamble);
+        if !latex.ends_with('\n') {
+            latex.push('\n');
+        }
+        let title = lookup(&meta_data, "title");
+        if let Some(title) = title {
+            let subtitle = match lookup(&meta_data, "subtitle") {
+                Some(subtitle) => format!("\\\\\n\\large {}", escape(subtitle)),
+                None => String::new(),
+            };
+            latex.push_str(&format!("\\title{{{}{subtitle}}}\n", escape(title)));
+        }
+        if let Some(author) = lookup(&meta_data, "author") {
+            latex.push_str(&format!("\\author{{{}}}\n", escape(author)));
+        }
+        if let Some(date) = lookup(&meta_data, "date") {
+            latex.push_str(&format!("\\date{{{}}}\n", escape(date)));
+        }
+        latex.push_str("\n\\begin{document}\n");
+        if title.is_some() {
+            latex.push_str("\\maketitle\n");
+        }
+        let body = self.render_node(content);
+        latex.push_str(tidy(&body).trim_matches('\n'));
+        latex.push_str("\n\\end{document}");
+        latex
+    }
+
+    fn render_node(&mut self, tree: Tree) -> String {
+        if !is_blank(&tree) && !matches!(tree, Tree::Block(..) | Tree::ChapterMark(..)) {
+            self.chapter_started = false;
+        }
+        match tree {
+            Tree::Document(doc_type, content, _) => self.render_document(doc_type, *content),
+            Tree::Block(children, _) | Tree::Inline(children, _) => {
+                let mut latex = String::new();
+                let mut children = children.into_iter().peekable();
+                while let Some(child) = children.next() {
+                    let link = matches!(child, Tree::HyperRef(..));
+                    latex.push_str(&self.render_node(child));
+                    if link && children.peek().is_some_and(Tree::starts_with_word) {
+                        latex.push(' ');
+                    }
+                }
+                latex
+            }
+            Tree::Paragraph(_) => "\n\n".to_string(),
+            Tree::LineBreak(_) => "\n".to_string(),
+            // groff requests passed through with `//`. the table of contents is known
+            // to LaTeX as well, the rest is left out
+            Tree::Literal(s, _) if s.starts_with('.') => match s.trim() {
+                ".TOC" => "\\tableofcontents\n".to_string(),
+                _ => String::new(),
+            },
+            Tree::Literal(s, _) | Tree::PreformattedLiteral(s, _) | Tree::EscapeLit(s, _) => {
+                escape(&s)
+            }
+            Tree::Escaped(c, _) => escape(&c.to_string()),
+            Tree::DropCap(c, lines, _) => {
+                format!(
+                    "\\lettrine[lines={lines}]{{{}}}{{}}",
+                    escape(&(c as char).to_string())
+                )
+            }
+            Tree::Color(name, _) => format!("\\color{{{}}}", color_name(&name)),
+            Tree::ChapterMark(content, _) => self.render_chapter_mark(*content),
+            Tree::Heading(title, level, name, _) => self.render_heading(*title, level, &name),
+            Tree::Bold(content, _) => format!("\\textbf{{{}}}", self.render_node(*content)),
+            Tree::Italic(content, _) => format!("\\emph{{{}}}", self.render_node(*content)),
+            Tree::BoldItalic(content, _) => {
+                format!("\\textbf{{\\emph{{{}}}}}", self.render_node(*content))
+            }
+            Tree::SmallCaps(content, _) => format!("\\textsc{{{}}}", self.render_node(*content)),
+            Tree::CodeBlock(_, content, _) => {
+                let code = text(&content);
+                let newline = if code.ends_with('\n') { "" } else { "\n" };
+                format!("\n\\begin{{verbatim}}\n{code}{newline}\\end{{verbatim}}\n")
+            }
+            Tree::InlineCode(content, _) => format!("\\texttt{{{}}}", escape(&text(&content))),
+            Tree::Quote(content, _) => format!("``{}''", self.render_node(*content)),
+            Tree::Footnote(content, _) => self.render_footnote(*content),
+            Tree::RightSidenote(content, _) => {
+                format!("\\marginpar{{{}}}", self.render_node(*content))
+            }
+            Tree::HyperRef(link_text, url, _) => format!(
+                "\\href{{{}}}{{{}}}",
+                escape_url(&text(&url)),
+                self.render_node(*link_text)
+            ),
+            Tree::DocRef(target, link_text, _) => format!(
+                "\\hyperref[{}]{{{}}}",
+                label(&target),
+                self.render_node(*link_text)
+            ),
+            Tree::List(items, _, kind, _) => self.render_list(*items, kind),
+            Tree::ListItem(content, _, _) => match *content {
+                Tree::Empty(_) => String::new(),
+                content => format!("\\item {}\n", self.render_node(content).trim()),
+            },
+            // the meta data is set in the preamble and by `\maketitle`
+            Tree::MetaDataBlock(..) | Tree::MetaDataItem(..) => String::new(),
+            Tree::ImageSizeSpec(width, height, _) => {
+                format!("width={}pt,height={}pt", text(&width), text(&height))
+            }
+            Tree::Image(caption, path, size_spec, _) => {
+                self.render_image(*caption, &path, *size_spec)
+            }
+            Tree::VSpace(_) | Tree::Empty(_) => String::new(),
+            Tree::EmDash(_) => "---".to_string(),
+            Tree::EnDash(_) => "--".to_string(),
+            Tree::Table(alignments, header, rows, caption, _) => {
+                self.render_table(&alignments, *header, rows, *caption)
+            }
+            Tree::TableRow(cells, _) => self.render_table_row(cells, false),
+            Tree::BlockQuote(content, _) => format!(
+                "\n\\begin{{quote}}\n{}\n\\end{{quote}}\n",
+                self.render_node(*content).trim_matches('\n')
+            ),
+            Tree::FootnoteRef(label, _) => escape(&format!("[^{label}]")),
+            Tree::FootnoteDef(..) | Tree::LinkDef(..) | Tree::Include(..) => String::new(),
+            Tree::LinkRef(_, link_text, suffix, _) => {
+                format!("[{}]{}", self.render_node(*link_text), escape(&suffix))
+            }
+        }
+    }
+
+    /// renders a heading as the sectioning command of its level. in documents
+    /// with chapters, top-level headings start a chapter
+    fn render_heading(&mut self, title: Tree, level: u8, name: &str) -> String {
+        const SECTIONS: [&str; 5] = [
+            "chapter",
+            "section",
+            "subsection",
+            "subsubsection",
+            "paragraph",
+        ];
+        let level = if self.chapters { level } else { level + 1 };
+        let command = SECTIONS[usize::from(level).min(SECTIONS.len() - 1)];
+        let mut latex = String::from("\n");
+        if matches!(self.doc_type, DocType::SLIDES) && level == 1 {
+            if self.slide_started {
+                latex.push_str("\\clearpage\n");
+            }
+            self.slide_started = true;
+        }
+        let title = self.render_node(title);
+        latex.push_str(&format!("\\{command}{{{}}}", title.trim()));
+        if !name.is_empty() {
+            latex.push_str(&format!("\\label{{{}}}", label(name)));
+        }
+        latex.push('\n');
+        self.chapter_started = command == "chapter";
+        latex
+    }
+
+    /// a chapter mark right after a top-level heading marks its chapter in the
+    /// margin. any other one starts a chapter of its own, named after the mark
+    fn render_chapter_mark(&mut self, content: Tree) -> String {
+        let chapter_started = self.chapter_started;
+        let mark = self.render_node(content);
+        if chapter_started {
+            format!("\\marginpar{{\\Huge {mark}}}\n")
+        } else {
+            format!("\n\\chapter{{{mark}}}\n")
+        }
+    }
+
+    fn render_footnote(&mut self, content: Tree) -> String {
+        if self.in_footnote {
+            return format!(" ({})", self.render_node(content).trim());
+        }
+        self.in_footnote = true;
+        let footnote = self.render_node(content);
+        self.in_footnote = false;
+        format!("\\footnote{{{}}}", footnote.trim())
+    }
+
+    /// renders a list. nested lists follow the item they belong to, which LaTeX
+    /// takes as part of it
+    fn render_list(&mut self, items: Tree, kind: ListKind) -> String {
+        let (environment, options) = match kind {
+            ListKind::Bullet(_) => ("itemize", String::new()),
+            ListKind::Ordered(1, _) => ("enumerate", String::new()),
+            ListKind::Ordered(start, _) => ("enumerate", format!("[start={start}]")),
+        };
+        let items = self.render_node(items);
+        format!(
+            "\n\\begin{{{environment}}}{options}\n{}\n\\end{{{environment}}}\n",
+            items.trim_matches('\n')
+        )
+    }
+
+    /// renders an image as a figure, or as part of the line inside text
+    fn render_image(&mut self, caption: Tree, path: &Tree, size_spec: Tree) -> String {
+        let size = match size_spec {
+            Tree::Empty(_) => String::new(),
+            size_spec => format!("[{}]", self.render_node(size_spec)),
+        };
+        let image = format!("\\includegraphics{size}{{{}}}", text(path));
+        let caption = match caption {
+            Tree::Empty(_) => String::new(),
+            caption => format!("\\caption{{{}}}\n", self.render_node(caption).trim()),
+        };
+        format!("\n\\begin{{figure}}[htbp]\n\\centering\n{image}\n{caption}\\end{{figure}}\n")
+    }
+
+    /// renders a table with a bold header row and an optional caption
+    fn render_table(
+        &mut self,
+        alignments: &[Alignment],
+        header: Tree,
+        rows: Vec<Tree>,
+        caption: Tree,
+    ) -> String {
+        let columns: String = alignments
+            .iter()
+            .map(|alignment| match alignment {
+                Alignment::Center => 'c',
+                Alignment::Right => 'r',
+                Alignment::Left | Alignment::Default => 'l',
+            })
+            .collect();
+        let mut latex = format!(
+            "\n\\begin{{table}}[htbp]\n\\centering\n\\begin{{tabular}}{{{columns}}}\n\\hline\n"
+        );
+        if let Tree::TableRow(cells, _) = header {
+            latex.push_str(&self.render_table_row(cells, true));
+            latex.push_str("\\hline\n");
+        }
+        for row in rows {
+            latex.push_str(&self.render_node(row));
+        }
+        latex.push_str("\\hline\n\\end{tabular}\n");
+        if !matches!(caption, Tree::Empty(_)) {
+            latex.push_str(&format!(
+                "\\caption{{{}}}\n",
+                self.render_node(caption).trim()
+            ));
+        }
+        latex.push_str("\\end{table}\n");
+        latex
+    }
+
+    fn render_table_row(&mut self, cells: Vec<Tree>, header: bool) -> String {
+        let cells: Vec<String> = cells
+            .into_iter()
+            .map(|cell| {
+                let cell = self.render_node(cell).trim().to_string();
+                if header && !cell.is_empty() {
+                    format!("\\textbf{{{cell}}}")
+                } else {
+                    cell
+                }
+            })
+            .collect();
+        format!("{} \\\\\n", cells.join(" & "))
+    }
+}
+
+/// removes the blank lines following another one, outside of verbatim text, and
+/// joins lists interrupted by blank lines only
+fn tidy(latex: &str) -> String {
+    let mut tidy = String::with_capacity(latex.len());
+    let mut verbatim = false;
+    let mut blank = false;
+    for line in latex.lines() {
+        if line == "\\begin{verbatim}" {
+            verbatim = true;
+        } else if line == "\\end{verbatim}" {
+            verbatim = false;
+        } else if !verbatim && line.trim().is_empty() {
+            if blank {
+                continue;
+            }
+            blank = true;
+            tidy.push('\n');
+            continue;
+        }
+        blank = false;
+        let joined = ["itemize", "enumerate"].iter().any(|environment| {
+            let end = format!("\\end{{{environment}}}");
+            let list = tidy.trim_end_matches('\n');
+            if line.starts_with(&format!("\\begin{{{environment}}}")) && list.ends_with(&end) {
+                tidy.truncate(list.len() - end.len());
+                true
+            } else {
+                false
+            }
+        });
+        if !joined {
+            tidy.push_str(line);
+            tidy.push('\n');
+        }
+    }
+    tidy
+}
+
+/// the name of a color, without what LaTeX does not take in one
+fn color_name(name: &Tree) -> String {
+    text(name)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .collect()
+}
+
+/// a label for `\label` and `\hyperref`, which must not contain special characters
+fn label(name: &str) -> String {
+    name.chars()
+        .filter(|c| !"\\{}#%~^$&".contains(*c))
+        .collect()
+}
+
+impl Render for Renderer<'_> {
+    fn render(&mut self, tree: Tree) -> String {
+        self.render_node(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Process;
+
+    /// renders `input` with the footnotes and links processors, without a preamble
+    fn latex(input: &str) -> String {
+        let (tree, _) = crate::parser::Parser::parse(input);
+        let mut config = Config::default();
+        config.skip_preamble = true;
+        let mut chain =
+            crate::create_chain_of(&["footnotes".to_string(), "links".to_string()], &config)
+                .unwrap();
+        let tree = chain.process(tree).unwrap();
+        new(&config).unwrap().render(tree)
+    }
+
+    /// the part between `\begin{document}` and `\end{document}`
+    fn body(input: &str) -> String {
+        let latex = latex(input);
+        let start = latex.find("\\begin{document}\n").unwrap() + "\\begin{document}\n".len();
+        latex[start..latex.rfind("\n\\end{document}").unwrap()].to_string()
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        assert_eq!(
+            escape("#1 a_b {x} ~ ^ \\"),
+            "\\#1 a\\_b \\{x\\} \\textasciitilde{} \\textasciicircum{} \\textbackslash{}"
+        );
+        assert_eq!(body("50% of $5 & more"), "50\\% of \\$5 \\& more");
+    }
+
+    #[test]
+    fn meta_data_sets_title_and_author() {
+        let latex = latex("---\ntitle: \"Why MaTo?\"\nauthor: Sven\n---\nText\n");
+        assert!(
+            latex.starts_with("\\documentclass{article}\n\\title{Why MaTo?}\n\\author{Sven}\n"),
+            "{latex}"
+        );
+        assert!(latex.contains("\\begin{document}\n\\maketitle\n"));
+    }
+
+    #[test]
+    fn inline_formatting() {
+        assert_eq!(
+            body("*b* _i_ {sc} `a_b` \"q\""),
+            "\\textbf{b} \\emph{i} \\textsc{sc} \\texttt{a\\_b} ``q''"
+        );
+    }
+
+    #[test]
+    fn links_keep_the_space_after_them() {
+        assert_eq!(
+            body("a [l](u) b <https://y.org> too."),
+            "a \\href{u}{l} b \\href{https://y.org}{https://y.org} too."
+        );
+    }
+
+    #[test]
+    fn sidenotes_drop_caps_and_footnotes() {
+        assert_eq!(
+            body("%%Drop >(side) a^(note)"),
+            "\\lettrine[lines=3]{D}{}rop \\marginpar{side}a\\footnote{note}"
+        );
+    }
+
+    #[test]
+    fn chapter_marks_are_chapters() {
+        let latex = latex("# Showcase /show/\n>>(S)\n\nText\n\n>>(T)\n\nMore\n");
+        assert!(latex.starts_with("\\documentclass{report}\n"), "{latex}");
+        assert!(latex.contains("\\chapter{Showcase}\\label{show}\n\n\\marginpar{\\Huge S}\n"));
+        assert!(latex.contains("\\chapter{T}\n"), "{latex}");
+    }
+
+    #[test]
+    fn headings_lists_and_code() {
+        assert_eq!(
+            body("## Intro\n\n3. x\n4. y\n\n```\n{a}\n```\n"),
+            "\\subsection{Intro}\n\n\\begin{enumerate}[start=3]\n\\item x\n\\item y\n\\end{enumerate}\n\n\\begin{verbatim}\n{a}\n\\end{verbatim}"
+        );
+    }
+
+    #[test]
+    fn tables_are_aligned() {
+        assert_eq!(
+            body("| a | b |\n|:-:|--:|\n| 1 | 2 |\n\nTable: cap\n"),
+            "\\begin{table}[htbp]\n\\centering\n\\begin{tabular}{cr}\n\\hline\n\
+             \\textbf{a} & \\textbf{b} \\\\\n\\hline\n1 & 2 \\\\\n\\hline\n\\end{tabular}\n\
+             \\caption{cap}\n\\end{table}"
+        );
+    }
+}
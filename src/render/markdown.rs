@@ -1,19 +1,32 @@
-use crate::syntax::Tree;
+use crate::syntax::{Alignment, ListKind, Tree};
 
 use crate::Render;
 
 #[derive(Default)]
 pub struct Renderer {
     char_index: usize,
+    /// width of the line prefixes of the blocks we are in, like `> ` of block quotes
+    indent: usize,
+    /// kinds of the lists we are in, ordered ones count their items up
+    lists: Vec<ListKind>,
 }
 
 impl Renderer {
     pub fn new() -> Self {
-        Self { char_index: 0 }
+        Self {
+            char_index: 0,
+            indent: 0,
+            lists: Vec::new(),
+        }
     }
 
     fn wrap_at(&mut self, s: String, col: usize) -> String {
         let mut result = String::new();
+        if s == "\n" {
+            // a line break inside a list item, we reflow the item instead
+            self.char_index += 1;
+            return " ".to_string();
+        }
         if s.starts_with(' ') {
             result = " ".to_string();
             self.char_index += 1;
@@ -28,7 +41,7 @@ impl Renderer {
                 self.char_index += word.len();
             } else {
                 result = format!("{result}\n{word}");
-                self.char_index = word.len();
+                self.char_index = self.indent + word.len();
             }
             self.char_index += 1;
         }
@@ -39,10 +52,11 @@ impl Renderer {
 impl Render for Renderer {
     fn render(&mut self, exp: crate::syntax::Tree) -> String {
         match exp {
-            Tree::Literal(s) => self.wrap_at(s, 68),
-            Tree::EscapeLit(s) => s,
-            Tree::PreformattedLiteral(s) => s,
-            Tree::Bold(b_exp) => {
+            Tree::Literal(s, _) => self.wrap_at(s, 68),
+            Tree::EscapeLit(s, _) => s,
+            Tree::Escaped(c, _) => format!("\\{c}"),
+            Tree::PreformattedLiteral(s, _) => s,
+            Tree::Bold(b_exp, _) => {
                 let mut bold_text = self.render(*b_exp);
                 // if the text between the * chars would immediately
                 // start with a newline, we break the opening * onto
@@ -55,53 +69,186 @@ impl Render for Renderer {
                     format!("*{bold_text}*")
                 }
             }
-            Tree::Italic(b_exp) => format!("_{}_", self.render(*b_exp)),
-            Tree::BoldItalic(b_exp) => format!("_**{}**_", self.render(*b_exp)),
-            Tree::SmallCaps(be) => format!("{{{}}}", self.render(*be)),
-            Tree::CodeBlock(b1, b2) => format!("```{}\n{}```", self.render(*b1), self.render(*b2)),
-            Tree::InlineCode(b_exp) => format!("`{}`", self.render(*b_exp)),
-            Tree::Heading(b_exp, level, _) => {
+            Tree::Italic(b_exp, _) => format!("_{}_", self.render(*b_exp)),
+            Tree::BoldItalic(b_exp, _) => format!("_**{}**_", self.render(*b_exp)),
+            Tree::SmallCaps(be, _) => format!("{{{}}}", self.render(*be)),
+            Tree::CodeBlock(b1, b2, _) => format!("```{}\n{}```", self.render(*b1), self.render(*b2)),
+            Tree::InlineCode(b_exp, _) => format!("`{}`", self.render(*b_exp)),
+            Tree::Heading(b_exp, level, name, _) => {
                 let prefix = (0..level + 1).map(|_| "#").collect::<String>();
-                format!("{} {}", prefix, self.render(*b_exp))
+                let title = self.render(*b_exp);
+                if name.is_empty() {
+                    format!("{} {}", prefix, title)
+                } else {
+                    format!("{} {} /{name}/", prefix, title.trim_end())
+                }
             }
-            Tree::Quote(b_exp) => format!("\"{}\"", self.render(*b_exp)),
-            Tree::ChapterMark(b_exp) => format!(">>({})", self.render(*b_exp)),
-            Tree::RightSidenote(b_exp) => format!(">({})", self.render(*b_exp)),
-            Tree::Footnote(b_exp) => format!("^({})", self.render(*b_exp)),
-            Tree::HyperRef(b1, b2) => format!("[{}]({})", self.render(*b1), self.render(*b2)),
-            Tree::Cat(b1, b2) => {
-                format!("{}{}", self.render(*b1), self.render(*b2))
+            Tree::Quote(b_exp, _) => format!("\"{}\"", self.render(*b_exp)),
+            Tree::ChapterMark(b_exp, _) => format!(">>({})", self.render(*b_exp)),
+            Tree::RightSidenote(b_exp, _) => format!(">({})", self.render(*b_exp)),
+            Tree::Footnote(b_exp, _) => format!("^({})", self.render(*b_exp)),
+            Tree::HyperRef(b1, b2, _) => {
+                let (text, url) = (self.render(*b1), self.render(*b2));
+                if url == text || url == format!("mailto:{text}") {
+                    format!("<{text}>")
+                } else {
+                    format!("[{text}]({url})")
+                }
             }
-            Tree::Empty() => String::new(),
-            Tree::Paragraph() => {
-                self.char_index = 0;
+            Tree::Block(children, _) | Tree::Inline(children, _) => {
+                let mut md = String::new();
+                let mut children = children.into_iter().peekable();
+                while let Some(child) = children.next() {
+                    let link = matches!(child, Tree::HyperRef(..));
+                    md.push_str(&self.render(child));
+                    if link && children.peek().is_some_and(Tree::starts_with_word) {
+                        md.push(' ');
+                        self.char_index += 1;
+                    }
+                }
+                md
+            }
+            Tree::Empty(_) => String::new(),
+            Tree::Paragraph(_) => {
+                self.char_index = self.indent;
                 "\n".to_string()
             }
-            Tree::LineBreak() => {
-                self.char_index = 0;
+            Tree::LineBreak(_) => {
+                self.char_index = self.indent;
                 "\n".to_string()
             }
-            Tree::Document(_, be) => self.render(*be),
-            Tree::List(b_exp, _) => self.render(*b_exp),
-            Tree::ListItem(b_exp, level) => {
+            Tree::Document(_, be, _) => self.render(*be),
+            Tree::List(b_exp, _, kind, _) => {
+                self.lists.push(kind);
+                let items = self.render(*b_exp);
+                self.lists.pop();
+                items
+            }
+            Tree::ListItem(b_exp, level, _) => {
                 let indent = (0..level).map(|_| "  ").collect::<String>();
-                format!("{}{}", indent, self.render(*b_exp))
+                let marker = match self.lists.last_mut() {
+                    Some(ListKind::Ordered(number, delimiter)) => {
+                        *number += 1;
+                        format!("{}{delimiter}", *number - 1)
+                    }
+                    Some(ListKind::Bullet(bullet)) => bullet.to_string(),
+                    None => "*".to_string(),
+                };
+                let outer_indent = self.indent;
+                self.indent = outer_indent + indent.len() + 2;
+                self.char_index = outer_indent + indent.len() + marker.len() + 1;
+                // wrapped lines continue the item, so they need to be indented
+                let item = self
+                    .render(*b_exp)
+                    .replace('\n', &format!("\n{indent}  "));
+                self.indent = outer_indent;
+                self.char_index = outer_indent;
+                format!("{indent}{marker} {item}\n")
             }
-            Tree::MetaDataBlock(b_exp) => format!("---\n{}---\n\n", self.render(*b_exp)),
-            Tree::MetaDataItem(key, value) => format!("{key}: {value}\n"),
-            Tree::Image(b1, b2, b3) => format!(
+            Tree::MetaDataBlock(b_exp, _) => format!("---\n{}---\n\n", self.render(*b_exp)),
+            Tree::MetaDataItem(key, value, _) => format!("{key}: {value}\n"),
+            Tree::Image(b1, b2, b3, _) => format!(
                 "![{}|{}]({})",
                 self.render(*b1),
                 self.render(*b3),
                 self.render(*b2)
             ),
-            Tree::Color(b_exp) => format!("\\{{{}}}", self.render(*b_exp)),
-            Tree::ImageSizeSpec(b1, b2) => format!("{}x{}", self.render(*b1), self.render(*b2)),
-            Tree::VSpace() => String::new(),
-            Tree::DropCap(_, _) => todo!(),
-            Tree::DocRef(_, _) => todo!(),
-            Tree::EmDash => "\u{2014}".to_owned(),
-            Tree::EnDash => "\u{2013}".to_owned(),
+            Tree::Color(b_exp, _) => format!("\\{{{}}}", self.render(*b_exp)),
+            Tree::ImageSizeSpec(b1, b2, _) => format!("{}x{}", self.render(*b1), self.render(*b2)),
+            Tree::VSpace(_) => String::new(),
+            // the parser counts one `%` less than the lines a drop cap spans
+            Tree::DropCap(c, lines, _) => {
+                let marker = "%".repeat(usize::from(lines.saturating_sub(1)).max(1));
+                self.char_index += marker.len() + 1;
+                format!("{marker}{}", c as char)
+            }
+            Tree::DocRef(target, b_exp, _) => format!("[{}](#{target})", self.render(*b_exp)),
+            Tree::EmDash(_) => "\u{2014}".to_owned(),
+            Tree::EnDash(_) => "\u{2013}".to_owned(),
+            Tree::Table(alignments, header, rows, caption, _) => {
+                let mut table = self.render(*header);
+                let delimiters: Vec<&str> = alignments
+                    .iter()
+                    .map(|alignment| match alignment {
+                        Alignment::Default => "---",
+                        Alignment::Left => ":--",
+                        Alignment::Center => ":-:",
+                        Alignment::Right => "--:",
+                    })
+                    .collect();
+                table.push_str(&format!("| {} |\n", delimiters.join(" | ")));
+                for row in rows {
+                    table.push_str(&self.render(row));
+                }
+                if !matches!(*caption, Tree::Empty(_)) {
+                    self.char_index = 0;
+                    table.push_str(&format!("\nTable: {}\n", self.render(*caption)));
+                }
+                self.char_index = 0;
+                table
+            }
+            Tree::TableRow(cells, _) => {
+                // cells must not be wrapped, as rows end at the line end
+                let cells: Vec<String> = cells
+                    .into_iter()
+                    .map(|cell| {
+                        self.char_index = 0;
+                        self.render(cell).replace('\n', " ")
+                    })
+                    .collect();
+                format!("| {} |\n", cells.join(" | "))
+            }
+            Tree::FootnoteRef(label, _) => format!("[^{label}]"),
+            Tree::Include(file, _) => format!("//include {file}"),
+            Tree::FootnoteDef(label, b_exp, _) => {
+                let outer_indent = self.indent;
+                self.indent = outer_indent + 4;
+                self.char_index = outer_indent + label.len() + 5;
+                let content = self.render(*b_exp);
+                self.indent = outer_indent;
+                self.char_index = outer_indent;
+                // the lines after the first one are indented to continue the definition
+                let mut lines = content.lines();
+                let first = lines.next().unwrap_or_default();
+                let rest: String = lines
+                    .map(|line| {
+                        if line.is_empty() {
+                            "\n".to_string()
+                        } else {
+                            format!("\n    {line}")
+                        }
+                    })
+                    .collect();
+                format!("[^{label}]: {first}{rest}\n")
+            }
+            Tree::LinkRef(_, b_exp, suffix, _) => format!("[{}]{suffix}", self.render(*b_exp)),
+            Tree::LinkDef(label, b_exp, title, _) => {
+                let url = self.render(*b_exp);
+                self.char_index = self.indent;
+                if title.is_empty() {
+                    format!("[{label}]: {url}\n")
+                } else {
+                    format!("[{label}]: {url} \"{title}\"\n")
+                }
+            }
+            Tree::BlockQuote(b_exp, _) => {
+                self.indent += 2;
+                self.char_index = self.indent;
+                let quote = self.render(*b_exp);
+                self.indent -= 2;
+                self.char_index = self.indent;
+                quote
+                    .trim_end_matches('\n')
+                    .lines()
+                    .map(|line| {
+                        if line.is_empty() {
+                            ">\n".to_string()
+                        } else {
+                            format!("> {line}\n")
+                        }
+                    })
+                    .collect()
+            }
         }
     }
 }
@@ -140,69 +287,72 @@ mod tests {
 
     #[test]
     fn line_break() {
-        assert_eq!(render(Tree::LineBreak()), "\n");
+        assert_eq!(render(Tree::LineBreak(Span::default())), "\n");
     }
 
     #[test]
     fn paragraph() {
-        assert_eq!(render(Tree::Paragraph()), "\n");
+        assert_eq!(render(Tree::Paragraph(Span::default())), "\n");
     }
 
     #[test]
     fn vspace() {
-        assert_eq!(render(Tree::VSpace()), "");
+        assert_eq!(render(Tree::VSpace(Span::default())), "");
     }
 
     #[test]
     fn em_dash() {
-        assert_eq!(render(Tree::EmDash), "\u{2014}");
+        assert_eq!(render(Tree::EmDash(Span::default())), "\u{2014}");
     }
 
     #[test]
     fn en_dash() {
-        assert_eq!(render(Tree::EnDash), "\u{2013}");
+        assert_eq!(render(Tree::EnDash(Span::default())), "\u{2013}");
     }
 
     // --- Inline formatting ---
 
     #[test]
     fn bold_node() {
-        assert_eq!(render(Tree::Bold(Box::new(lit("text")))), "*text*");
+        assert_eq!(render(Tree::Bold(Box::new(lit("text")), Span::default())), "*text*");
     }
 
     #[test]
     fn bold_starting_with_newline_moves_star_to_newline() {
         // If bold content starts with \n, the opening * is moved to the newline
-        let exp = Tree::Bold(Box::new(Tree::LineBreak().cat(lit("text"))));
+        let exp = Tree::Bold(
+            Box::new(Tree::LineBreak(Span::default()).cat(lit("text"))),
+            Span::default(),
+        );
         assert_eq!(render(exp), "\n*text*");
     }
 
     #[test]
     fn italic_node() {
-        assert_eq!(render(Tree::Italic(Box::new(lit("text")))), "_text_");
+        assert_eq!(render(Tree::Italic(Box::new(lit("text")), Span::default())), "_text_");
     }
 
     #[test]
     fn bold_italic_node() {
         assert_eq!(
-            render(Tree::BoldItalic(Box::new(lit("text")))),
+            render(Tree::BoldItalic(Box::new(lit("text")), Span::default())),
             "_**text**_"
         );
     }
 
     #[test]
     fn small_caps_node() {
-        assert_eq!(render(Tree::SmallCaps(Box::new(lit("sc")))), "{sc}");
+        assert_eq!(render(Tree::SmallCaps(Box::new(lit("sc")), Span::default())), "{sc}");
     }
 
     #[test]
     fn inline_code_node() {
-        assert_eq!(render(Tree::InlineCode(Box::new(lit("x")))), "`x`");
+        assert_eq!(render(Tree::InlineCode(Box::new(lit("x")), Span::default())), "`x`");
     }
 
     #[test]
     fn quote_node() {
-        assert_eq!(render(Tree::Quote(Box::new(lit("q")))), "\"q\"");
+        assert_eq!(render(Tree::Quote(Box::new(lit("q")), Span::default())), "\"q\"");
     }
 
     // --- Block-level nodes ---
@@ -220,7 +370,11 @@ mod tests {
     #[test]
     fn code_block_plain() {
         assert_eq!(
-            render(Tree::CodeBlock(Box::new(empty()), Box::new(prelit("code\n")))),
+            render(Tree::CodeBlock(
+                Box::new(empty()),
+                Box::new(prelit("code\n")),
+                Span::default()
+            )),
             "```\ncode\n```"
         );
     }
@@ -230,28 +384,27 @@ mod tests {
         assert_eq!(
             render(Tree::CodeBlock(
                 Box::new(lit("rust")),
-                Box::new(prelit("fn f() {}\n"))
-            )),
+                Box::new(prelit("fn f() {}\n")), Span::default())),
             "```rust\nfn f() {}\n```"
         );
     }
 
     #[test]
     fn footnote_node() {
-        assert_eq!(render(Tree::Footnote(Box::new(lit("note")))), "^(note)");
+        assert_eq!(render(Tree::Footnote(Box::new(lit("note")), Span::default())), "^(note)");
     }
 
     #[test]
     fn right_sidenote_node() {
         assert_eq!(
-            render(Tree::RightSidenote(Box::new(lit("side")))),
+            render(Tree::RightSidenote(Box::new(lit("side")), Span::default())),
             ">(side)"
         );
     }
 
     #[test]
     fn chapter_mark_node() {
-        assert_eq!(render(Tree::ChapterMark(Box::new(lit("1")))), ">>(1)");
+        assert_eq!(render(Tree::ChapterMark(Box::new(lit("1")), Span::default())), ">>(1)");
     }
 
     #[test]
@@ -300,23 +453,26 @@ mod tests {
 
     #[test]
     fn list_item_level_0() {
-        assert_eq!(render(Tree::ListItem(Box::new(lit("item")), 0)), "item");
+        assert_eq!(render(Tree::ListItem(Box::new(lit("item")), 0, Span::default())), "* item\n");
     }
 
     #[test]
     fn list_item_level_1_indented() {
-        assert_eq!(render(Tree::ListItem(Box::new(lit("item")), 1)), "  item");
+        assert_eq!(render(Tree::ListItem(Box::new(lit("item")), 1, Span::default())), "  * item\n");
     }
 
     #[test]
     fn list_renders_inner_content() {
-        assert_eq!(render(Tree::List(Box::new(lit("content")), 0)), "content");
+        assert_eq!(
+            render(Tree::List(Box::new(lit("content")), 0, ListKind::Bullet('*'), Span::default())),
+            "content"
+        );
     }
 
-    // --- Cat and Document ---
+    // --- Sequences and Document ---
 
     #[test]
-    fn cat_concatenates() {
+    fn inline_concatenates() {
         assert_eq!(render(lit("a").cat(lit("b"))), "ab");
     }
 
@@ -325,8 +481,7 @@ mod tests {
         assert_eq!(
             render(Tree::Document(
                 crate::syntax::DocType::DEFAULT,
-                Box::new(lit("body"))
-            )),
+                Box::new(lit("body")), Span::default())),
             "body"
         );
     }
@@ -345,4 +500,91 @@ mod tests {
         let output = render(lit("short line"));
         assert!(!output.contains('\n'));
     }
+
+    #[test]
+    fn ordered_list_round_trips() {
+        let input = "3) third\n4) fourth\n  * nested\n  * bullets\n5) fifth\n";
+        let (tree, _) = crate::parser::Parser::parse(input);
+        assert_eq!(render(tree), input);
+    }
+
+    #[test]
+    fn multiline_list_item_is_reflowed() {
+        let (tree, _) = crate::parser::Parser::parse("- line one\n  continues\n");
+        assert_eq!(render(tree), "- line one continues\n");
+    }
+
+    #[test]
+    fn drop_caps_and_internal_links_round_trip() {
+        let input = "%%This is [the start](#start).\n\n%Then more.";
+        let (tree, _) = crate::parser::Parser::parse(input);
+        assert_eq!(render(tree), input);
+    }
+
+    #[test]
+    fn heading_anchors_round_trip() {
+        let input = "## Start /start/\n\nsee [the start](#start)";
+        let (tree, _) = crate::parser::Parser::parse(input);
+        assert_eq!(render(tree), input);
+    }
+
+    #[test]
+    fn block_quote_round_trips() {
+        let input = "> a\n>\n> > nested\n> - item\n";
+        let (tree, _) = crate::parser::Parser::parse(input);
+        assert_eq!(render(tree), input);
+    }
+
+    #[test]
+    fn footnote_definition_round_trips() {
+        let input = "a[^1]\n\n[^1]: one\n    two\n\n    three\n";
+        let (tree, _) = crate::parser::Parser::parse(input);
+        assert_eq!(render(tree), input);
+    }
+
+    #[test]
+    fn autolinks_are_written_in_angle_brackets() {
+        let (tree, _) = crate::parser::Parser::parse("see https://x.org, or <me@x.org>");
+        assert_eq!(render(tree), "see <https://x.org>, or <me@x.org>");
+    }
+
+    #[test]
+    fn links_keep_the_space_after_them() {
+        let input = "a <https://q.org> b [t](u) c";
+        let (tree, _) = crate::parser::Parser::parse(input);
+        assert_eq!(render(tree), input);
+    }
+
+    #[test]
+    fn reference_links_round_trip() {
+        let input = "[a][Ref] b [c][] [d]\n\n[Ref]: http://example.com \"Title\"\n[c]: <#c>\n";
+        let (tree, _) = crate::parser::Parser::parse(input);
+        assert_eq!(
+            render(tree),
+            "[a][Ref] b [c][] [d]\n\n[Ref]: http://example.com \"Title\"\n[c]: #c\n"
+        );
+    }
+
+    // --- Tables ---
+
+    #[test]
+    fn backslash_escapes_round_trip() {
+        let input = "\\*a\\* \\\\ \\q\n\n| a \\| b |\n| --- |\n";
+        let (tree, _) = crate::parser::Parser::parse(input);
+        assert_eq!(render(tree), input);
+    }
+
+    #[test]
+    fn table_round_trips() {
+        let input = "| *a* | b |\n| :-: | --: |\n| 1 | 2 |\n\nTable: caption\n";
+        let (tree, _) = crate::parser::Parser::parse(input);
+        assert_eq!(render(tree), input);
+    }
+
+    #[test]
+    fn long_table_cells_are_not_wrapped() {
+        let cell = "word ".repeat(20);
+        let row = Tree::TableRow(vec![lit(cell.trim())], Span::default());
+        assert_eq!(render(row), format!("| {} |\n", cell.trim()));
+    }
 }
@@ -3,6 +3,11 @@
 
 use std::fmt;
 
+pub mod dot;
+pub mod json;
+mod traverse;
+pub use traverse::{fold_children, visit_children, Fold, Visit};
+
 #[derive(Debug, Clone)]
 pub enum DocType {
     DEFAULT,
@@ -11,269 +16,579 @@ pub enum DocType {
     LETTER,
 }
 
-/// Expressions are the building blocks of an abstract syntax tree
-#[derive(Debug)]
+/// Horizontal alignment of a table column, as given in the delimiter
+/// row of a pipe table: `:--`, `:-:` or `--:`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    /// no colons, left to the renderer
+    Default,
+    Left,
+    Center,
+    Right,
+}
+
+/// Kind of a list, as given by the marker of its first item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListKind {
+    /// a bullet list, with `*` or `-` as marker
+    Bullet(char),
+    /// a numbered list starting at the given number, with `.` or `)`
+    /// following the numbers
+    Ordered(u32, char),
+}
+
+impl ListKind {
+    /// true, if an item with marker kind `other` continues a list of this kind
+    #[must_use]
+    pub fn continued_by(self, other: Self) -> bool {
+        match (self, other) {
+            (Self::Bullet(a), Self::Bullet(b)) => a == b,
+            (Self::Ordered(_, a), Self::Ordered(_, b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Location of a node in the markdown source.
+///
+/// Offsets are byte offsets into the input, lines and columns are
+/// 1-based, columns count bytes from the beginning of the line. Nodes
+/// that were not created by the parser carry the default span, which
+/// has a line of 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    /// byte offset of the first character of the node
+    pub start: usize,
+    /// byte offset one past the last character of the node
+    pub end: usize,
+    /// line of the first character
+    pub line: usize,
+    /// column of the first character
+    pub column: usize,
+    /// line of the character following the node
+    pub end_line: usize,
+    /// column of the character following the node
+    pub end_column: usize,
+}
+
+impl Span {
+    /// true, if this span was not set by the parser
+    pub const fn is_unknown(&self) -> bool {
+        self.line == 0
+    }
+
+    /// returns a span covering both `self` and `other`. unknown spans are ignored.
+    #[must_use]
+    pub fn union(self, other: Span) -> Span {
+        if self.is_unknown() {
+            return other;
+        }
+        if other.is_unknown() {
+            return self;
+        }
+        let first = if self.start <= other.start { self } else { other };
+        let last = if self.end >= other.end { self } else { other };
+        Span {
+            start: first.start,
+            end: last.end,
+            line: first.line,
+            column: first.column,
+            end_line: last.end_line,
+            end_column: last.end_column,
+        }
+    }
+}
+
+/// Expressions are the building blocks of an abstract syntax tree.
+///
+/// Every variant carries the [`Span`] of the markdown it was parsed
+/// from as its last field.
+#[derive(Clone)]
 pub enum Tree {
-    Document(DocType, Box<Tree>),
+    Document(DocType, Box<Tree>, Span),
     /// Separate consequential paragraphs
-    Paragraph(),
+    Paragraph(Span),
     /// code and stuff
-    PreformattedLiteral(String),
+    PreformattedLiteral(String, Span),
     /// A literal is a string rendered as is
-    Literal(String),
+    Literal(String, Span),
     /// An escaped literal probably has to be treated in a special
     /// way, depending on the rendering backend
-    EscapeLit(String),
+    EscapeLit(String, Span),
+    /// A markdown metacharacter escaped with a backslash, like `\*`,
+    /// to be taken literally
+    Escaped(char, Span),
     /// A dropping capital, usually found at the beginning of chapters
     /// lowering down given amount of lines
-    DropCap(u8, u8),
+    DropCap(u8, u8, Span),
     // A color specification
-    Color(Box<Tree>),
+    Color(Box<Tree>, Span),
     /// Most often a single digit signifying the chapter number, and a color
-    ChapterMark(Box<Tree>),
+    ChapterMark(Box<Tree>, Span),
     /// Section headers with a separate parameter specifying the level
-    Heading(Box<Tree>, u8, String),
+    Heading(Box<Tree>, u8, String, Span),
     /// Encapsulates boldness; can contain various other formattings
-    Bold(Box<Tree>),
+    Bold(Box<Tree>, Span),
     /// Encapsulates cursiveness; can contain varios other formattings
-    Italic(Box<Tree>),
+    Italic(Box<Tree>, Span),
     /// Bold and Italic at the same time - nesting does not work here
     /// as we ne a special escape sequence to activate this: \*[BDI]
-    BoldItalic(Box<Tree>),
-    SmallCaps(Box<Tree>),
+    BoldItalic(Box<Tree>, Span),
+    SmallCaps(Box<Tree>, Span),
     /// Encapsulates code placed as a separate block, set apart from
     /// normal, flowing text
-    CodeBlock(Box<Tree>, Box<Tree>),
+    CodeBlock(Box<Tree>, Box<Tree>, Span),
     /// Encapsulates text rendered in non-proportional font, usually
     /// used for computer code, placed in line with normal text
-    InlineCode(Box<Tree>),
-    Quote(Box<Tree>),
-    Footnote(Box<Tree>),
-    RightSidenote(Box<Tree>),
+    InlineCode(Box<Tree>, Span),
+    Quote(Box<Tree>, Span),
+    Footnote(Box<Tree>, Span),
+    RightSidenote(Box<Tree>, Span),
     // web-link
-    HyperRef(Box<Tree>, Box<Tree>),
+    HyperRef(Box<Tree>, Box<Tree>, Span),
     // document internal link
-    DocRef(String, Box<Tree>),
-    // this enables composition, forming the tree
-    Cat(Box<Tree>, Box<Tree>),
+    DocRef(String, Box<Tree>, Span),
+    /// a sequence of blocks, parsed with the complete mark-up set, like the
+    /// paragraphs and headings of the document, the items of a list or the
+    /// lines of a block quote
+    Block(Vec<Tree>, Span),
+    /// a sequence of inline elements, like the text and formatting of a link
+    /// text or of a table cell
+    Inline(Vec<Tree>, Span),
     // Lists, should contain ListItems
-    List(Box<Tree>, u8),
+    List(Box<Tree>, u8, ListKind, Span),
     // singular items of lists
-    ListItem(Box<Tree>, u8),
+    ListItem(Box<Tree>, u8, Span),
     // captures a meta data block, basically a list of key values
     // like title, author etc.
-    MetaDataBlock(Box<Tree>),
+    MetaDataBlock(Box<Tree>, Span),
     // a singular meta data item
-    MetaDataItem(String, String),
+    MetaDataItem(String, String, Span),
     // captures size specification in the of XxY
-    ImageSizeSpec(Box<Tree>, Box<Tree>),
+    ImageSizeSpec(Box<Tree>, Box<Tree>, Span),
     /// image with caption, path, and image size spec
-    Image(Box<Tree>, Box<Tree>, Box<Tree>),
+    Image(Box<Tree>, Box<Tree>, Box<Tree>, Span),
     /// new line
-    LineBreak(),
+    LineBreak(Span),
     /// groff knows .SP instructions, which are important to insert
     /// after headings to introduce some vertical space after the heading
-    VSpace(),
+    VSpace(Span),
     // this is a neutral element, yielding no ouput
-    Empty(),
+    Empty(Span),
     /// typographic em dash (---)
-    EmDash,
+    EmDash(Span),
     /// typographic en dash (--)
-    EnDash,
+    EnDash(Span),
+    /// pipe table with column alignments, a header row, body rows
+    /// and a caption, which is `Empty` if there is none
+    Table(Vec<Alignment>, Box<Tree>, Vec<Tree>, Box<Tree>, Span),
+    /// a row of table cells
+    TableRow(Vec<Tree>, Span),
+    /// a quotation set apart from the text, lines starting with `>`
+    BlockQuote(Box<Tree>, Span),
+    // the footnotes, links and include processors resolve the nodes below,
+    // renderers only meet them when those did not run
+    /// reference `[^label]` to a footnote defined elsewhere
+    FootnoteRef(String, Span),
+    /// definition `[^label]: ...` of a referenced footnote
+    FootnoteDef(String, Box<Tree>, Span),
+    /// reference link with label, link text and the markdown following the
+    /// text: `[ref]` for `[text][ref]`, `[]` for `[ref][]` and nothing for `[ref]`
+    LinkRef(String, Box<Tree>, String, Span),
+    /// definition `[label]: url "title"` of a reference link target, with
+    /// label, url literal and title, which is empty if there is none
+    LinkDef(String, Box<Tree>, String, Span),
+    /// `//include file.md` directive, to be replaced by the parsed file. the
+    /// spec names the file and, optionally, the part of it to include, like
+    /// `file.md:10-20` or `file.md#Heading`, split by the include processor
+    Include(String, Span),
 }
 
 impl Tree {
-    /// constructs new Exp of self and expr
+    /// constructs an inline sequence of self followed by expr. inline sequences
+    /// are extended instead of nested.
     #[must_use]
     pub fn cat(self, expr: Self) -> Self {
-        Self::Cat(Box::new(self), Box::new(expr))
+        let span = self.span().union(expr.span());
+        let mut children = match self {
+            Self::Inline(children, _) => children,
+            _ => vec![self],
+        };
+        match expr {
+            Self::Inline(more, _) => children.extend(more),
+            _ => children.push(expr),
+        }
+        Self::Inline(children, span)
     }
-    pub fn cat_box(self, expr: Box<Self>) -> Box<Self> {
-        Box::new(Self::Cat(Box::new(self), expr))
+
+    /// the source location this node was parsed from
+    pub fn span(&self) -> Span {
+        match self {
+            Tree::Document(_, _, s)
+            | Tree::Paragraph(s)
+            | Tree::PreformattedLiteral(_, s)
+            | Tree::Literal(_, s)
+            | Tree::EscapeLit(_, s)
+            | Tree::Escaped(_, s)
+            | Tree::DropCap(_, _, s)
+            | Tree::Color(_, s)
+            | Tree::ChapterMark(_, s)
+            | Tree::Heading(_, _, _, s)
+            | Tree::Bold(_, s)
+            | Tree::Italic(_, s)
+            | Tree::BoldItalic(_, s)
+            | Tree::SmallCaps(_, s)
+            | Tree::CodeBlock(_, _, s)
+            | Tree::InlineCode(_, s)
+            | Tree::Quote(_, s)
+            | Tree::Footnote(_, s)
+            | Tree::RightSidenote(_, s)
+            | Tree::HyperRef(_, _, s)
+            | Tree::DocRef(_, _, s)
+            | Tree::Block(_, s)
+            | Tree::Inline(_, s)
+            | Tree::List(_, _, _, s)
+            | Tree::ListItem(_, _, s)
+            | Tree::MetaDataBlock(_, s)
+            | Tree::MetaDataItem(_, _, s)
+            | Tree::ImageSizeSpec(_, _, s)
+            | Tree::Image(_, _, _, s)
+            | Tree::LineBreak(s)
+            | Tree::VSpace(s)
+            | Tree::Empty(s)
+            | Tree::EmDash(s)
+            | Tree::EnDash(s)
+            | Tree::Table(_, _, _, _, s)
+            | Tree::BlockQuote(_, s)
+            | Tree::FootnoteRef(_, s)
+            | Tree::Include(_, s)
+            | Tree::FootnoteDef(_, _, s)
+            | Tree::LinkRef(_, _, _, s)
+            | Tree::LinkDef(_, _, _, s)
+            | Tree::TableRow(_, s) => *s,
+        }
     }
-}
 
-fn address_of(t: &Tree) -> String {
-    format!("{t:p}").strip_prefix('0').unwrap().to_owned()
-}
+    /// replaces the span of this node
+    #[must_use]
+    pub fn with_span(mut self, span: Span) -> Self {
+        *self.span_mut() = span;
+        self
+    }
 
-/// incomplete display impl that generates graphviz dot
-/// notation tree
-impl fmt::Display for Tree {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn span_mut(&mut self) -> &mut Span {
         match self {
-            Tree::Cat(t1, t2) => {
-                write!(
-                    f,
-                    "{}[label=\"||\"];\n{} -> {{{},{}}};\n{}\n{}",
-                    address_of(self),
-                    address_of(self),
-                    address_of(t1),
-                    address_of(t2),
-                    *t1,
-                    *t2
-                )
+            Tree::Document(_, _, s)
+            | Tree::Paragraph(s)
+            | Tree::PreformattedLiteral(_, s)
+            | Tree::Literal(_, s)
+            | Tree::EscapeLit(_, s)
+            | Tree::Escaped(_, s)
+            | Tree::DropCap(_, _, s)
+            | Tree::Color(_, s)
+            | Tree::ChapterMark(_, s)
+            | Tree::Heading(_, _, _, s)
+            | Tree::Bold(_, s)
+            | Tree::Italic(_, s)
+            | Tree::BoldItalic(_, s)
+            | Tree::SmallCaps(_, s)
+            | Tree::CodeBlock(_, _, s)
+            | Tree::InlineCode(_, s)
+            | Tree::Quote(_, s)
+            | Tree::Footnote(_, s)
+            | Tree::RightSidenote(_, s)
+            | Tree::HyperRef(_, _, s)
+            | Tree::DocRef(_, _, s)
+            | Tree::Block(_, s)
+            | Tree::Inline(_, s)
+            | Tree::List(_, _, _, s)
+            | Tree::ListItem(_, _, s)
+            | Tree::MetaDataBlock(_, s)
+            | Tree::MetaDataItem(_, _, s)
+            | Tree::ImageSizeSpec(_, _, s)
+            | Tree::Image(_, _, _, s)
+            | Tree::LineBreak(s)
+            | Tree::VSpace(s)
+            | Tree::Empty(s)
+            | Tree::EmDash(s)
+            | Tree::EnDash(s)
+            | Tree::Table(_, _, _, _, s)
+            | Tree::BlockQuote(_, s)
+            | Tree::FootnoteRef(_, s)
+            | Tree::Include(_, s)
+            | Tree::FootnoteDef(_, _, s)
+            | Tree::LinkRef(_, _, _, s)
+            | Tree::LinkDef(_, _, _, s)
+            | Tree::TableRow(_, s) => s,
+        }
+    }
+
+    /// the nodes directly below this one
+    pub fn children(&self) -> Vec<&Tree> {
+        match self {
+            Tree::Document(_, t, _)
+            | Tree::Color(t, _)
+            | Tree::ChapterMark(t, _)
+            | Tree::Heading(t, _, _, _)
+            | Tree::Bold(t, _)
+            | Tree::Italic(t, _)
+            | Tree::BoldItalic(t, _)
+            | Tree::SmallCaps(t, _)
+            | Tree::InlineCode(t, _)
+            | Tree::Quote(t, _)
+            | Tree::Footnote(t, _)
+            | Tree::RightSidenote(t, _)
+            | Tree::DocRef(_, t, _)
+            | Tree::List(t, _, _, _)
+            | Tree::ListItem(t, _, _)
+            | Tree::MetaDataBlock(t, _)
+            | Tree::BlockQuote(t, _)
+            | Tree::FootnoteDef(_, t, _)
+            | Tree::LinkRef(_, t, _, _)
+            | Tree::LinkDef(_, t, _, _) => vec![&**t],
+            Tree::CodeBlock(t1, t2, _)
+            | Tree::HyperRef(t1, t2, _)
+            | Tree::ImageSizeSpec(t1, t2, _) => vec![&**t1, &**t2],
+            Tree::Image(t1, t2, t3, _) => vec![&**t1, &**t2, &**t3],
+            Tree::Table(_, header, rows, caption, _) => std::iter::once(&**header)
+                .chain(rows.iter())
+                .chain(std::iter::once(&**caption))
+                .collect(),
+            Tree::TableRow(cells, _) | Tree::Block(cells, _) | Tree::Inline(cells, _) => {
+                cells.iter().collect()
             }
-            Tree::Literal(s) => write!(
-                f,
-                "{} [label=\"l('{}')\"];",
-                address_of(self),
-                s.replace("\"", "")
-            ),
-            Tree::Document(_, t) => write!(
-                f,
-                "digraph graphname {{\n{}[label=\"Root\"];\n{} ->{};\n{}\n}}",
-                address_of(self),
-                address_of(self),
-                address_of(t),
-                t
-            ),
-            Tree::Paragraph() => write!(f, "{} [label=\"P\"];", address_of(self)),
-            Tree::PreformattedLiteral(_) => todo!(),
-            Tree::EscapeLit(s) => write!(
-                f,
-                "{} [label=\"el('{}')\"];",
-                address_of(self),
-                s.replace("\"", "")
-            ),
-            Tree::DropCap(_, _) => todo!(),
-            Tree::Color(_) => todo!(),
-            Tree::ChapterMark(_) => todo!(),
-            Tree::Heading(t, lvl, _) => {
-                write!(
-                    f,
-                    "{} [label=\"H {}\"];\n{} -> {};\n{}",
-                    address_of(self),
-                    lvl,
-                    address_of(self),
-                    address_of(t),
-                    *t
-                )
+            Tree::Paragraph(_)
+            | Tree::PreformattedLiteral(_, _)
+            | Tree::Literal(_, _)
+            | Tree::EscapeLit(_, _)
+            | Tree::Escaped(_, _)
+            | Tree::DropCap(_, _, _)
+            | Tree::MetaDataItem(_, _, _)
+            | Tree::FootnoteRef(_, _)
+            | Tree::Include(_, _)
+            | Tree::LineBreak(_)
+            | Tree::VSpace(_)
+            | Tree::Empty(_)
+            | Tree::EmDash(_)
+            | Tree::EnDash(_) => vec![],
+        }
+    }
+
+    /// the nodes directly below this one, mutably
+    pub fn children_mut(&mut self) -> Vec<&mut Tree> {
+        match self {
+            Tree::Document(_, t, _)
+            | Tree::Color(t, _)
+            | Tree::ChapterMark(t, _)
+            | Tree::Heading(t, _, _, _)
+            | Tree::Bold(t, _)
+            | Tree::Italic(t, _)
+            | Tree::BoldItalic(t, _)
+            | Tree::SmallCaps(t, _)
+            | Tree::InlineCode(t, _)
+            | Tree::Quote(t, _)
+            | Tree::Footnote(t, _)
+            | Tree::RightSidenote(t, _)
+            | Tree::DocRef(_, t, _)
+            | Tree::List(t, _, _, _)
+            | Tree::ListItem(t, _, _)
+            | Tree::MetaDataBlock(t, _)
+            | Tree::BlockQuote(t, _)
+            | Tree::FootnoteDef(_, t, _)
+            | Tree::LinkRef(_, t, _, _)
+            | Tree::LinkDef(_, t, _, _) => vec![&mut **t],
+            Tree::CodeBlock(t1, t2, _)
+            | Tree::HyperRef(t1, t2, _)
+            | Tree::ImageSizeSpec(t1, t2, _) => vec![&mut **t1, &mut **t2],
+            Tree::Image(t1, t2, t3, _) => vec![&mut **t1, &mut **t2, &mut **t3],
+            Tree::Table(_, header, rows, caption, _) => std::iter::once(&mut **header)
+                .chain(rows.iter_mut())
+                .chain(std::iter::once(&mut **caption))
+                .collect(),
+            Tree::TableRow(cells, _) | Tree::Block(cells, _) | Tree::Inline(cells, _) => {
+                cells.iter_mut().collect()
+            }
+            Tree::Paragraph(_)
+            | Tree::PreformattedLiteral(_, _)
+            | Tree::Literal(_, _)
+            | Tree::EscapeLit(_, _)
+            | Tree::Escaped(_, _)
+            | Tree::DropCap(_, _, _)
+            | Tree::MetaDataItem(_, _, _)
+            | Tree::FootnoteRef(_, _)
+            | Tree::Include(_, _)
+            | Tree::LineBreak(_)
+            | Tree::VSpace(_)
+            | Tree::Empty(_)
+            | Tree::EmDash(_)
+            | Tree::EnDash(_) => vec![],
+        }
+    }
+
+    /// applies `f` to the spans of this node and of all nodes below it
+    pub fn map_spans(&mut self, f: &mut impl FnMut(&mut Span)) {
+        f(self.span_mut());
+        for child in self.children_mut() {
+            child.map_spans(f);
+        }
+    }
+
+    /// does this node start with a word, rather than with white space or
+    /// punctuation? the parser takes the space after a link as part of it, a
+    /// link followed by a word needs it put back
+    pub fn starts_with_word(&self) -> bool {
+        match self {
+            Tree::Literal(s, _) | Tree::EscapeLit(s, _) => {
+                s.starts_with(|c: char| !c.is_whitespace() && !c.is_ascii_punctuation())
             }
-            Tree::Bold(t) => write!(
-                f,
-                "{} [label=\"B\"];\n{} -> {};\n{}",
-                address_of(self),
-                address_of(self),
-                address_of(t),
-                *t
-            ),
-            Tree::Italic(t) => write!(
-                f,
-                "{} [label=\"I\"];\n{} -> {};\n{}",
-                address_of(self),
-                address_of(self),
-                address_of(t),
-                *t
-            ),
-            Tree::BoldItalic(_) => todo!(),
-            Tree::SmallCaps(_) => todo!(),
-            Tree::CodeBlock(_, _) => todo!(),
-            Tree::InlineCode(t) => write!(
-                f,
-                "{} [label=\"C\"]; {} -> {};\n{}",
-                address_of(self),
-                address_of(self),
-                address_of(t),
-                *t
-            ),
-            Tree::Quote(_) => todo!(),
-            Tree::Footnote(_) => todo!(),
-            Tree::RightSidenote(_) => todo!(),
-            Tree::HyperRef(_, _) => todo!(),
-            Tree::DocRef(_, _) => todo!(),
-            Tree::List(t, l) => write!(
-                f,
-                "{} [label=\"L {}\"];\n{} -> {};\n{}",
-                address_of(self),
-                l,
-                address_of(self),
-                address_of(t),
-                *t
-            ),
-            Tree::ListItem(t, l) => write!(
-                f,
-                "{} [label=\"Li {}\"];\n{} -> {};\n{}",
-                address_of(self),
-                l,
-                address_of(self),
-                address_of(t),
-                *t
-            ),
-            Tree::MetaDataBlock(_) => todo!(),
-            Tree::MetaDataItem(_, _) => todo!(),
-            Tree::ImageSizeSpec(_, _) => todo!(),
-            Tree::Image(_, _, _) => todo!(),
-            Tree::LineBreak() => write!(f, "{} [label=\"\\\\n\"]", address_of(self)),
-            Tree::VSpace() => write!(f, "{} [label=\"V\"]", address_of(self)),
-            Tree::Empty() => write!(f, "{} [label=\"\"]", address_of(self)),
-            Tree::EmDash => write!(f, "{} [label=\"---\"]", address_of(self)),
-            Tree::EnDash => write!(f, "{} [label=\"--\"]", address_of(self)),
+            Tree::Escaped(c, _) => !c.is_whitespace() && !c.is_ascii_punctuation(),
+            Tree::Bold(t, _)
+            | Tree::Italic(t, _)
+            | Tree::BoldItalic(t, _)
+            | Tree::SmallCaps(t, _)
+            | Tree::HyperRef(t, _, _)
+            | Tree::DocRef(_, t, _) => t.starts_with_word(),
+            // code and quotes start with a mark in the markdown, too
+            Tree::InlineCode(..) | Tree::Quote(..) => true,
+            Tree::Inline(children, _) => children.first().is_some_and(Tree::starts_with_word),
+            _ => false,
+        }
+    }
+}
+
+/// Debug output deliberately leaves out spans, so that dumped
+/// trees stay readable and comparable. Use [`Tree::span`] to
+/// inspect source locations.
+impl fmt::Debug for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        macro_rules! tuple {
+            ($name:expr $(, $field:expr)*) => {
+                f.debug_tuple($name)$(.field($field))*.finish()
+            };
+        }
+        match self {
+            Tree::Document(dt, t, _) => tuple!("Document", dt, t),
+            Tree::Paragraph(_) => tuple!("Paragraph"),
+            Tree::PreformattedLiteral(s, _) => tuple!("PreformattedLiteral", s),
+            Tree::Literal(s, _) => tuple!("Literal", s),
+            Tree::EscapeLit(s, _) => tuple!("EscapeLit", s),
+            Tree::Escaped(c, _) => tuple!("Escaped", c),
+            Tree::DropCap(c, l, _) => tuple!("DropCap", c, l),
+            Tree::Color(t, _) => tuple!("Color", t),
+            Tree::ChapterMark(t, _) => tuple!("ChapterMark", t),
+            Tree::Heading(t, l, n, _) => tuple!("Heading", t, l, n),
+            Tree::Bold(t, _) => tuple!("Bold", t),
+            Tree::Italic(t, _) => tuple!("Italic", t),
+            Tree::BoldItalic(t, _) => tuple!("BoldItalic", t),
+            Tree::SmallCaps(t, _) => tuple!("SmallCaps", t),
+            Tree::CodeBlock(t1, t2, _) => tuple!("CodeBlock", t1, t2),
+            Tree::InlineCode(t, _) => tuple!("InlineCode", t),
+            Tree::Quote(t, _) => tuple!("Quote", t),
+            Tree::Footnote(t, _) => tuple!("Footnote", t),
+            Tree::RightSidenote(t, _) => tuple!("RightSidenote", t),
+            Tree::HyperRef(t1, t2, _) => tuple!("HyperRef", t1, t2),
+            Tree::DocRef(s, t, _) => tuple!("DocRef", s, t),
+            Tree::Block(c, _) => tuple!("Block", c),
+            Tree::Inline(c, _) => tuple!("Inline", c),
+            Tree::List(t, l, k, _) => tuple!("List", t, l, k),
+            Tree::ListItem(t, l, _) => tuple!("ListItem", t, l),
+            Tree::MetaDataBlock(t, _) => tuple!("MetaDataBlock", t),
+            Tree::MetaDataItem(k, v, _) => tuple!("MetaDataItem", k, v),
+            Tree::ImageSizeSpec(t1, t2, _) => tuple!("ImageSizeSpec", t1, t2),
+            Tree::Image(t1, t2, t3, _) => tuple!("Image", t1, t2, t3),
+            Tree::LineBreak(_) => tuple!("LineBreak"),
+            Tree::VSpace(_) => tuple!("VSpace"),
+            Tree::Empty(_) => tuple!("Empty"),
+            Tree::EmDash(_) => tuple!("EmDash"),
+            Tree::EnDash(_) => tuple!("EnDash"),
+            Tree::Table(a, h, r, c, _) => tuple!("Table", a, h, r, c),
+            Tree::TableRow(c, _) => tuple!("TableRow", c),
+            Tree::BlockQuote(t, _) => tuple!("BlockQuote", t),
+            Tree::FootnoteRef(l, _) => tuple!("FootnoteRef", l),
+            Tree::FootnoteDef(l, t, _) => tuple!("FootnoteDef", l, t),
+            Tree::LinkRef(l, t, s, _) => tuple!("LinkRef", l, t, s),
+            Tree::LinkDef(l, u, t, _) => tuple!("LinkDef", l, u, t),
+            Tree::Include(f, _) => tuple!("Include", f),
         }
     }
 }
 
+/// graphviz dot notation of the tree, see [`dot::to_dot`]
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&dot::to_dot(self, false))
+    }
+}
+
 // TODO all these to_string invocation incur a copy!
 #[must_use]
 pub fn lit(s: &str) -> Tree {
-    Tree::Literal(s.to_string())
+    Tree::Literal(s.to_string(), Span::default())
 }
 #[must_use]
 pub fn prelit(s: &str) -> Tree {
-    Tree::PreformattedLiteral(s.to_string())
+    Tree::PreformattedLiteral(s.to_string(), Span::default())
 }
 #[must_use]
 pub fn escape_lit(s: &str) -> Tree {
-    Tree::EscapeLit(s.to_string())
+    Tree::EscapeLit(s.to_string(), Span::default())
 }
 #[must_use]
 pub fn heading(exp: Tree, lvl: u8, name: &str) -> Tree {
-    Tree::Heading(Box::new(exp), lvl, name.to_string())
+    Tree::Heading(Box::new(exp), lvl, name.to_string(), Span::default())
 }
 #[must_use]
 pub fn color(exp: Tree) -> Tree {
-    Tree::Color(Box::new(exp))
+    Tree::Color(Box::new(exp), Span::default())
 }
 #[must_use]
 pub fn footnote(exp: Tree) -> Tree {
-    Tree::Footnote(Box::new(exp))
+    Tree::Footnote(Box::new(exp), Span::default())
 }
 #[must_use]
 pub fn hyperref(exp1: Tree, exp2: Tree) -> Tree {
-    Tree::HyperRef(Box::new(exp1), Box::new(exp2))
+    Tree::HyperRef(Box::new(exp1), Box::new(exp2), Span::default())
 }
 #[must_use]
 pub fn bold(exp: Tree) -> Tree {
-    Tree::Bold(Box::new(exp))
+    Tree::Bold(Box::new(exp), Span::default())
 }
 #[must_use]
-pub fn list(exp: Tree, level: u8) -> Tree {
-    Tree::List(Box::new(exp), level)
+pub fn list(exp: Tree, level: u8, kind: ListKind) -> Tree {
+    Tree::List(Box::new(exp), level, kind, Span::default())
 }
 #[must_use]
 pub fn list_item(exp: Tree, level: u8) -> Tree {
-    Tree::ListItem(Box::new(exp), level)
+    Tree::ListItem(Box::new(exp), level, Span::default())
 }
 #[must_use]
 pub fn meta_data_item(key: String, value: String) -> Tree {
-    Tree::MetaDataItem(key, value)
+    Tree::MetaDataItem(key, value, Span::default())
 }
 #[must_use]
 pub fn meta_data_block(exp: Tree) -> Tree {
-    Tree::MetaDataBlock(Box::new(exp))
+    Tree::MetaDataBlock(Box::new(exp), Span::default())
 }
 #[must_use]
 pub fn image_size(x: Tree, y: Tree) -> Tree {
-    Tree::ImageSizeSpec(Box::new(x), Box::new(y))
+    Tree::ImageSizeSpec(Box::new(x), Box::new(y), Span::default())
 }
 #[must_use]
 pub fn image(caption: Tree, path: Tree, size_spec: Tree) -> Tree {
-    Tree::Image(Box::new(caption), Box::new(path), Box::new(size_spec))
+    Tree::Image(Box::new(caption), Box::new(path), Box::new(size_spec), Span::default())
 }
 #[must_use]
 pub fn empty() -> Tree {
-    Tree::Empty()
+    Tree::Empty(Span::default())
 }
 pub fn cat(t1: Tree, t2: Tree) -> Tree {
-    Tree::Cat(Box::new(t1), Box::new(t2))
+    t1.cat(t2)
+}
+/// constructs a block sequence of `children`
+#[must_use]
+pub fn block(children: Vec<Tree>) -> Tree {
+    let span = children
+        .iter()
+        .fold(Span::default(), |span, child| span.union(child.span()));
+    Tree::Block(children, span)
 }
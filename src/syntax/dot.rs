@@ -0,0 +1,253 @@
+//! graphviz dot export of syntax trees, meant for debugging the parser
+//!
+//! Nodes are numbered in the order they are visited, so exporting the same
+//! tree twice yields the same graph. Block nodes are drawn as boxes, inline
+//! nodes as ellipses. With clustering turned on, every run of inline nodes
+//! below a block node is grouped into a cluster of its own.
+
+use super::Tree;
+
+/// literal text in labels is cut off after this many characters
+const MAX_TEXT_LEN: usize = 40;
+
+/// exports `tree` as a graphviz digraph, optionally clustering inline nodes
+#[must_use]
+pub fn to_dot(tree: &Tree, cluster_inlines: bool) -> String {
+    let mut exporter = Exporter {
+        cluster_inlines,
+        next_id: 0,
+        nodes: String::new(),
+        edges: String::new(),
+    };
+    exporter.node(tree, 1, false);
+    format!(
+        "digraph mato {{\n  node [fontname=\"monospace\"];\n{}{}}}\n",
+        exporter.nodes, exporter.edges
+    )
+}
+
+struct Exporter {
+    cluster_inlines: bool,
+    next_id: usize,
+    nodes: String,
+    edges: String,
+}
+
+impl Exporter {
+    /// writes `tree` and everything below it, returns the id of its node
+    fn node(&mut self, tree: &Tree, depth: usize, in_cluster: bool) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let indent = "  ".repeat(depth);
+        let shape = if is_block(tree) { "box" } else { "ellipse" };
+        self.nodes.push_str(&format!(
+            "{indent}n{id} [shape={shape}, label=\"{}\"];\n",
+            escape(&label(tree))
+        ));
+        let names = edge_names(tree);
+        let mut in_run = false;
+        for (i, child) in tree.children().into_iter().enumerate() {
+            let inline = self.cluster_inlines && !in_cluster && !is_block(child);
+            if inline && !in_run {
+                self.nodes.push_str(&format!(
+                    "{indent}subgraph cluster_{} {{\n{indent}  style=dashed; color=grey;\n",
+                    self.next_id
+                ));
+            } else if !inline && in_run {
+                self.nodes.push_str(&format!("{indent}}}\n"));
+            }
+            in_run = inline;
+            let child_depth = if in_run { depth + 1 } else { depth };
+            let child_id = self.node(child, child_depth, in_cluster || in_run);
+            match names.get(i) {
+                Some(name) => self
+                    .edges
+                    .push_str(&format!("  n{id} -> n{child_id} [label=\"{name}\"];\n")),
+                None => self.edges.push_str(&format!("  n{id} -> n{child_id};\n")),
+            }
+        }
+        if in_run {
+            self.nodes.push_str(&format!("{indent}}}\n"));
+        }
+        id
+    }
+}
+
+/// true for nodes that structure the document, as opposed to running text
+fn is_block(tree: &Tree) -> bool {
+    matches!(
+        tree,
+        Tree::Document(..)
+            | Tree::Paragraph(..)
+            | Tree::ChapterMark(..)
+            | Tree::Heading(..)
+            | Tree::CodeBlock(..)
+            | Tree::Block(..)
+            | Tree::List(..)
+            | Tree::ListItem(..)
+            | Tree::MetaDataBlock(..)
+            | Tree::MetaDataItem(..)
+            | Tree::VSpace(..)
+            | Tree::Table(..)
+            | Tree::TableRow(..)
+            | Tree::BlockQuote(..)
+            | Tree::FootnoteDef(..)
+            | Tree::LinkDef(..)
+    )
+}
+
+/// names of the edges to the children of nodes with children of different roles
+fn edge_names(tree: &Tree) -> Vec<&'static str> {
+    match tree {
+        Tree::CodeBlock(..) => vec!["type", "content"],
+        Tree::HyperRef(..) => vec!["text", "url"],
+        Tree::ImageSizeSpec(..) => vec!["width", "height"],
+        Tree::Image(..) => vec!["caption", "path", "size"],
+        Tree::LinkDef(..) => vec!["url"],
+        Tree::Table(_, _, rows, _, _) => {
+            let mut names = vec!["header"];
+            names.extend(std::iter::repeat_n("row", rows.len()));
+            names.push("caption");
+            names
+        }
+        _ => vec![],
+    }
+}
+
+/// quotes `s`, cutting it short if it is too long to be readable in a graph
+fn text(s: &str) -> String {
+    if s.chars().count() > MAX_TEXT_LEN {
+        let short: String = s.chars().take(MAX_TEXT_LEN).collect();
+        format!("\"{short}...\"")
+    } else {
+        format!("\"{s}\"")
+    }
+}
+
+/// the name of the node, followed by the data it holds besides its children
+fn label(tree: &Tree) -> String {
+    match tree {
+        Tree::Document(dt, _, _) => format!("Document\n{dt:?}"),
+        Tree::Paragraph(_) => "Paragraph".to_string(),
+        Tree::PreformattedLiteral(s, _) => format!("PreformattedLiteral\n{}", text(s)),
+        Tree::Literal(s, _) => format!("Literal\n{}", text(s)),
+        Tree::EscapeLit(s, _) => format!("EscapeLit\n{}", text(s)),
+        Tree::Escaped(c, _) => format!("Escaped\n'{c}'"),
+        Tree::DropCap(c, l, _) => format!("DropCap\n{c} {l}"),
+        Tree::Color(_, _) => "Color".to_string(),
+        Tree::ChapterMark(_, _) => "ChapterMark".to_string(),
+        Tree::Heading(_, l, n, _) => format!("Heading {l}\n{}", text(n)),
+        Tree::Bold(_, _) => "Bold".to_string(),
+        Tree::Italic(_, _) => "Italic".to_string(),
+        Tree::BoldItalic(_, _) => "BoldItalic".to_string(),
+        Tree::SmallCaps(_, _) => "SmallCaps".to_string(),
+        Tree::CodeBlock(_, _, _) => "CodeBlock".to_string(),
+        Tree::InlineCode(_, _) => "InlineCode".to_string(),
+        Tree::Quote(_, _) => "Quote".to_string(),
+        Tree::Footnote(_, _) => "Footnote".to_string(),
+        Tree::RightSidenote(_, _) => "RightSidenote".to_string(),
+        Tree::HyperRef(_, _, _) => "HyperRef".to_string(),
+        Tree::DocRef(target, _, _) => format!("DocRef\n{}", text(target)),
+        Tree::Block(_, _) => "Block".to_string(),
+        Tree::Inline(_, _) => "Inline".to_string(),
+        Tree::List(_, l, k, _) => format!("List {l}\n{k:?}"),
+        Tree::ListItem(_, l, _) => format!("ListItem {l}"),
+        Tree::MetaDataBlock(_, _) => "MetaDataBlock".to_string(),
+        Tree::MetaDataItem(k, v, _) => format!("MetaDataItem\n{k} = {}", text(v)),
+        Tree::ImageSizeSpec(_, _, _) => "ImageSizeSpec".to_string(),
+        Tree::Image(_, _, _, _) => "Image".to_string(),
+        Tree::LineBreak(_) => "LineBreak".to_string(),
+        Tree::VSpace(_) => "VSpace".to_string(),
+        Tree::Empty(_) => "Empty".to_string(),
+        Tree::EmDash(_) => "EmDash".to_string(),
+        Tree::EnDash(_) => "EnDash".to_string(),
+        Tree::Table(alignments, _, _, _, _) => format!("Table\n{alignments:?}"),
+        Tree::TableRow(_, _) => "TableRow".to_string(),
+        Tree::BlockQuote(_, _) => "BlockQuote".to_string(),
+        Tree::FootnoteRef(l, _) => format!("FootnoteRef\n{}", text(l)),
+        Tree::FootnoteDef(l, _, _) => format!("FootnoteDef\n{}", text(l)),
+        Tree::LinkRef(l, _, s, _) => format!("LinkRef\n{} {}", text(l), text(s)),
+        Tree::LinkDef(l, _, t, _) => format!("LinkDef\n{} {}", text(l), text(t)),
+        Tree::Include(f, _) => format!("Include\n{}", text(f)),
+    }
+}
+
+/// escapes `s` for use inside a double quoted dot string
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::syntax::{bold, lit};
+
+    #[test]
+    fn nodes_are_numbered_in_visiting_order() {
+        let tree = lit("a").cat(bold(lit("b")));
+        assert_eq!(
+            to_dot(&tree, false),
+            "digraph mato {
+  node [fontname=\"monospace\"];
+  n0 [shape=ellipse, label=\"Inline\"];
+  n1 [shape=ellipse, label=\"Literal\\n\\\"a\\\"\"];
+  n2 [shape=ellipse, label=\"Bold\"];
+  n3 [shape=ellipse, label=\"Literal\\n\\\"b\\\"\"];
+  n0 -> n1;
+  n2 -> n3;
+  n0 -> n2;
+}
+"
+        );
+    }
+
+    #[test]
+    fn every_variant_is_exported() {
+        let (tree, _) = Parser::parse(
+            "---\ntitle: T\n---\n# H\n\n*a* _b_ `c` \"q\" ^(s) \\* --- -- [^n] <http://x.y>\n\n\
+             * [l][r]\n\n> quote\n\n```pic\nbox\n```\n\n| a |\n|---|\n| 1 |\n\n\
+             ![cap](p.png){width=1 height=2}\n\n[^n]: note\n[r]: #h\n",
+        );
+        let dot = to_dot(&tree, false);
+        assert!(dot.starts_with("digraph mato {"));
+        for name in [
+            "MetaDataItem",
+            "Heading",
+            "CodeBlock",
+            "Table",
+            "LinkDef",
+            "FootnoteDef",
+        ] {
+            assert!(dot.contains(name), "{name} missing in {dot}");
+        }
+    }
+
+    #[test]
+    fn inline_runs_are_clustered() {
+        let tree = crate::syntax::block(vec![
+            lit("a"),
+            lit("b").cat(lit("c")),
+            Tree::VSpace(Default::default()),
+            lit("d"),
+        ]);
+        let dot = to_dot(&tree, true);
+        assert!(dot.contains("  subgraph cluster_1 {\n"));
+        assert!(dot.contains("    n2 [shape=ellipse, label=\"Inline\"];"));
+        assert!(dot.contains("    n4 [shape=ellipse"));
+        assert!(dot.contains("  }\n  n5 [shape=box, label=\"VSpace\"];"));
+        assert!(dot.contains("  subgraph cluster_6 {\n"));
+        assert_eq!(dot.matches("subgraph").count(), 2);
+    }
+
+    #[test]
+    fn labels_are_escaped_and_shortened() {
+        let long = "\"quoted\" \\ and a lot more text than fits into a node";
+        let dot = to_dot(&lit(long), false);
+        assert!(
+            dot.contains(r#"label="Literal\n\"\"quoted\" \\ and a lot more text than fits...\"""#)
+        );
+    }
+}
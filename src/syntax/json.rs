@@ -0,0 +1,792 @@
+//! JSON representation of syntax trees, see `doc/json.md` for the schema
+//!
+//! The representation follows pandoc: a document is wrapped into an object
+//! carrying the schema version, each node is an object with its variant name
+//! in `"t"`, its fields in `"c"` and, if known, its source span in `"s"`.
+//! [`to_json`] writes it, [`from_json`] reads it back.
+
+use std::fmt;
+
+use super::{Alignment, DocType, ListKind, Span, Tree};
+
+/// version of the JSON schema, the major part changes on incompatible changes
+pub const SCHEMA_VERSION: [u32; 2] = [1, 1];
+
+/// a problem found while reading JSON, located by byte offset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub message: String,
+    /// byte offset into the JSON text, `None` for errors in the tree structure
+    pub offset: Option<usize>,
+}
+
+impl Error {
+    fn syntax(message: &str, offset: usize) -> Self {
+        Self {
+            message: message.to_string(),
+            offset: Some(offset),
+        }
+    }
+
+    fn schema(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            offset: None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "invalid JSON at byte {offset}: {}", self.message),
+            None => write!(f, "invalid mato JSON: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// writes `tree` as a versioned JSON document
+#[must_use]
+pub fn to_json(tree: &Tree) -> String {
+    let mut out = format!(
+        "{{\"mato-api-version\":[{},{}],\"tree\":",
+        SCHEMA_VERSION[0], SCHEMA_VERSION[1]
+    );
+    write_node(&mut out, tree);
+    out.push('}');
+    out
+}
+
+/// reads a tree written by [`to_json`], or by anyone following the schema
+pub fn from_json(input: &str) -> Result<Tree, Error> {
+    let value = Reader::new(input).document()?;
+    let version = value
+        .get("mato-api-version")
+        .and_then(Value::as_array)
+        .and_then(|v| v.first())
+        .and_then(Value::as_u32)
+        .ok_or_else(|| Error::schema("missing \"mato-api-version\""))?;
+    if version != SCHEMA_VERSION[0] {
+        return Err(Error::schema(&format!(
+            "unsupported schema version {version}, expected {}",
+            SCHEMA_VERSION[0]
+        )));
+    }
+    node(
+        value
+            .get("tree")
+            .ok_or_else(|| Error::schema("missing \"tree\""))?,
+    )
+}
+
+// --- writing ---
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// a field of a node, as written into `"c"`
+enum Field<'a> {
+    Node(&'a Tree),
+    Nodes(&'a [Tree]),
+    Str(&'a str),
+    Char(char),
+    Number(u32),
+    DocType(&'a DocType),
+    ListKind(ListKind),
+    Alignments(&'a [Alignment]),
+}
+
+fn write_field(out: &mut String, field: &Field) {
+    match field {
+        Field::Node(t) => write_node(out, t),
+        Field::Nodes(ts) => {
+            out.push('[');
+            for (i, t) in ts.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_node(out, t);
+            }
+            out.push(']');
+        }
+        Field::Str(s) => write_string(out, s),
+        Field::Char(c) => write_string(out, &c.to_string()),
+        Field::Number(n) => out.push_str(&n.to_string()),
+        Field::DocType(dt) => write_string(out, &format!("{dt:?}")),
+        Field::ListKind(ListKind::Bullet(c)) => {
+            out.push_str("{\"t\":\"Bullet\",\"c\":");
+            write_string(out, &c.to_string());
+            out.push('}');
+        }
+        Field::ListKind(ListKind::Ordered(start, c)) => {
+            out.push_str(&format!("{{\"t\":\"Ordered\",\"c\":[{start},"));
+            write_string(out, &c.to_string());
+            out.push_str("]}");
+        }
+        Field::Alignments(alignments) => {
+            let names: Vec<String> = alignments.iter().map(|a| format!("\"{a:?}\"")).collect();
+            out.push_str(&format!("[{}]", names.join(",")));
+        }
+    }
+}
+
+/// the variant name and the fields of `tree`
+fn fields(tree: &Tree) -> (&'static str, Vec<Field<'_>>) {
+    use Field::{Alignments, Char, Node, Nodes, Number, Str};
+    match tree {
+        Tree::Document(dt, t, _) => ("Document", vec![Field::DocType(dt), Node(t)]),
+        Tree::Paragraph(_) => ("Paragraph", vec![]),
+        Tree::PreformattedLiteral(s, _) => ("PreformattedLiteral", vec![Str(s)]),
+        Tree::Literal(s, _) => ("Literal", vec![Str(s)]),
+        Tree::EscapeLit(s, _) => ("EscapeLit", vec![Str(s)]),
+        Tree::Escaped(c, _) => ("Escaped", vec![Char(*c)]),
+        Tree::DropCap(c, l, _) => ("DropCap", vec![Number((*c).into()), Number((*l).into())]),
+        Tree::Color(t, _) => ("Color", vec![Node(t)]),
+        Tree::ChapterMark(t, _) => ("ChapterMark", vec![Node(t)]),
+        Tree::Heading(t, l, n, _) => ("Heading", vec![Node(t), Number((*l).into()), Str(n)]),
+        Tree::Bold(t, _) => ("Bold", vec![Node(t)]),
+        Tree::Italic(t, _) => ("Italic", vec![Node(t)]),
+        Tree::BoldItalic(t, _) => ("BoldItalic", vec![Node(t)]),
+        Tree::SmallCaps(t, _) => ("SmallCaps", vec![Node(t)]),
+        Tree::CodeBlock(t1, t2, _) => ("CodeBlock", vec![Node(t1), Node(t2)]),
+        Tree::InlineCode(t, _) => ("InlineCode", vec![Node(t)]),
+        Tree::Quote(t, _) => ("Quote", vec![Node(t)]),
+        Tree::Footnote(t, _) => ("Footnote", vec![Node(t)]),
+        Tree::RightSidenote(t, _) => ("RightSidenote", vec![Node(t)]),
+        Tree::HyperRef(t1, t2, _) => ("HyperRef", vec![Node(t1), Node(t2)]),
+        Tree::DocRef(s, t, _) => ("DocRef", vec![Str(s), Node(t)]),
+        Tree::Block(c, _) => ("Block", vec![Nodes(c)]),
+        Tree::Inline(c, _) => ("Inline", vec![Nodes(c)]),
+        Tree::List(t, l, k, _) => (
+            "List",
+            vec![Node(t), Number((*l).into()), Field::ListKind(*k)],
+        ),
+        Tree::ListItem(t, l, _) => ("ListItem", vec![Node(t), Number((*l).into())]),
+        Tree::MetaDataBlock(t, _) => ("MetaDataBlock", vec![Node(t)]),
+        Tree::MetaDataItem(k, v, _) => ("MetaDataItem", vec![Str(k), Str(v)]),
+        Tree::ImageSizeSpec(t1, t2, _) => ("ImageSizeSpec", vec![Node(t1), Node(t2)]),
+        Tree::Image(t1, t2, t3, _) => ("Image", vec![Node(t1), Node(t2), Node(t3)]),
+        Tree::LineBreak(_) => ("LineBreak", vec![]),
+        Tree::VSpace(_) => ("VSpace", vec![]),
+        Tree::Empty(_) => ("Empty", vec![]),
+        Tree::EmDash(_) => ("EmDash", vec![]),
+        Tree::EnDash(_) => ("EnDash", vec![]),
+        Tree::Table(a, h, r, c, _) => ("Table", vec![Alignments(a), Node(h), Nodes(r), Node(c)]),
+        Tree::TableRow(c, _) => ("TableRow", vec![Nodes(c)]),
+        Tree::BlockQuote(t, _) => ("BlockQuote", vec![Node(t)]),
+        Tree::FootnoteRef(l, _) => ("FootnoteRef", vec![Str(l)]),
+        Tree::FootnoteDef(l, t, _) => ("FootnoteDef", vec![Str(l), Node(t)]),
+        Tree::LinkRef(l, t, s, _) => ("LinkRef", vec![Str(l), Node(t), Str(s)]),
+        Tree::LinkDef(l, u, t, _) => ("LinkDef", vec![Str(l), Node(u), Str(t)]),
+        Tree::Include(f, _) => ("Include", vec![Str(f)]),
+    }
+}
+
+fn write_node(out: &mut String, tree: &Tree) {
+    let (name, fields) = fields(tree);
+    out.push_str(&format!("{{\"t\":\"{name}\""));
+    match fields.as_slice() {
+        [] => {}
+        [field] => {
+            out.push_str(",\"c\":");
+            write_field(out, field);
+        }
+        _ => {
+            out.push_str(",\"c\":[");
+            for (i, field) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_field(out, field);
+            }
+            out.push(']');
+        }
+    }
+    let span = tree.span();
+    if !span.is_unknown() {
+        out.push_str(&format!(
+            ",\"s\":[{},{},{},{},{},{}]",
+            span.start, span.end, span.line, span.column, span.end_line, span.end_column
+        ));
+    }
+    out.push('}');
+}
+
+// --- reading ---
+
+/// a parsed JSON value
+#[derive(Debug)]
+enum Value {
+    /// `true`, `false` and `null`, which the schema has no use for
+    Keyword,
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_u32(&self) -> Option<u32> {
+        match self {
+            Value::Number(n) if n.fract() == 0.0 && *n >= 0.0 && *n <= f64::from(u32::MAX) => {
+                Some(*n as u32)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// a recursive descent parser for plain JSON
+struct Reader<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input: input.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, Error> {
+        Err(Error::syntax(message, self.pos))
+    }
+
+    fn document(&mut self) -> Result<Value, Error> {
+        let value = self.value()?;
+        self.skip_whitespace();
+        if self.pos < self.input.len() {
+            return self.error("trailing characters after JSON value");
+        }
+        Ok(value)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.input.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", c as char))
+        }
+    }
+
+    fn keyword(&mut self, word: &str) -> Result<Value, Error> {
+        if self.input[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(Value::Keyword)
+        } else {
+            self.error("unexpected character")
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        self.skip_whitespace();
+        match self.input.get(self.pos) {
+            None => self.error("unexpected end of input"),
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b't') => self.keyword("true"),
+            Some(b'f') => self.keyword("false"),
+            Some(b'n') => self.keyword("null"),
+            Some(c) if *c == b'-' || c.is_ascii_digit() => self.number(),
+            Some(_) => self.error("unexpected character"),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, Error> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.input.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.input.get(self.pos) != Some(&b'"') {
+                return self.error("expected object key");
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.input.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(members));
+                }
+                _ => return self.error("expected ',' or '}'"),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, Error> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.input.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Value::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.input.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(values));
+                }
+                _ => return self.error("expected ',' or ']'"),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, Error> {
+        let start = self.pos;
+        while self.pos < self.input.len()
+            && matches!(
+                self.input[self.pos],
+                b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+            )
+        {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap_or_default();
+        match text.parse() {
+            Ok(n) => Ok(Value::Number(n)),
+            Err(_) => self.error("invalid number"),
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, Error> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok());
+        match digits {
+            Some(n) => {
+                self.pos += 4;
+                Ok(n)
+            }
+            None => self.error("invalid unicode escape"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            match self.input.get(self.pos) {
+                None => return self.error("unterminated string"),
+                Some(b'"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = match self.input.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            let mut code = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.input[self.pos..].starts_with(b"\\u")
+                            {
+                                let escape = self.pos;
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                if (0xdc00..0xe000).contains(&low) {
+                                    code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                                } else {
+                                    // a lone high surrogate; the next escape stands on its own
+                                    self.pos = escape;
+                                }
+                            }
+                            self.pos -= 1;
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return self.error("invalid escape"),
+                    };
+                    self.pos += 1;
+                    bytes.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                Some(c) => {
+                    bytes.push(*c);
+                    self.pos += 1;
+                }
+            }
+        }
+        String::from_utf8(bytes).or_else(|_| self.error("string is not valid UTF-8"))
+    }
+}
+
+/// the fields of node `name`, which holds `count` of them
+fn node_fields<'v>(
+    name: &str,
+    c: Option<&'v Value>,
+    count: usize,
+) -> Result<Vec<&'v Value>, Error> {
+    let fields: Vec<&Value> = match (count, c) {
+        (0, _) => vec![],
+        (1, Some(value)) => vec![value],
+        (_, Some(Value::Array(values))) => values.iter().collect(),
+        _ => vec![],
+    };
+    if fields.len() == count {
+        Ok(fields)
+    } else {
+        Err(Error::schema(&format!("{name} takes {count} field(s)")))
+    }
+}
+
+fn string(name: &str, value: &Value) -> Result<String, Error> {
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| Error::schema(&format!("{name}: expected a string")))
+}
+
+fn character(name: &str, value: &Value) -> Result<char, Error> {
+    let s = string(name, value)?;
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(Error::schema(&format!(
+            "{name}: expected a single character"
+        ))),
+    }
+}
+
+fn small(name: &str, value: &Value) -> Result<u8, Error> {
+    value
+        .as_u32()
+        .and_then(|n| u8::try_from(n).ok())
+        .ok_or_else(|| Error::schema(&format!("{name}: expected a number from 0 to 255")))
+}
+
+fn boxed(value: &Value) -> Result<Box<Tree>, Error> {
+    Ok(Box::new(node(value)?))
+}
+
+fn nodes(name: &str, value: &Value) -> Result<Vec<Tree>, Error> {
+    value
+        .as_array()
+        .ok_or_else(|| Error::schema(&format!("{name}: expected an array of nodes")))?
+        .iter()
+        .map(node)
+        .collect()
+}
+
+fn doc_type(value: &Value) -> Result<DocType, Error> {
+    match value.as_str() {
+        Some("DEFAULT") => Ok(DocType::DEFAULT),
+        Some("CHAPTER") => Ok(DocType::CHAPTER),
+        Some("SLIDES") => Ok(DocType::SLIDES),
+        Some("LETTER") => Ok(DocType::LETTER),
+        _ => Err(Error::schema("Document: unknown document type")),
+    }
+}
+
+fn list_kind(value: &Value) -> Result<ListKind, Error> {
+    let name = value.get("t").and_then(Value::as_str).unwrap_or_default();
+    let c = value.get("c");
+    match name {
+        "Bullet" => {
+            let f = node_fields("Bullet", c, 1)?;
+            Ok(ListKind::Bullet(character("Bullet", f[0])?))
+        }
+        "Ordered" => {
+            let f = node_fields("Ordered", c, 2)?;
+            let start = f[0]
+                .as_u32()
+                .ok_or_else(|| Error::schema("Ordered: expected a start number"))?;
+            Ok(ListKind::Ordered(start, character("Ordered", f[1])?))
+        }
+        _ => Err(Error::schema("List: unknown list kind")),
+    }
+}
+
+fn alignments(value: &Value) -> Result<Vec<Alignment>, Error> {
+    value
+        .as_array()
+        .ok_or_else(|| Error::schema("Table: expected an array of alignments"))?
+        .iter()
+        .map(|a| match a.as_str() {
+            Some("Default") => Ok(Alignment::Default),
+            Some("Left") => Ok(Alignment::Left),
+            Some("Center") => Ok(Alignment::Center),
+            Some("Right") => Ok(Alignment::Right),
+            _ => Err(Error::schema("Table: unknown alignment")),
+        })
+        .collect()
+}
+
+fn span(value: Option<&Value>) -> Result<Span, Error> {
+    let Some(value) = value else {
+        return Ok(Span::default());
+    };
+    let numbers: Option<Vec<usize>> = value
+        .as_array()
+        .map(|values| {
+            values
+                .iter()
+                .map(|value| value.as_u32().map(|n| n as usize))
+                .collect()
+        })
+        .unwrap_or_default();
+    match numbers.as_deref() {
+        Some(&[start, end, line, column, end_line, end_column]) => Ok(Span {
+            start,
+            end,
+            line,
+            column,
+            end_line,
+            end_column,
+        }),
+        _ => Err(Error::schema("a span takes six numbers")),
+    }
+}
+
+fn node(value: &Value) -> Result<Tree, Error> {
+    let name = value
+        .get("t")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::schema("expected a node with a \"t\" member"))?;
+    let c = value.get("c");
+    let s = span(value.get("s"))?;
+    let f = |count| node_fields(name, c, count);
+    Ok(match name {
+        "Document" => {
+            let f = f(2)?;
+            Tree::Document(doc_type(f[0])?, boxed(f[1])?, s)
+        }
+        "Paragraph" => Tree::Paragraph(s),
+        "PreformattedLiteral" => Tree::PreformattedLiteral(string(name, f(1)?[0])?, s),
+        "Literal" => Tree::Literal(string(name, f(1)?[0])?, s),
+        "EscapeLit" => Tree::EscapeLit(string(name, f(1)?[0])?, s),
+        "Escaped" => Tree::Escaped(character(name, f(1)?[0])?, s),
+        "DropCap" => {
+            let f = f(2)?;
+            Tree::DropCap(small(name, f[0])?, small(name, f[1])?, s)
+        }
+        "Color" => Tree::Color(boxed(f(1)?[0])?, s),
+        "ChapterMark" => Tree::ChapterMark(boxed(f(1)?[0])?, s),
+        "Heading" => {
+            let f = f(3)?;
+            Tree::Heading(boxed(f[0])?, small(name, f[1])?, string(name, f[2])?, s)
+        }
+        "Bold" => Tree::Bold(boxed(f(1)?[0])?, s),
+        "Italic" => Tree::Italic(boxed(f(1)?[0])?, s),
+        "BoldItalic" => Tree::BoldItalic(boxed(f(1)?[0])?, s),
+        "SmallCaps" => Tree::SmallCaps(boxed(f(1)?[0])?, s),
+        "CodeBlock" => {
+            let f = f(2)?;
+            Tree::CodeBlock(boxed(f[0])?, boxed(f[1])?, s)
+        }
+        "InlineCode" => Tree::InlineCode(boxed(f(1)?[0])?, s),
+        "Quote" => Tree::Quote(boxed(f(1)?[0])?, s),
+        "Footnote" => Tree::Footnote(boxed(f(1)?[0])?, s),
+        "RightSidenote" => Tree::RightSidenote(boxed(f(1)?[0])?, s),
+        "HyperRef" => {
+            let f = f(2)?;
+            Tree::HyperRef(boxed(f[0])?, boxed(f[1])?, s)
+        }
+        "DocRef" => {
+            let f = f(2)?;
+            Tree::DocRef(string(name, f[0])?, boxed(f[1])?, s)
+        }
+        "Block" => Tree::Block(nodes(name, f(1)?[0])?, s),
+        "Inline" => Tree::Inline(nodes(name, f(1)?[0])?, s),
+        "List" => {
+            let f = f(3)?;
+            Tree::List(boxed(f[0])?, small(name, f[1])?, list_kind(f[2])?, s)
+        }
+        "ListItem" => {
+            let f = f(2)?;
+            Tree::ListItem(boxed(f[0])?, small(name, f[1])?, s)
+        }
+        "MetaDataBlock" => Tree::MetaDataBlock(boxed(f(1)?[0])?, s),
+        "MetaDataItem" => {
+            let f = f(2)?;
+            Tree::MetaDataItem(string(name, f[0])?, string(name, f[1])?, s)
+        }
+        "ImageSizeSpec" => {
+            let f = f(2)?;
+            Tree::ImageSizeSpec(boxed(f[0])?, boxed(f[1])?, s)
+        }
+        "Image" => {
+            let f = f(3)?;
+            Tree::Image(boxed(f[0])?, boxed(f[1])?, boxed(f[2])?, s)
+        }
+        "LineBreak" => Tree::LineBreak(s),
+        "VSpace" => Tree::VSpace(s),
+        "Empty" => Tree::Empty(s),
+        "EmDash" => Tree::EmDash(s),
+        "EnDash" => Tree::EnDash(s),
+        "Table" => {
+            let f = f(4)?;
+            Tree::Table(
+                alignments(f[0])?,
+                boxed(f[1])?,
+                nodes(name, f[2])?,
+                boxed(f[3])?,
+                s,
+            )
+        }
+        "TableRow" => Tree::TableRow(nodes(name, f(1)?[0])?, s),
+        "BlockQuote" => Tree::BlockQuote(boxed(f(1)?[0])?, s),
+        "FootnoteRef" => Tree::FootnoteRef(string(name, f(1)?[0])?, s),
+        "FootnoteDef" => {
+            let f = f(2)?;
+            Tree::FootnoteDef(string(name, f[0])?, boxed(f[1])?, s)
+        }
+        "LinkRef" => {
+            let f = f(3)?;
+            Tree::LinkRef(string(name, f[0])?, boxed(f[1])?, string(name, f[2])?, s)
+        }
+        "LinkDef" => {
+            let f = f(3)?;
+            Tree::LinkDef(string(name, f[0])?, boxed(f[1])?, string(name, f[2])?, s)
+        }
+        "Include" => Tree::Include(string(name, f(1)?[0])?, s),
+        _ => return Err(Error::schema(&format!("unknown node type {name}"))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::syntax::{bold, lit};
+
+    #[test]
+    fn nodes_are_written_pandoc_style() {
+        let tree = lit("a \"b\"").cat(bold(lit("c")));
+        assert_eq!(
+            to_json(&tree),
+            r#"{"mato-api-version":[1,1],"tree":{"t":"Inline","c":[{"t":"Literal","c":"a \"b\""},{"t":"Bold","c":{"t":"Literal","c":"c"}}]}}"#
+        );
+    }
+
+    #[test]
+    fn spans_are_written_when_known() {
+        let (tree, _) = Parser::parse("a");
+        assert_eq!(
+            to_json(&tree),
+            r#"{"mato-api-version":[1,1],"tree":{"t":"Document","c":["DEFAULT",{"t":"Literal","c":"a","s":[0,1,1,1,1,2]}],"s":[0,1,1,1,1,2]}}"#
+        );
+    }
+
+    #[test]
+    fn parsed_documents_round_trip() {
+        let input = "---\ntitle: T\n---\n# H\n\n*a* _b_ `c` \"q\" \\* --- -- [^n] <http://x.y>\n\n\
+                     3. [l][r]\n   - item\n\n> quote\n\n```pic\nbox\n```\n\n| a | b |\n|:--|--:|\n| 1 | 2 |\n\n\
+                     ![cap](p.png)\n\n[^n]: note\n[r]: #h \"title\"\n";
+        let (tree, _) = Parser::parse(input);
+        let read = from_json(&to_json(&tree)).unwrap();
+        assert_eq!(format!("{read:?}"), format!("{tree:?}"));
+        assert_eq!(read.span(), tree.span());
+        assert_eq!(to_json(&read), to_json(&tree));
+    }
+
+    #[test]
+    fn whitespace_escapes_and_missing_spans_are_accepted() {
+        let json = "{ \"mato-api-version\": [1, 2],\n  \"tree\": { \"t\": \"Inline\", \"c\": [\n\
+                    { \"t\": \"Literal\", \"c\": \"\\u00e4\\n\\ud83d\\ude00\" }, { \"t\": \"EmDash\" } ] } }";
+        assert_eq!(
+            format!("{:?}", from_json(json).unwrap()),
+            "Inline([Literal(\"ä\\n😀\"), EmDash])"
+        );
+    }
+
+    #[test]
+    fn unpaired_surrogates_become_replacement_characters() {
+        let literal = |escapes: &str| {
+            let json = format!(
+                "{{\"mato-api-version\":[1,0],\"tree\":{{\"t\":\"Literal\",\"c\":\"{escapes}\"}}}}"
+            );
+            format!("{:?}", from_json(&json).unwrap())
+        };
+        assert_eq!(literal("\\ud800\\u0000"), "Literal(\"\u{fffd}\\0\")");
+        assert_eq!(literal("\\ud800\\ud800x"), "Literal(\"\u{fffd}\u{fffd}x\")");
+        assert_eq!(literal("\\udc00"), "Literal(\"\u{fffd}\")");
+        assert_eq!(literal("\\ud83d\\ude00"), "Literal(\"😀\")");
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let error = |json: &str| from_json(json).unwrap_err().to_string();
+        assert_eq!(
+            error("{\"mato-api-version\":[1,0],\"tree\":{\"t\":\"Literal\"}"),
+            "invalid JSON at byte 48: expected ',' or '}'"
+        );
+        assert_eq!(
+            error("{\"mato-api-version\":[2,0],\"tree\":{\"t\":\"Empty\"}}"),
+            "invalid mato JSON: unsupported schema version 2, expected 1"
+        );
+        assert_eq!(
+            error("{\"mato-api-version\":[1,0],\"tree\":{\"t\":\"Bold\"}}"),
+            "invalid mato JSON: Bold takes 1 field(s)"
+        );
+        assert_eq!(
+            error("{\"mato-api-version\":[1,0],\"tree\":{\"t\":\"Blink\"}}"),
+            "invalid mato JSON: unknown node type Blink"
+        );
+    }
+}
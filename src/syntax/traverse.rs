@@ -0,0 +1,156 @@
+//! generic traversal of the syntax tree
+//!
+//! [`Fold`] rebuilds a tree node by node, [`Visit`] walks it read-only.
+//! Both recurse into every child by default, so an implementation only
+//! overrides the nodes it cares about and hands everything else back to
+//! [`fold_children`] or [`visit_children`].
+
+use super::Tree;
+
+/// transforms an owned tree, bottom-up or top-down as the implementation sees fit
+pub trait Fold {
+    /// folds `tree`, by default by folding its children
+    fn fold(&mut self, tree: Tree) -> Tree {
+        fold_children(self, tree)
+    }
+}
+
+/// walks a tree without changing it
+pub trait Visit {
+    /// visits `tree`, by default by visiting its children
+    fn visit(&mut self, tree: &Tree) {
+        visit_children(self, tree);
+    }
+}
+
+/// folds the tree inside `tree`, reusing its allocation
+fn fold_box<F: Fold + ?Sized>(folder: &mut F, mut tree: Box<Tree>) -> Box<Tree> {
+    *tree = folder.fold(*tree);
+    tree
+}
+
+fn fold_vec<F: Fold + ?Sized>(folder: &mut F, trees: Vec<Tree>) -> Vec<Tree> {
+    trees.into_iter().map(|t| folder.fold(t)).collect()
+}
+
+/// rebuilds `tree` with each of its children passed through `folder`
+pub fn fold_children<F: Fold + ?Sized>(folder: &mut F, tree: Tree) -> Tree {
+    match tree {
+        Tree::Document(dt, t, span) => Tree::Document(dt, fold_box(folder, t), span),
+        Tree::Color(t, span) => Tree::Color(fold_box(folder, t), span),
+        Tree::ChapterMark(t, span) => Tree::ChapterMark(fold_box(folder, t), span),
+        Tree::Heading(t, level, id, span) => Tree::Heading(fold_box(folder, t), level, id, span),
+        Tree::Bold(t, span) => Tree::Bold(fold_box(folder, t), span),
+        Tree::Italic(t, span) => Tree::Italic(fold_box(folder, t), span),
+        Tree::BoldItalic(t, span) => Tree::BoldItalic(fold_box(folder, t), span),
+        Tree::SmallCaps(t, span) => Tree::SmallCaps(fold_box(folder, t), span),
+        Tree::CodeBlock(t1, t2, span) => {
+            Tree::CodeBlock(fold_box(folder, t1), fold_box(folder, t2), span)
+        }
+        Tree::InlineCode(t, span) => Tree::InlineCode(fold_box(folder, t), span),
+        Tree::Quote(t, span) => Tree::Quote(fold_box(folder, t), span),
+        Tree::Footnote(t, span) => Tree::Footnote(fold_box(folder, t), span),
+        Tree::RightSidenote(t, span) => Tree::RightSidenote(fold_box(folder, t), span),
+        Tree::HyperRef(t1, t2, span) => {
+            Tree::HyperRef(fold_box(folder, t1), fold_box(folder, t2), span)
+        }
+        Tree::DocRef(target, t, span) => Tree::DocRef(target, fold_box(folder, t), span),
+        Tree::Block(children, span) => Tree::Block(fold_vec(folder, children), span),
+        Tree::Inline(children, span) => Tree::Inline(fold_vec(folder, children), span),
+        Tree::List(t, level, kind, span) => Tree::List(fold_box(folder, t), level, kind, span),
+        Tree::ListItem(t, level, span) => Tree::ListItem(fold_box(folder, t), level, span),
+        Tree::MetaDataBlock(t, span) => Tree::MetaDataBlock(fold_box(folder, t), span),
+        Tree::ImageSizeSpec(t1, t2, span) => {
+            Tree::ImageSizeSpec(fold_box(folder, t1), fold_box(folder, t2), span)
+        }
+        Tree::Image(t1, t2, t3, span) => Tree::Image(
+            fold_box(folder, t1),
+            fold_box(folder, t2),
+            fold_box(folder, t3),
+            span,
+        ),
+        Tree::Table(alignments, header, rows, caption, span) => Tree::Table(
+            alignments,
+            fold_box(folder, header),
+            fold_vec(folder, rows),
+            fold_box(folder, caption),
+            span,
+        ),
+        Tree::TableRow(cells, span) => Tree::TableRow(fold_vec(folder, cells), span),
+        Tree::BlockQuote(t, span) => Tree::BlockQuote(fold_box(folder, t), span),
+        Tree::FootnoteDef(label, t, span) => Tree::FootnoteDef(label, fold_box(folder, t), span),
+        Tree::LinkRef(label, t, suffix, span) => {
+            Tree::LinkRef(label, fold_box(folder, t), suffix, span)
+        }
+        Tree::LinkDef(label, t, title, span) => {
+            Tree::LinkDef(label, fold_box(folder, t), title, span)
+        }
+        Tree::Paragraph(_)
+        | Tree::PreformattedLiteral(_, _)
+        | Tree::Literal(_, _)
+        | Tree::EscapeLit(_, _)
+        | Tree::Escaped(_, _)
+        | Tree::DropCap(_, _, _)
+        | Tree::MetaDataItem(_, _, _)
+        | Tree::FootnoteRef(_, _)
+        | Tree::Include(_, _)
+        | Tree::LineBreak(_)
+        | Tree::VSpace(_)
+        | Tree::Empty(_)
+        | Tree::EmDash(_)
+        | Tree::EnDash(_) => tree,
+    }
+}
+
+/// passes each of the children of `tree` to `visitor`
+pub fn visit_children<V: Visit + ?Sized>(visitor: &mut V, tree: &Tree) {
+    for child in tree.children() {
+        visitor.visit(child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::{bold, lit};
+
+    struct Upcase;
+
+    impl Fold for Upcase {
+        fn fold(&mut self, tree: Tree) -> Tree {
+            match tree {
+                Tree::Literal(s, span) => lit(&s.to_uppercase()).with_span(span),
+                _ => fold_children(self, tree),
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Literals(Vec<String>);
+
+    impl Visit for Literals {
+        fn visit(&mut self, tree: &Tree) {
+            if let Tree::Literal(s, _) = tree {
+                self.0.push(s.clone());
+            }
+            visit_children(self, tree);
+        }
+    }
+
+    #[test]
+    fn fold_reaches_nested_nodes() {
+        let tree = lit("a").cat(bold(lit("b").cat(bold(lit("c")))));
+        assert_eq!(
+            format!("{:?}", Upcase.fold(tree)),
+            "Inline([Literal(\"A\"), Bold(Inline([Literal(\"B\"), Bold(Literal(\"C\"))]))])"
+        );
+    }
+
+    #[test]
+    fn visit_reaches_nested_nodes() {
+        let tree = lit("a").cat(bold(lit("b").cat(bold(lit("c")))));
+        let mut literals = Literals::default();
+        literals.visit(&tree);
+        assert_eq!(literals.0, ["a", "b", "c"]);
+    }
+}
@@ -2,6 +2,7 @@ use std::env;
 
 use crate::alacritty_cli::AlaCli;
 use crate::wezterm_cli::{WTCli, WTPane};
+use crate::{Error, Result};
 
 const DEFAULT_EDITOR: &str = "nvim";
 
@@ -22,60 +23,62 @@ fn get_editor() -> String {
     }
 }
 
+/// the numeric handle of a WezTerm `pane`
+fn pane_handle(pane: &WTPane) -> Result<usize> {
+    pane.id.parse::<usize>().map_err(|e| {
+        Error::Process(format!("cannot parse WezTerm pane id '{}': {e}", pane.id))
+    })
+}
+
 impl TermCli {
-    pub fn get() -> Self {
+    pub fn get() -> Result<Self> {
         if env::var("WEZTERM_PANE").is_ok() {
-            TermCli::WezTerm
+            Ok(TermCli::WezTerm)
         } else if env::var("KITTY_WINDOW_ID").is_ok() {
-            crate::die!("kitty is not supported");
+            Err(Error::Config("kitty is not supported".to_string()))
         } else if env::var("ALACRITTY_WINDOW_ID").is_ok() {
-            TermCli::Alacritty
+            Ok(TermCli::Alacritty)
         } else {
-            crate::die!("no supported terminal detected (not WezTerm, Alacritty, or Kitty)");
+            Err(Error::Config(
+                "no supported terminal detected (not WezTerm, Alacritty, or Kitty)".to_string(),
+            ))
         }
     }
 
-    pub fn get_active_windows_handle(&self) -> usize {
+    pub fn get_active_windows_handle(&self) -> Result<usize> {
         match self {
-            Self::WezTerm => {
-                let id = WTCli::new().active_pane().id;
-                id.parse::<usize>()
-                    .unwrap_or_else(|e| crate::die!("cannot parse WezTerm pane id '{}': {e}", id))
-            }
+            Self::WezTerm => pane_handle(&WTCli::new().active_pane()?),
             // alacritty has no remote api — the editor runs in the current terminal
-            Self::Alacritty => 0,
-            _ => 0,
+            Self::Alacritty => Ok(0),
+            _ => Ok(0),
         }
     }
     /// opens an editor and blocks on the call
-    pub fn open_editor(&self, source_file: &str) {
+    pub fn open_editor(&self, source_file: &str) -> Result<()> {
         match self {
-            Self::WezTerm | Self::Alacritty => {
-                crate::spawn(vec![&get_editor(), source_file]);
-            }
-            _ => {}
+            Self::WezTerm | Self::Alacritty => crate::spawn(vec![&get_editor(), source_file]),
+            _ => Ok(()),
         }
     }
 
     /// opens an editor in a new window asynchronously an returns a numeric handle to it
-    pub fn spawn_editor(&self, source_file: &str) -> usize {
+    pub fn spawn_editor(&self, source_file: &str) -> Result<usize> {
         match self {
             Self::WezTerm => {
                 let wt_cli = WTCli::new();
-                let editor_pane = wt_cli.spawn(&format!("{} {}", get_editor(), source_file));
-                editor_pane.id.parse::<usize>()
-                    .unwrap_or_else(|e| crate::die!("cannot parse WezTerm pane id '{}': {e}", editor_pane.id))
+                let editor_pane = wt_cli.spawn(&format!("{} {}", get_editor(), source_file))?;
+                pane_handle(&editor_pane)
             }
             Self::Alacritty => {
                 let window = AlaCli::new()
-                    .spawn_window(&format!("{} {}", get_editor(), source_file));
-                window.pid as usize
+                    .spawn_window(&format!("{} {}", get_editor(), source_file))?;
+                Ok(window.pid as usize)
             }
-            _ => 0,
+            _ => Ok(0),
         }
     }
 
-    pub fn exec_matopdf(&self, source_file: &str, lang: &str, timing_chart: bool, t_handle: usize) -> usize {
+    pub fn exec_matopdf(&self, source_file: &str, lang: &str, timing_chart: bool, t_handle: usize) -> Result<usize> {
         let timing_flag = if timing_chart { " --timing-chart" } else { "" };
         match self {
             Self::WezTerm => {
@@ -86,20 +89,21 @@ impl TermCli {
                     .split(format!("matopdf -w -V -l {lang}{timing_flag} {source_file}").as_str())
                     .percent(15)
                     .bottom()
-                    .exec();
-                mato_pane.id.parse::<usize>()
-                    .unwrap_or_else(|e| crate::die!("cannot parse WezTerm pane id '{}': {e}", mato_pane.id))
+                    .exec()?;
+                pane_handle(&mato_pane)
             }
             Self::Alacritty => {
                 let window = AlaCli::new()
-                    .spawn_window(&format!("matopdf -w -V -l {lang}{timing_flag} {source_file}"));
-                window.pid as usize
+                    .spawn_window(&format!("matopdf -w -V -l {lang}{timing_flag} {source_file}"))?;
+                Ok(window.pid as usize)
             }
-            _ => 0,
+            _ => Ok(0),
         }
     }
 
-    pub fn exec_termpdf(&self, target_file: &str, t_handle: usize) -> usize {
+    // the viewer is detached on purpose; xdg-open is never waited on
+    #[allow(clippy::zombie_processes)]
+    pub fn exec_termpdf(&self, target_file: &str, t_handle: usize) -> Result<usize> {
         match self {
             Self::WezTerm => {
                 let editor_pane = WTPane {
@@ -108,54 +112,54 @@ impl TermCli {
                 let termpdf_pane = editor_pane
                     .split(&format!("termpdf.py {target_file}"))
                     .right()
-                    .exec();
-                termpdf_pane.id.parse::<usize>()
-                    .unwrap_or_else(|e| crate::die!("cannot parse WezTerm pane id '{}': {e}", termpdf_pane.id))
+                    .exec()?;
+                pane_handle(&termpdf_pane)
             }
             Self::Alacritty => {
                 std::process::Command::new("xdg-open")
                     .arg(target_file)
                     .spawn()
-                    .unwrap_or_else(|e| crate::die!("failed to open PDF with xdg-open: {e}"));
-                0
+                    .map_err(|e| Error::io("failed to open PDF with xdg-open", e))?;
+                Ok(0)
             }
-            _ => 0,
+            _ => Ok(0),
         }
     }
 
     /// sets the focus to the given pane or window
     /// identified by the given handle
-    pub fn focus(&self, t_handle: usize) {
+    pub fn focus(&self, t_handle: usize) -> Result<()> {
         match self {
             Self::WezTerm => {
                 let pane = WTPane {
                     id: t_handle.to_string(),
                 };
-                pane.activate();
+                pane.activate()
             }
             // no-op for alacritty: focus management is left to the window manager
-            Self::Alacritty => {}
-            _ => {}
+            Self::Alacritty => Ok(()),
+            _ => Ok(()),
         }
     }
 
     /// closes, or kills the pane or window identified
     /// by the given handle
-    pub fn close(&self, t_handle: usize) {
+    pub fn close(&self, t_handle: usize) -> Result<()> {
         match self {
             TermCli::WezTerm => {
                 let pane = WTPane {
                     id: t_handle.to_string(),
                 };
-                pane.kill();
+                pane.kill()
             }
             TermCli::Alacritty => {
                 if t_handle != 0 {
                     crate::alacritty_cli::AlaWindow { pid: t_handle as u32 }.kill();
                 }
+                Ok(())
             }
-            TermCli::Kitty => todo!(),
-            TermCli::Other => todo!(),
+            // nothing has been opened for other terminals, see `spawn_editor`
+            _ => Ok(()),
         }
     }
 }
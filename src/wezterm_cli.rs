@@ -2,13 +2,14 @@
 //! wezterm command line interface
 use std::env;
 
+use crate::{Error, Result};
+
 /// acquires and returns the current directory as a `String``
-fn current_dir() -> String {
-    let dir = env::current_dir()
-        .unwrap_or_else(|e| crate::die!("cannot determine current directory: {e}"));
+fn current_dir() -> Result<String> {
+    let dir = env::current_dir().map_err(|e| Error::io("cannot determine current directory", e))?;
     dir.to_str()
-        .unwrap_or_else(|| crate::die!("current directory path contains invalid UTF-8"))
-        .to_string()
+        .map(str::to_string)
+        .ok_or_else(|| Error::Process("current directory path contains invalid UTF-8".to_string()))
 }
 
 /// handle to wez term cli
@@ -25,22 +26,23 @@ impl WTCli {
     /// This is necessary, as otherwise, the environment and
     /// more specifically the $PATH variable would lack the
     /// user's settings as defined in her ~/.zshrc or equivalent.
-    pub fn spawn(&self, cmd: &str) -> WTPane {
+    pub fn spawn(&self, cmd: &str) -> Result<WTPane> {
         let pane_id = wt_cli_exec(
             [
                 vec!["spawn"],
-                current_dir_vec(&current_dir()),
+                current_dir_vec(&current_dir()?),
                 wrap_in_shell(&shell(), cmd),
             ]
             .concat(),
-        );
-        WTPane { id: pane_id }
+        )?;
+        Ok(WTPane { id: pane_id })
     }
 
-    pub fn active_pane(&self) -> WTPane {
-        let pane_id = env::var("WEZTERM_PANE")
-            .unwrap_or_else(|_| crate::die!("WEZTERM_PANE is not set — is this running inside WezTerm?"));
-        WTPane { id: pane_id }
+    pub fn active_pane(&self) -> Result<WTPane> {
+        let pane_id = env::var("WEZTERM_PANE").map_err(|_| {
+            Error::Config("WEZTERM_PANE is not set — is this running inside WezTerm?".to_string())
+        })?;
+        Ok(WTPane { id: pane_id })
     }
 }
 
@@ -52,7 +54,7 @@ impl Default for WTCli {
 
 /// executes the given `cmd` as a sub process and
 /// returns its output as a string
-fn wt_cli_exec(cmd: Vec<&str>) -> String {
+fn wt_cli_exec(cmd: Vec<&str>) -> Result<String> {
     let cmd = [vec!["wezterm", "cli"], cmd].concat();
     crate::exec(cmd)
 }
@@ -123,18 +125,18 @@ impl SplitOptsBuilder {
         self.opts.iter().map(AsRef::as_ref).collect()
     }
 
-    pub fn exec(&self) -> WTPane {
+    pub fn exec(&self) -> Result<WTPane> {
         let pane_id = wt_cli_exec(
             [
                 vec!["split-pane"],
                 pane_id_vec(&self.id),
-                current_dir_vec(&current_dir()),
+                current_dir_vec(&current_dir()?),
                 self.as_vec(),
                 wrap_in_shell(&shell(), &self.cmd),
             ]
             .concat(),
-        );
-        WTPane { id: pane_id }
+        )?;
+        Ok(WTPane { id: pane_id })
     }
 }
 impl WTPane {
@@ -150,12 +152,12 @@ impl WTPane {
         }
     }
     /// activates the pane identified by `self`, which means, it gets the focus
-    pub fn activate(&self) {
-        wt_cli_exec([vec!["activate-pane"], pane_id_vec(&self.id)].concat());
+    pub fn activate(&self) -> Result<()> {
+        wt_cli_exec([vec!["activate-pane"], pane_id_vec(&self.id)].concat()).map(drop)
     }
 
     /// kill a pane
-    pub fn kill(&self) {
-        wt_cli_exec([vec!["kill-pane"], pane_id_vec(&self.id)].concat());
+    pub fn kill(&self) -> Result<()> {
+        wt_cli_exec([vec!["kill-pane"], pane_id_vec(&self.id)].concat()).map(drop)
     }
 }